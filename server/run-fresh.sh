@@ -112,7 +112,7 @@ if ! grep -q "record_apis:" "$CONFIG_FILE"; then
 fi
 
 # Verify config has OAuth client ID (should be actual value, not <REDACTED>)
-if grep -q 'client_id: "<REDACTED>"' "$CONFIG_FILE"; then
+if grep -qE 'client_id: "<(REDACTED|[A-Z0-9_]+_OAUTH_CLIENT_ID)>"' "$CONFIG_FILE"; then
     echo "Error: OAuth client ID not properly set in config file (still has <REDACTED> placeholder)"
     exit 1
 fi
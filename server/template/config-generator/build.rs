@@ -1,18 +1,14 @@
 fn main() -> std::io::Result<()> {
     println!("cargo::rerun-if-changed=proto");
     
-    let config = prost_build::Config::new();
-    
-    // Use prost-reflect-build to compile proto and generate descriptor pool
-    // This generates the Rust code and file descriptor set
-    // prost-reflect-build automatically adds ReflectMessage derive
-    prost_reflect_build::Builder::new()
-        .descriptor_pool("crate::DESCRIPTOR_POOL")
-        .compile_protos_with_config(
-            config,
-            &["proto/vault.proto"],
-            &["proto"],
-        )?;
+    // Compile the protos and write the file descriptor set the library embeds. The descriptors are
+    // read through `Schema`, so the generated structs don't get a `ReflectMessage` impl that would
+    // have to decode the set infallibly.
+    let mut config = prost_build::Config::new();
+    config.file_descriptor_set_path(
+        std::path::PathBuf::from(std::env::var("OUT_DIR").expect("cargo sets OUT_DIR")).join("file_descriptor_set.bin"),
+    );
+    config.compile_protos(&["proto/config.proto", "proto/vault.proto"], &["proto"])?;
     
     Ok(())
 }
@@ -0,0 +1,2724 @@
+//! Config generation for TrailBase, usable without running the binary
+//!
+//! [`parse_authn_file`] reads an authn file into [`AuthnData`], and [`generate`] fills a
+//! `config.textproto` template from it and builds the matching vault. Neither writes files or exits
+//! the process; failures are returned as [`GenError`]. [`generate_with`] takes a runtime [`Schema`]
+//! and [`GenerateOptions`] for what the binary's `--descriptor-set`, `--vault-key-template` and
+//! `--no-validate` flags control. [`merge_with`] updates an existing config instead of a template,
+//! [`generate_vault_with`] builds the vault alone, and [`merge_vault_with`] updates an existing one.
+//! [`parse_vault`] reads a vault file back into a [`Vault`].
+//! [`encode_vault`] serializes a vault's secrets as textproto or in the binary wire format.
+
+use lazy_static::lazy_static;
+use prost::Message;
+use prost_reflect::text_format::{FormatOptions, ParseError};
+use prost_reflect::{DescriptorPool, DynamicMessage, MapKey, MessageDescriptor, ReflectMessage, SetFieldError, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::sync::LazyLock;
+
+mod structured_authn;
+
+// Include generated protobuf code
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
+
+// File descriptor set written by the build script
+static FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+// Decoded once; a corrupt set is kept as its error, so every caller gets `GenError::Schema`
+static EMBEDDED_SCHEMA: LazyLock<Result<Schema, String>> =
+    LazyLock::new(|| Schema::decode(FILE_DESCRIPTOR_SET, "embedded descriptor set", DEFAULT_VAULT_MESSAGE));
+
+lazy_static! {
+    static ref FORMAT_OPTIONS: FormatOptions = FormatOptions::new().pretty(true).expand_any(true);
+}
+
+/// Why generation failed. Messages name the offending key, field or file; secret values are never
+/// included.
+#[derive(Debug)]
+pub enum GenError {
+    /// The authn file lacks keys `AUTH_MODE` requires or has malformed values. The whole file is
+    /// checked first, so every problem is listed, missing keys in file-reading order.
+    Authn { missing: Vec<String>, invalid: Vec<InvalidValue>, auth_mode: AuthMode },
+    /// A JSON or YAML authn file isn't well-formed or has a field the generator doesn't know. `file`
+    /// names it when several files are layered.
+    AuthnSyntax { format: AuthnFormat, file: Option<String>, line: usize, column: usize, message: String },
+    /// The template file couldn't be read
+    TemplateRead { path: String, source: io::Error },
+    /// The authn file couldn't be read
+    AuthnRead { path: String, source: io::Error },
+    /// The template has an unbalanced `#if`, isn't a valid config, or can't be filled
+    Template(String),
+    /// The descriptor set is corrupt or lacks a message the generator needs
+    Schema(String),
+    /// The vault keys can't be built, e.g. because the vault key template is unusable, or an existing
+    /// vault doesn't parse
+    Vault(String),
+    /// An output message couldn't be serialized
+    Serialize(String),
+    /// A generated output doesn't survive re-parsing against its descriptor
+    Validation(String),
+    /// A check the caller asked for rejected the outputs, e.g. a redaction-policy violation
+    Rejected(String),
+    /// A step around generation failed, e.g. the pre-hook or comparing with an existing config
+    Step(String),
+    /// An output file couldn't be written
+    Write { path: String, source: io::Error },
+    /// Another file couldn't be read or moved, e.g. the descriptor set or an existing output;
+    /// `context` says which, such as `failed to read descriptor set 'schema.pb'`
+    Io { context: String, source: io::Error },
+}
+
+impl std::fmt::Display for GenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenError::Authn { missing, invalid, auth_mode } => {
+                f.write_str("invalid authn file")?;
+                let mut separator = ": ";
+                if !missing.is_empty() {
+                    write!(f, "{}missing: {} (required for AUTH_MODE={}", separator, missing.join(", "), auth_mode)?;
+                    for key in missing {
+                        if key == NO_PROVIDER_KEY {
+                            f.write_str("; no OAuth provider found, set e.g. GOOGLE_OAUTH_CLIENT_ID and GOOGLE_OAUTH_CLIENT_SECRET")?;
+                        } else if let Some(prefix) = key.strip_suffix("_OAUTH_CLIENT_SECRET") {
+                            // Secrets are only required for providers whose client ID is set
+                            write!(f, "; OAuth provider '{}' is incomplete", prefix.to_lowercase())?;
+                        }
+                    }
+                    f.write_str(")")?;
+                    separator = "; ";
+                }
+                if !invalid.is_empty() {
+                    let invalid: Vec<String> = invalid.iter().map(InvalidValue::to_string).collect();
+                    write!(f, "{}invalid: {}", separator, invalid.join(", "))?;
+                }
+                Ok(())
+            }
+            GenError::AuthnSyntax { format, file, line, column, message } => {
+                let file = file.as_ref().map(|file| format!(" '{}'", file)).unwrap_or_default();
+                write!(f, "invalid {} authn file{} at line {}, column {}: {}", format, file, line, column, message)
+            }
+            GenError::TemplateRead { path, source } => write!(f, "failed to read template file '{}': {}", path, source),
+            GenError::AuthnRead { path, source } => write!(f, "failed to read authn file '{}': {}", path, source),
+            GenError::Write { path, source } => write!(f, "failed to write '{}': {}", path, source),
+            GenError::Io { context, source } => write!(f, "{}: {}", context, source),
+            GenError::Template(message)
+            | GenError::Schema(message)
+            | GenError::Vault(message)
+            | GenError::Serialize(message)
+            | GenError::Validation(message)
+            | GenError::Rejected(message)
+            | GenError::Step(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for GenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenError::TemplateRead { source, .. }
+            | GenError::AuthnRead { source, .. }
+            | GenError::Write { source, .. }
+            | GenError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A malformed authn file value
+#[derive(Debug)]
+pub struct InvalidValue {
+    pub key: String,
+    /// The value as written, left out for secret keys and values that failed to unquote or be read
+    pub value: Option<String>,
+    /// What is wrong with it, e.g. `not a port number`
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}='{}' ({})", self.key, value, self.reason),
+            None => write!(f, "{} ({})", self.key, self.reason),
+        }
+    }
+}
+
+/// Default for [`GenerateOptions::placeholder`]
+pub const DEFAULT_PLACEHOLDER: &str = "<REDACTED>";
+
+/// Full name of the vault message, unless [`Schema::load_with`] is given another
+pub const DEFAULT_VAULT_MESSAGE: &str = "config.Vault";
+
+/// Stand-in key listed as missing in [`GenError::Authn`] when `AUTH_MODE` needs OAuth but no provider is set
+pub const NO_PROVIDER_KEY: &str = "<PROVIDER>_OAUTH_CLIENT_ID";
+
+/// Settings for [`generate_with`]; the default is what [`generate`] uses
+pub struct GenerateOptions {
+    /// Vault key name for each OAuth provider's client secret; `{PROVIDER}` is the upper-cased provider name
+    pub vault_key_template: String,
+    /// Vault key for a secret by its authn key (`EMAIL_SMTP_PASSWORD` or
+    /// `<PROVIDER>_OAUTH_CLIENT_SECRET`), overriding the default and `vault_key_template`; see
+    /// [`parse_vault_key_map`]
+    pub vault_keys: BTreeMap<String, String>,
+    /// Sentinel the config carries for values that live in the vault, written into
+    /// `smtp_password` and expected in `client_secret`
+    pub placeholder: String,
+    /// Re-parse both outputs against their descriptors before returning them
+    pub validate: bool,
+    /// Fail when the template and the authn file configure different OAuth providers, instead of
+    /// warning about each one only one side has
+    pub strict_providers: bool,
+    /// Secrets stored encoded in the authn file, by authn key, decoded before they go into the vault
+    pub secret_encodings: BTreeMap<String, SecretEncoding>,
+    /// Comment text written below the `# Auto-generated ...` line of both outputs, each line as a
+    /// `# ` comment, e.g. which template the files came from
+    pub header: Option<String>,
+    /// The existing vault's secrets when rotating: a generated secret whose value differs from the
+    /// one here keeps the old value under `<KEY>_PREVIOUS`, and an unchanged one keeps its existing
+    /// `_PREVIOUS` entry
+    pub previous_secrets: Option<BTreeMap<String, String>>,
+    /// Trim surrounding whitespace from each secret as it goes into the vault, after `@path` files,
+    /// quotes and environment fallbacks are resolved, with a warning for each one that changed
+    pub normalize_secrets: bool,
+    /// Write only the config fields the generator sets (client IDs, provider options, email
+    /// settings and `CONFIG_` values), leaving out every value the template supplies; the template
+    /// still decides which providers and email blocks there are. Ignored by [`merge_with`].
+    pub minimal: bool,
+}
+
+/// Suffix of the vault key a rotated secret's previous value is kept under, see
+/// [`GenerateOptions::previous_secrets`]
+pub const PREVIOUS_SECRET_SUFFIX: &str = "_PREVIOUS";
+
+/// How a secret's authn value is encoded, for [`GenerateOptions::secret_encodings`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretEncoding {
+    /// Standard base64 (`+` and `/`), padding optional
+    Base64,
+    /// Hexadecimal, two digits per byte in either case
+    Hex,
+}
+
+impl std::fmt::Display for SecretEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SecretEncoding::Base64 => "base64",
+            SecretEncoding::Hex => "hex",
+        })
+    }
+}
+
+/// How the vault file is serialized, for [`encode_vault`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VaultFormat {
+    /// Text format with the generator's preface, as in [`GeneratedVault::vault`]
+    #[default]
+    Textproto,
+    /// The protobuf binary wire format, for deployments that load the vault encoded
+    Binary,
+}
+
+impl std::fmt::Display for VaultFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VaultFormat::Textproto => "textproto",
+            VaultFormat::Binary => "binary",
+        })
+    }
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            vault_key_template: DEFAULT_VAULT_KEY_TEMPLATE.to_string(),
+            vault_keys: BTreeMap::new(),
+            placeholder: DEFAULT_PLACEHOLDER.to_string(),
+            validate: true,
+            strict_providers: false,
+            secret_encodings: BTreeMap::new(),
+            header: None,
+            previous_secrets: None,
+            normalize_secrets: false,
+            minimal: false,
+        }
+    }
+}
+
+/// The generated files' contents
+pub struct GeneratedOutput {
+    /// `config.textproto`, with secrets left as the placeholder (`<REDACTED>` by default)
+    pub config: String,
+    /// `secrets.textproto`
+    pub vault: String,
+    /// The vault's secrets by key, as serialized in `vault`
+    pub secrets: BTreeMap<String, String>,
+    /// What filling the template did, in order, for `--verbose`
+    pub notes: Vec<FillNote>,
+    /// Problems that don't stop generation, e.g. an OAuth provider only the template configures
+    pub warnings: Vec<String>,
+    /// The OAuth providers the config enables, by name, sorted
+    pub providers: Vec<String>,
+    /// Authn settings that took their default, see [`AuthnData::applied_defaults`]
+    pub defaults: Vec<String>,
+}
+
+impl GeneratedOutput {
+    /// The vault's secret key names, sorted
+    pub fn vault_keys(&self) -> Vec<&str> {
+        self.secrets.keys().map(String::as_str).collect()
+    }
+}
+
+/// The vault alone, from [`generate_vault_with`]
+pub struct GeneratedVault {
+    /// `secrets.textproto`
+    pub vault: String,
+    /// The vault's secrets by key, as serialized in `vault`
+    pub secrets: BTreeMap<String, String>,
+    /// Problems that don't stop generation, e.g. a secret [`GenerateOptions::normalize_secrets`] trimmed
+    pub warnings: Vec<String>,
+}
+
+/// One step of filling the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FillNote {
+    /// A value was substituted or a block changed, e.g. `set client_id for google`
+    Filled(String),
+    /// An authn value had nowhere to go, or a template value that was overwritten wasn't its
+    /// placeholder
+    Unmatched(String),
+}
+
+/// Generate the config and vault from a template and parsed authn file, using the embedded schema
+/// and default options
+pub fn generate(template: &str, authn: &AuthnData) -> Result<GeneratedOutput, GenError> {
+    let schema = Schema::embedded()?;
+    generate_with(&schema, template, authn, &GenerateOptions::default())
+}
+
+/// Generate the config and vault against `schema`: apply the template's `#if` conditionals, fill
+/// in the authn values through the descriptor pool, drop the blocks `AUTH_MODE` doesn't use, and map
+/// the secrets onto vault keys. Unknown authn keys the template's conditionals don't test are
+/// returned as warnings.
+pub fn generate_with(
+    schema: &Schema,
+    template: &str,
+    authn: &AuthnData,
+    options: &GenerateOptions,
+) -> Result<GeneratedOutput, GenError> {
+    // Keep `#if KEY ... #endif` blocks only when the authn file sets KEY
+    let filtered = apply_template_conditionals(template, &authn.keys).map_err(GenError::Template)?;
+    
+    // Parse the template so a typo is a parse error rather than an unfilled placeholder
+    let config = DynamicMessage::parse_text_format(schema.config.clone(), &filtered)
+        .map_err(|e| {
+            GenError::Template(format!("not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e)))
+        })?;
+    
+    let mut output = fill_and_render(schema, config, authn, options, false)?;
+    // A key nothing reads is most likely a typo, e.g. `EMAIL_SMTP_HOSTNAME`
+    let tested = template_conditional_keys(template);
+    output.warnings.extend(authn.unknown_keys.iter().filter(|key| !tested.contains(key.as_str())).map(|key| {
+        format!("{} is not an authn key the generator reads, and no #if in the template tests it; ignored", key)
+    }));
+    Ok(output)
+}
+
+/// Update an existing config instead of filling a template: `existing` is parsed as the base, only
+/// the OAuth client IDs, email settings and `CONFIG_` values are set from the authn file, and every
+/// other field is kept as it is. Blocks `AUTH_MODE` doesn't use are left alone too. The vault is
+/// built as by [`generate_with`].
+pub fn merge_with(
+    schema: &Schema,
+    existing: &str,
+    authn: &AuthnData,
+    options: &GenerateOptions,
+) -> Result<GeneratedOutput, GenError> {
+    let config = DynamicMessage::parse_text_format(schema.config.clone(), existing)
+        .map_err(|e| {
+            GenError::Step(format!("existing config is not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e)))
+        })?;
+    fill_and_render(schema, config, authn, options, true)
+}
+
+/// Fill the authn values into `config` and build both outputs. When `merge` is set, `config` is a
+/// previously generated config: client IDs are expected to be replaced, and no block is removed.
+fn fill_and_render(
+    schema: &Schema,
+    mut config: DynamicMessage,
+    authn: &AuthnData,
+    options: &GenerateOptions,
+    merge: bool,
+) -> Result<GeneratedOutput, GenError> {
+    let fill_error = if merge { GenError::Step } else { GenError::Template };
+    
+    // A provider on only one side leaves its block unfilled or its credentials unused
+    let mut warnings = if authn.auth_mode.uses_oauth() { provider_mismatches(&config, authn) } else { Vec::new() };
+    if options.strict_providers && !warnings.is_empty() {
+        return Err(GenError::Rejected(format!(
+            "the template and authn file configure different OAuth providers:\n  {}",
+            warnings.join("\n  ")
+        )));
+    }
+    
+    // The template's blocks without their values, for `minimal` to fill the same way
+    let skeleton = (options.minimal && !merge).then(|| message_skeleton(&config));
+    
+    // A scope or redirect URL the schema can't hold would otherwise vanish without a trace
+    if authn.auth_mode.uses_oauth() {
+        warnings.extend(unusable_provider_options(schema, authn));
+    }
+    
+    // Set client IDs and email settings through the descriptor; secrets remain the placeholder
+    // as they will be loaded from vault
+    let mut notes = Vec::new();
+    fill_config(&mut config, &schema.email, authn, &options.placeholder, !merge, &mut notes).map_err(fill_error)?;
+    set_config_values(schema, &mut config, authn, &mut notes)?;
+    
+    // Emit only the auth blocks AUTH_MODE asks for; a merged config keeps the ones it has
+    if !merge {
+        apply_auth_mode(&mut config, &schema.email, authn.auth_mode, &mut notes);
+    }
+    
+    // The full fill above has checked everything and recorded the notes, so this one can't fail
+    if let Some(mut minimal) = skeleton {
+        fill_config(&mut minimal, &schema.email, authn, &options.placeholder, false, &mut Vec::new()).map_err(fill_error)?;
+        set_config_values(schema, &mut minimal, authn, &mut Vec::new())?;
+        apply_auth_mode(&mut minimal, &schema.email, authn.auth_mode, &mut Vec::new());
+        prune_empty_messages(&mut minimal);
+        config = minimal;
+    }
+    
+    let config = format!("{}{}\n", preface(&schema.config, options.header.as_deref()), to_canonical_text(&config));
+    
+    // Re-parse the config so an interpolated value that isn't valid textproto
+    // is reported here rather than by TrailBase at startup
+    if options.validate {
+        validate_config(schema, &config, &options.placeholder)
+            .map_err(|e| GenError::Validation(format!("generated config failed validation: {}", e)))?;
+    }
+    
+    let vault = generate_vault_with(schema, authn, options)?;
+    warnings.extend(vault.warnings);
+    let GeneratedVault { vault, secrets, .. } = vault;
+    let providers = authn.oauth_providers.iter().map(|provider| provider.name.clone()).collect();
+    Ok(GeneratedOutput { config, vault, secrets, notes, warnings, providers, defaults: authn.applied_defaults() })
+}
+
+/// Build only the vault, for when the config isn't regenerated (e.g. after rotating a secret); no
+/// template is needed
+pub fn generate_vault_with(schema: &Schema, authn: &AuthnData, options: &GenerateOptions) -> Result<GeneratedVault, GenError> {
+    // Generate vault file with client secrets and email password (client IDs and email non-secrets are in config file, not vault)
+    let (secrets, warnings) = vault_secrets(authn, options).map_err(GenError::Vault)?;
+    render_vault(schema, secrets, warnings, options)
+}
+
+/// Update an existing vault instead of replacing it: the secrets the authn file provides are
+/// inserted or updated and every other secret in `existing`, such as one added by hand, is kept
+pub fn merge_vault_with(
+    schema: &Schema,
+    existing: &str,
+    authn: &AuthnData,
+    options: &GenerateOptions,
+) -> Result<GeneratedVault, GenError> {
+    let existing = parse_vault_with(schema, existing).map_err(|e| GenError::Step(format!("existing vault: {}", e)))?;
+    let mut secrets: BTreeMap<String, String> = existing.secrets.into_iter().collect();
+    let (generated, warnings) = vault_secrets(authn, options).map_err(GenError::Vault)?;
+    secrets.extend(generated);
+    render_vault(schema, secrets, warnings, options)
+}
+
+/// Parse a vault textproto, such as [`GeneratedOutput::vault`], into a [`Vault`] using the embedded
+/// schema. Comments, including the generator's preface line, are ignored by the text format parser.
+pub fn parse_vault(text: &str) -> Result<Vault, GenError> {
+    parse_vault_with(&Schema::embedded()?, text)
+}
+
+/// Parse a vault textproto against `schema`'s vault message, e.g. one from a runtime descriptor set
+pub fn parse_vault_with(schema: &Schema, text: &str) -> Result<Vault, GenError> {
+    DynamicMessage::parse_text_format(schema.vault.clone(), text)
+        .map_err(|e| GenError::Vault(format!("not a valid {} message: {}", schema.vault.full_name(), redact_parse_error(&e))))?
+        .transcode_to::<Vault>()
+        .map_err(|e| GenError::Vault(format!("{} does not decode as the built-in Vault: {}", schema.vault.full_name(), e)))
+}
+
+/// Serialize `secrets` as the vault file, re-parsing it if `options` asks for validation
+fn render_vault(
+    schema: &Schema,
+    secrets: BTreeMap<String, String>,
+    warnings: Vec<String>,
+    options: &GenerateOptions,
+) -> Result<GeneratedVault, GenError> {
+    let vault = generate_vault_file(schema, &secrets, options.header.as_deref())
+        .map_err(|e| GenError::Serialize(format!("failed to generate vault file: {}", e)))?;
+    
+    if options.validate {
+        validate_vault(schema, &vault, &secrets)
+            .map_err(|e| GenError::Validation(format!("generated vault failed validation: {}", e)))?;
+    }
+    Ok(GeneratedVault { vault, secrets, warnings })
+}
+
+/// The message descriptors the generator works with, all resolved from one descriptor pool:
+/// the one embedded at build time, or a `--descriptor-set` file supplied at runtime
+#[derive(Clone)]
+pub struct Schema {
+    pub config: MessageDescriptor,
+    pub vault: MessageDescriptor,
+    pub email: MessageDescriptor,
+    pub oauth_provider: MessageDescriptor,
+}
+
+impl Schema {
+    /// The schema embedded at build time, decoded on first use. A corrupt embedded set is a
+    /// `GenError::Schema`, not a panic.
+    pub fn embedded() -> Result<Schema, GenError> {
+        EMBEDDED_SCHEMA.clone().map_err(GenError::Schema)
+    }
+    
+    /// Load from the `--descriptor-set` file if given, else from the embedded descriptor set
+    pub fn load(descriptor_set_path: Option<&str>) -> Result<Schema, GenError> {
+        Schema::load_with(descriptor_set_path, DEFAULT_VAULT_MESSAGE)
+    }
+    
+    /// [`Schema::load`], with the vault read from the message named `vault_message` (e.g.
+    /// `config.v2.Vault`) for proto versions that moved or renamed it
+    pub fn load_with(descriptor_set_path: Option<&str>, vault_message: &str) -> Result<Schema, GenError> {
+        match descriptor_set_path {
+            Some(path) => {
+                let bytes = fs::read(path)
+                    .map_err(|source| GenError::Io { context: format!("failed to read descriptor set '{}'", path), source })?;
+                Schema::decode(&bytes, &format!("descriptor set '{}'", path), vault_message).map_err(GenError::Schema)
+            }
+            None if vault_message == DEFAULT_VAULT_MESSAGE => Schema::embedded(),
+            None => Schema::decode(FILE_DESCRIPTOR_SET, "embedded descriptor set", vault_message).map_err(GenError::Schema),
+        }
+    }
+    
+    /// Decode an encoded `FileDescriptorSet` and look up every message the generator needs.
+    /// `source` names the descriptor set in error messages.
+    fn decode(bytes: &[u8], source: &str, vault_message: &str) -> Result<Schema, String> {
+        let pool = DescriptorPool::decode(bytes).map_err(|e| {
+            format!(
+                "failed to decode {}: {} (the descriptor set is corrupt or for an incompatible proto version)",
+                source, e
+            )
+        })?;
+        let message = |name: &str| {
+            pool.get_message_by_name(name).ok_or_else(|| {
+                format!(
+                    "message '{}' not found in {} (the descriptor set was built from a different or incompatible proto version)",
+                    name, source
+                )
+            })
+        };
+        // A moved vault is easy to find: it is the message with a `secrets` string map
+        let vault = message(vault_message).map_err(|e| {
+            let candidates: Vec<String> = pool
+                .all_messages()
+                .filter(|message| message.get_field_by_name("secrets").is_some_and(|field| is_string_map(&field)))
+                .map(|message| message.full_name().to_string())
+                .collect();
+            match candidates.as_slice() {
+                [] => e,
+                _ => format!("{}; messages with a map<string, string> secrets field: {}", e, candidates.join(", ")),
+            }
+        })?;
+        let schema = Schema {
+            config: message("config.Config")?,
+            vault,
+            email: message("config.EmailConfig")?,
+            oauth_provider: message("config.OAuthProviderConfig")?,
+        };
+        schema.check_vault_secrets(source)?;
+        schema.check_email_fields(source)?;
+        Ok(schema)
+    }
+    
+    /// Email blocks are filled by mapping each required `EMAIL_<FIELD>` key onto the `EmailConfig`
+    /// field of the same name; fail fast if the descriptor set renamed or retyped one rather than
+    /// writing a config with the value missing
+    fn check_email_fields(&self, source: &str) -> Result<(), String> {
+        for spec in EMAIL_KEY_FIELDS.iter().filter(|spec| spec.required) {
+            let name = email_field_name(spec.name);
+            let field = self.email.get_field_by_name(&name).ok_or_else(|| {
+                format!(
+                    "message '{}' in {} has no '{}' field for EMAIL_{} (the descriptor set was built from a different or incompatible proto version)",
+                    self.email.full_name(),
+                    source,
+                    name,
+                    spec.name
+                )
+            })?;
+            let expected = if spec.name == "SMTP_PORT" { "an integer" } else { "a string" };
+            let matches = !field.is_list()
+                && !field.is_map()
+                && match field.kind() {
+                    prost_reflect::Kind::String => spec.name != "SMTP_PORT",
+                    kind => spec.name == "SMTP_PORT" && integer_value(&kind, 0).is_some(),
+                };
+            if !matches {
+                return Err(format!(
+                    "field '{}' in {} is {}, expected {} for EMAIL_{} (the descriptor set was built from an incompatible proto version)",
+                    field.full_name(),
+                    source,
+                    describe_field_type(&field),
+                    expected,
+                    spec.name
+                ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Vault generation and parsing assume `secrets` is a `map<string, string>`; fail fast if the
+    /// descriptor set disagrees rather than writing a vault TrailBase can't read
+    fn check_vault_secrets(&self, source: &str) -> Result<(), String> {
+        let field = self.vault.get_field_by_name("secrets").ok_or_else(|| {
+            format!(
+                "message '{}' in {} has no 'secrets' field (expected map<string, string>)",
+                self.vault.full_name(),
+                source
+            )
+        })?;
+        if !is_string_map(&field) {
+            return Err(format!(
+                "field '{}' in {} is {}, expected map<string, string> (the descriptor set was built from an incompatible proto version)",
+                field.full_name(),
+                source,
+                describe_field_type(&field)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `field` is a `map<string, string>`
+fn is_string_map(field: &prost_reflect::FieldDescriptor) -> bool {
+    field.is_map()
+        && field.kind().as_message().is_some_and(|entry| {
+            entry.map_entry_key_field().kind() == prost_reflect::Kind::String
+                && entry.map_entry_value_field().kind() == prost_reflect::Kind::String
+        })
+}
+
+/// Render a field's type the way it is written in a .proto file, e.g. `map<string, int32>`
+fn describe_field_type(field: &prost_reflect::FieldDescriptor) -> String {
+    fn kind_name(kind: &prost_reflect::Kind) -> String {
+        use prost_reflect::Kind;
+        match kind {
+            Kind::Double => "double".to_string(),
+            Kind::Float => "float".to_string(),
+            Kind::Int32 => "int32".to_string(),
+            Kind::Int64 => "int64".to_string(),
+            Kind::Uint32 => "uint32".to_string(),
+            Kind::Uint64 => "uint64".to_string(),
+            Kind::Sint32 => "sint32".to_string(),
+            Kind::Sint64 => "sint64".to_string(),
+            Kind::Fixed32 => "fixed32".to_string(),
+            Kind::Fixed64 => "fixed64".to_string(),
+            Kind::Sfixed32 => "sfixed32".to_string(),
+            Kind::Sfixed64 => "sfixed64".to_string(),
+            Kind::Bool => "bool".to_string(),
+            Kind::String => "string".to_string(),
+            Kind::Bytes => "bytes".to_string(),
+            Kind::Message(message) => message.full_name().to_string(),
+            Kind::Enum(enumeration) => enumeration.full_name().to_string(),
+        }
+    }
+    
+    match field.kind().as_message().filter(|_| field.is_map()) {
+        Some(entry) => format!(
+            "map<{}, {}>",
+            kind_name(&entry.map_entry_key_field().kind()),
+            kind_name(&entry.map_entry_value_field().kind())
+        ),
+        None if field.is_list() => format!("repeated {}", kind_name(&field.kind())),
+        None => kind_name(&field.kind()),
+    }
+}
+
+/// Vault key TrailBase reads an OAuth provider's client secret from
+pub const DEFAULT_VAULT_KEY_TEMPLATE: &str = "TRAIL_AUTH_OAUTH_PROVIDERS_{PROVIDER}_CLIENT_SECRET";
+
+/// Vault key TrailBase reads the SMTP password from
+pub const DEFAULT_EMAIL_PASSWORD_VAULT_KEY: &str = "TRAIL_EMAIL_SMTP_PASSWORD";
+
+const PROVIDER_PLACEHOLDER: &str = "{PROVIDER}";
+
+/// Parse a vault key map: `AUTHN_KEY=VAULT_KEY` lines, e.g. `EMAIL_SMTP_PASSWORD=TRAIL_SMTP_PASSWORD`,
+/// naming the vault key each secret is written under. Blank lines and `#` comments are skipped.
+pub fn parse_vault_key_map(content: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut vault_keys = BTreeMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((authn_key, vault_key)) = line.split_once('=') else {
+            return Err(format!("line {}: expected AUTHN_KEY=VAULT_KEY", index + 1));
+        };
+        let (authn_key, vault_key) = (authn_key.trim(), vault_key.trim());
+        if !is_secret_authn_key(authn_key) {
+            return Err(format!(
+                "line {}: '{}' is not a secret authn key (EMAIL_SMTP_PASSWORD, EMAIL_<NAME>_SMTP_PASSWORD or <PROVIDER>_OAUTH_CLIENT_SECRET)",
+                index + 1,
+                authn_key
+            ));
+        }
+        if vault_key.is_empty() {
+            return Err(format!("line {}: empty vault key for {}", index + 1, authn_key));
+        }
+        if vault_keys.insert(authn_key.to_string(), vault_key.to_string()).is_some() {
+            return Err(format!("line {}: {} is mapped more than once", index + 1, authn_key));
+        }
+    }
+    Ok(vault_keys)
+}
+
+/// Read an `@path` authn value from its file, with its line endings normalized to `\n` and one
+/// trailing newline dropped
+fn read_value_file(path: &str) -> Result<String, String> {
+    if path.is_empty() {
+        return Err("'@' must be followed by a file path".to_string());
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read '@{}': {}", path, e))?;
+    let mut content = authn_lines(&content).collect::<Vec<_>>().join("\n");
+    if content.ends_with('\n') {
+        content.pop();
+    }
+    Ok(content)
+}
+
+/// The lines of an authn file. `\n`, `\r\n` and a lone `\r` all end a line, so a file saved with
+/// Windows or classic Mac OS line endings leaves no `\r` in its values.
+pub fn authn_lines(content: &str) -> impl Iterator<Item = &str> {
+    content.split('\n').flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'))
+}
+
+/// Read the value part of a `KEY=value` line, dotenv style. The contents of `'...'` are taken
+/// fully literally (no escapes, no `${}` interpolation, `#` and spaces kept); only a comment may
+/// follow the closing quote. In an unquoted value a `#` after whitespace starts a comment, and the
+/// rest is trimmed, so `abc==` and `a#b` are kept whole but `abc # note` is `abc`.
+fn unquote_authn_value(value: &str) -> Result<&str, String> {
+    match value.trim_start().strip_prefix('\'') {
+        Some(quoted) => match quoted.split_once('\'') {
+            Some((inner, rest)) => {
+                let rest = rest.trim_start();
+                if rest.is_empty() || rest.starts_with('#') {
+                    Ok(inner)
+                } else if rest.contains('\'') {
+                    Err("single quote inside a single-quoted value".to_string())
+                } else {
+                    Err("unexpected text after the closing quote".to_string())
+                }
+            }
+            None => Err("unterminated single quote".to_string()),
+        },
+        None => {
+            let comment = value
+                .char_indices()
+                .find(|&(index, c)| c == '#' && value[..index].ends_with(char::is_whitespace))
+                .map_or(value.len(), |(index, _)| index);
+            Ok(value[..comment].trim())
+        }
+    }
+}
+
+/// The keys the template's `#if KEY` directives test
+fn template_conditional_keys(template: &str) -> BTreeSet<&str> {
+    template.lines().filter_map(|line| line.trim().strip_prefix("#if ")).map(str::trim).collect()
+}
+
+/// Include the lines between `#if KEY` and `#endif` only when `KEY` is in `keys`, dropping the
+/// directive lines themselves. Blocks may nest; an unmatched `#if` or `#endif` is an error.
+fn apply_template_conditionals(template: &str, keys: &BTreeSet<String>) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    // (line number, key, included) for each open `#if`
+    let mut open: Vec<(usize, &str, bool)> = Vec::new();
+    
+    for (index, line) in template.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        let directive = line.trim();
+        if let Some(key) = directive.strip_prefix("#if ").map(str::trim) {
+            if key.is_empty() {
+                return Err(format!("line {}: '#if' requires an authn key", line_number));
+            }
+            let parent_included = open.last().is_none_or(|&(_, _, included)| included);
+            open.push((line_number, key, parent_included && keys.contains(key)));
+        } else if directive == "#endif" {
+            if open.pop().is_none() {
+                return Err(format!("line {}: '#endif' without matching '#if'", line_number));
+            }
+        } else if open.last().is_none_or(|&(_, _, included)| included) {
+            output.push_str(line);
+        }
+    }
+    
+    match open.last() {
+        Some((line_number, key, _)) => Err(format!("line {}: '#if {}' is never closed with '#endif'", line_number, key)),
+        None => Ok(output),
+    }
+}
+
+/// Which auth-related config blocks are emitted, selected by the authn file's `AUTH_MODE` key
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthMode {
+    Email,
+    OAuth,
+    Both,
+}
+
+impl AuthMode {
+    pub fn uses_oauth(self) -> bool {
+        self != AuthMode::Email
+    }
+    
+    pub fn uses_email(self) -> bool {
+        self != AuthMode::OAuth
+    }
+}
+
+impl std::fmt::Display for AuthMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthMode::Email => "email",
+            AuthMode::OAuth => "oauth",
+            AuthMode::Both => "both",
+        })
+    }
+}
+
+/// One OAuth provider's credentials, from its `<PROVIDER>_OAUTH_CLIENT_ID` and
+/// `<PROVIDER>_OAUTH_CLIENT_SECRET` keys. At least one is required unless `AUTH_MODE=email`.
+pub struct OAuthProvider {
+    /// Lowercased key prefix, e.g. `github`
+    pub name: String,
+    pub client_id: String,
+    /// `None` for a public client (`<PROVIDER>_OAUTH_PKCE=true`), which has no secret to vault
+    pub client_secret: Option<String>,
+    /// From `<PROVIDER>_OAUTH_SCOPES`, split on commas; `None` leaves the template's value
+    pub scopes: Option<Vec<String>>,
+    /// From `<PROVIDER>_OAUTH_REDIRECT_URL`; `None` leaves the template's value
+    pub redirect_url: Option<String>,
+}
+
+/// A provider's values by field, gathered from its `<PROVIDER>_OAUTH_*` keys
+#[derive(Default)]
+struct ProviderKeys {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    scopes: Option<String>,
+    redirect_url: Option<String>,
+    pkce: Option<String>,
+}
+
+impl ProviderKeys {
+    /// The value for a field of [`OAUTH_KEY_FIELDS`], e.g. `CLIENT_SECRET`
+    fn get(&self, field: &str) -> Option<&str> {
+        match field {
+            "CLIENT_ID" => self.client_id.as_deref(),
+            "CLIENT_SECRET" => self.client_secret.as_deref(),
+            "SCOPES" => self.scopes.as_deref(),
+            "REDIRECT_URL" => self.redirect_url.as_deref(),
+            "PKCE" => self.pkce.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// SMTP settings, required unless `AUTH_MODE=oauth`
+pub struct EmailSettings {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub sender_name: String,
+    pub sender_address: String,
+    /// `EMAIL_SMTP_SECURITY`, or the default for the port; `None` if neither says
+    pub smtp_security: Option<SmtpSecurity>,
+}
+
+impl EmailSettings {
+    /// The value for a text field of [`EMAIL_KEY_FIELDS`], e.g. `SMTP_HOST`
+    fn text(&self, field: &str) -> Option<&str> {
+        match field {
+            "SMTP_HOST" => Some(&self.smtp_host),
+            "SMTP_USERNAME" => Some(&self.smtp_username),
+            "SMTP_PASSWORD" => Some(&self.smtp_password),
+            "SENDER_NAME" => Some(&self.sender_name),
+            "SENDER_ADDRESS" => Some(&self.sender_address),
+            _ => None,
+        }
+    }
+}
+
+/// An email identity besides the main one, e.g. a marketing sender from `EMAIL_MARKETING_*` keys
+pub struct NamedEmail {
+    /// Lowercased key infix, e.g. `marketing`
+    pub name: String,
+    pub settings: EmailSettings,
+}
+
+/// How the SMTP connection is secured, from `EMAIL_SMTP_SECURITY=starttls|tls|none`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS
+    StartTls,
+    /// TLS from the first byte ("implicit TLS", usually port 465)
+    Tls,
+    /// No encryption
+    None,
+}
+
+impl SmtpSecurity {
+    /// The name used in the authn file; enum values in the config end with it upper-cased
+    pub fn name(self) -> &'static str {
+        match self {
+            SmtpSecurity::StartTls => "starttls",
+            SmtpSecurity::Tls => "tls",
+            SmtpSecurity::None => "none",
+        }
+    }
+    
+    /// The conventional mode for a submission port: 465 for implicit TLS, 587 for STARTTLS
+    pub fn default_for_port(port: u16) -> Option<SmtpSecurity> {
+        match port {
+            465 => Some(SmtpSecurity::Tls),
+            587 => Some(SmtpSecurity::StartTls),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SmtpSecurity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// SMTP ports in common use: relay, implicit TLS, submission, and the usual alternative submission port
+const COMMON_SMTP_PORTS: [u16; 4] = [25, 465, 587, 2525];
+
+/// `EmailConfig` field that receives the SMTP security mode, if the schema defines it
+const SMTP_SECURITY_FIELD: &str = "smtp_encryption";
+
+/// The `EmailConfig` field an `EMAIL_<FIELD>` key fills: the field in lower case, e.g. `smtp_host`
+/// for `SMTP_HOST`, except the security mode, which goes into [`SMTP_SECURITY_FIELD`]
+fn email_field_name(key_field: &str) -> String {
+    match key_field {
+        "SMTP_SECURITY" => SMTP_SECURITY_FIELD.to_string(),
+        _ => key_field.to_lowercase(),
+    }
+}
+
+/// `value` as a value of integer `kind`, or `None` if `kind` isn't an integer type
+fn integer_value(kind: &prost_reflect::Kind, value: u16) -> Option<Value> {
+    use prost_reflect::Kind;
+    Some(match kind {
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(value.into()),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(value.into()),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(value.into()),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(value.into()),
+        _ => return None,
+    })
+}
+
+/// `OAuthProviderConfig` fields that receive a provider's scopes and redirect URL, if the schema
+/// defines them
+const OAUTH_SCOPES_FIELD: &str = "scopes";
+const OAUTH_REDIRECT_URL_FIELD: &str = "redirect_url";
+
+/// How an authn file is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthnFormat {
+    /// `KEY=value` lines
+    KeyValue,
+    Json,
+    /// The block-mapping subset of YAML: nested `key: value` lines, comments and quoted scalars
+    Yaml,
+    /// A JSON array of `{provider, client_id, client_secret, redirect_url, scopes}` objects, as
+    /// `--providers-json` reads; never picked by [`AuthnFormat::from_path`]
+    ProvidersJson,
+}
+
+impl AuthnFormat {
+    /// The format an authn file's extension names: `.json`, `.yaml` or `.yml`, else `KEY=value`
+    pub fn from_path(path: &str) -> AuthnFormat {
+        let extension = std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => AuthnFormat::Json,
+            Some("yaml" | "yml") => AuthnFormat::Yaml,
+            _ => AuthnFormat::KeyValue,
+        }
+    }
+}
+
+impl std::fmt::Display for AuthnFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthnFormat::KeyValue => "KEY=value",
+            AuthnFormat::Json => "JSON",
+            AuthnFormat::Yaml => "YAML",
+            AuthnFormat::ProvidersJson => "providers JSON",
+        })
+    }
+}
+
+/// Structure to hold all parsed authentication and email configuration
+pub struct AuthnData {
+    pub auth_mode: AuthMode,
+    /// Sorted by name
+    pub oauth_providers: Vec<OAuthProvider>,
+    pub email: Option<EmailSettings>,
+    /// Further email identities from `EMAIL_<NAME>_*` keys, sorted by name; each fills the
+    /// template's `<name>_email` block
+    pub named_emails: Vec<NamedEmail>,
+    /// Other config fields to set, from `CONFIG_<field path>` keys: `(path, value)` in file order,
+    /// e.g. `("server.site_url", "https://example.com")`
+    pub config_values: Vec<(String, String)>,
+    /// Every key the file defines, including ones the generator doesn't read, plus the keys taken
+    /// from the environment; template `#if KEY` conditionals test these
+    pub keys: BTreeSet<String>,
+    /// Keys the file defines that the generator doesn't read, in file order; only `#if`
+    /// conditionals can use them, so [`generate_with`] warns about the ones its template doesn't test
+    pub unknown_keys: Vec<String>,
+    /// Suspicious values that don't stop generation, e.g. an unusual SMTP port
+    pub warnings: Vec<String>,
+}
+
+impl AuthnData {
+    /// Settings the file leaves out that took their default, as `KEY=value`, e.g. `AUTH_MODE=both`
+    /// or an SMTP security mode picked from the port
+    pub fn applied_defaults(&self) -> Vec<String> {
+        let mut defaults = Vec::new();
+        if !self.keys.contains("AUTH_MODE") {
+            defaults.push(format!("AUTH_MODE={}", self.auth_mode));
+        }
+        let emails = self.email.iter().map(|email| ("EMAIL_".to_string(), email));
+        let named = self.named_emails.iter().map(|named| (format!("EMAIL_{}_", named.name.to_uppercase()), &named.settings));
+        for (prefix, email) in emails.chain(named) {
+            let key = format!("{}SMTP_SECURITY", prefix);
+            if let Some(security) = email.smtp_security.filter(|_| !self.keys.contains(&key)) {
+                defaults.push(format!("{}={}", key, security));
+            }
+        }
+        defaults
+    }
+}
+
+/// Parse the .authn file and extract OAuth provider credentials and email configuration.
+/// Only the credentials needed by `AUTH_MODE` (default `both`) are required; the others are ignored.
+pub fn parse_authn_file(content: &str) -> Result<AuthnData, GenError> {
+    parse_authn_file_with_env(content, std::iter::empty())
+}
+
+/// Prefix of the authn keys that set a config field by path, e.g. `CONFIG_server.site_url`
+pub(crate) const CONFIG_KEY_PREFIX: &str = "CONFIG_";
+
+/// Whether the generator reads `key` from an authn file (and so from the environment). `CONFIG_`
+/// keys are left out: an unrelated `CONFIG_*` variable would fail as an unknown field path.
+fn is_authn_key(key: &str) -> bool {
+    GENERAL_KEYS.iter().any(|spec| spec.name == key)
+        || split_email_key(key).is_some()
+        || OAUTH_KEY_FIELDS.iter().any(|spec| {
+            key.strip_suffix(spec.name)
+                .and_then(|rest| rest.strip_suffix("_OAUTH_"))
+                .is_some_and(|prefix| !prefix.is_empty())
+        })
+}
+
+/// [`parse_authn_file`], with `env` (e.g. `std::env::vars()`) supplying keys the file doesn't set.
+/// The file always wins; only keys the generator reads are taken from `env`, with their values
+/// used exactly as given (no unquoting, trimming or `@path` indirection). A file value `@path` is
+/// read from that file, minus one trailing newline.
+pub fn parse_authn_file_with_env(content: &str, env: impl IntoIterator<Item = (String, String)>) -> Result<AuthnData, GenError> {
+    parse_authn_as(content, AuthnFormat::KeyValue, env)
+}
+
+/// Parse an authn file written in `format`; see [`parse_authn_file_with_env`] for `env`. JSON and
+/// YAML files are read into the same keys as the `KEY=value` format, so they are checked the same way.
+pub fn parse_authn_as(
+    content: &str,
+    format: AuthnFormat,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<AuthnData, GenError> {
+    authn_from_entries(authn_entries(content, format)?, env)
+}
+
+/// How [`parse_authn_layers_with`] combines a list-valued key that several files set. The only
+/// list-valued keys are `<PROVIDER>_OAUTH_SCOPES`; every other key is a scalar, and a later file's
+/// value always replaces it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// A later file's list replaces the earlier one, as for scalars
+    #[default]
+    Override,
+    /// A later file's items are added after the earlier ones, skipping items already listed
+    Append,
+}
+
+/// Whether `key` holds a comma-separated list that [`MergeStrategy::Append`] combines
+fn is_list_key(key: &str) -> bool {
+    key.strip_suffix("_OAUTH_SCOPES").is_some_and(|prefix| !prefix.is_empty())
+}
+
+/// Parse several authn files as one, each `(name, content, format)`: a key set by a later file
+/// replaces an earlier file's value, and keys only an earlier file sets are kept. The merged keys
+/// are checked as a whole, so a required key may come from any file. `name` is only used to say
+/// which file has a syntax error; see [`parse_authn_file_with_env`] for `env`.
+pub fn parse_authn_layers(
+    layers: &[(&str, &str, AuthnFormat)],
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<AuthnData, GenError> {
+    parse_authn_layers_with(layers, MergeStrategy::Override, env)
+}
+
+/// [`parse_authn_layers`], with `strategy` deciding whether a later file's list-valued keys replace
+/// or extend the earlier files' lists
+pub fn parse_authn_layers_with(
+    layers: &[(&str, &str, AuthnFormat)],
+    strategy: MergeStrategy,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<AuthnData, GenError> {
+    let mut merged: Vec<AuthnEntry> = Vec::new();
+    for &(name, content, format) in layers {
+        let entries = authn_entries(content, format).map_err(|e| match e {
+            GenError::AuthnSyntax { format, line, column, message, .. } => {
+                GenError::AuthnSyntax { format, file: Some(name.to_string()), line, column, message }
+            }
+            other => other,
+        })?;
+        for entry in entries {
+            match merged.iter_mut().find(|(key, _, _)| *key == entry.0) {
+                Some(existing) if strategy == MergeStrategy::Append && is_list_key(&entry.0) => append_list_entry(existing, entry),
+                Some(existing) => *existing = entry,
+                None => merged.push(entry),
+            }
+        }
+    }
+    authn_from_entries(merged, env)
+}
+
+/// Add `later`'s list items to `existing`'s, resolving `@path` values first so the files' contents
+/// are joined. A value that failed to unquote or read replaces the other, so its error is reported.
+fn append_list_entry<'a>(existing: &mut AuthnEntry<'a>, later: AuthnEntry<'a>) {
+    let resolve = |(_, value, raw): &AuthnEntry| match value {
+        Ok(value) if !raw.starts_with('\'') => value.strip_prefix('@').map_or_else(|| Ok(value.clone()), read_value_file),
+        other => other.clone(),
+    };
+    let (Ok(earlier), Ok(added)) = (resolve(existing), resolve(&later)) else {
+        *existing = later;
+        return;
+    };
+    let mut items: Vec<&str> = earlier.split(',').map(str::trim).filter(|item| !item.is_empty()).collect();
+    for item in added.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    *existing = (later.0, Ok(items.join(",")), "");
+}
+
+/// An authn entry: key, unquoted value or why unquoting failed, and raw value (empty where the
+/// format has no quoting)
+type AuthnEntry<'a> = (String, Result<String, String>, &'a str);
+
+/// Read an authn file's entries in file order, without checking them
+fn authn_entries(content: &str, format: AuthnFormat) -> Result<Vec<AuthnEntry<'_>>, GenError> {
+    let parsed = match format {
+        AuthnFormat::KeyValue => {
+            let mut entries = Vec::new();
+            for line in authn_lines(content) {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                
+                if let Some((key, value)) = line.split_once('=') {
+                    entries.push((key.trim().to_string(), unquote_authn_value(value).map(str::to_string), value.trim()));
+                }
+            }
+            return Ok(entries);
+        }
+        AuthnFormat::Json => structured_authn::parse_json(content),
+        AuthnFormat::Yaml => structured_authn::parse_yaml(content),
+        AuthnFormat::ProvidersJson => structured_authn::parse_providers_json(content),
+    };
+    let entries = parsed.map_err(|e| GenError::AuthnSyntax { format, file: None, line: e.line, column: e.column, message: e.message })?;
+    Ok(entries.into_iter().map(|(key, value)| (key, Ok(value), "")).collect())
+}
+
+/// Check authn entries, with keys from `env` appended for those the entries don't set
+fn authn_from_entries(
+    mut entries: Vec<AuthnEntry>,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<AuthnData, GenError> {
+    let mut auth_mode = AuthMode::Both;
+    let mut keys: BTreeSet<String> = entries.iter().map(|(key, _, _)| key.clone()).collect();
+    // Provider values by key prefix, e.g. `GITHUB`
+    let mut oauth_keys: BTreeMap<String, ProviderKeys> = BTreeMap::new();
+    // Field values by identity name (empty for the unnamed `email` block), then by field, e.g. `SMTP_HOST`
+    let mut email_keys: BTreeMap<String, BTreeMap<&'static str, String>> = BTreeMap::new();
+    let mut invalid = Vec::new();
+    let mut warnings = Vec::new();
+    let mut unknown_keys = Vec::new();
+    let mut config_values = Vec::new();
+    let mut auth_mode_valid = true;
+    
+    // `@path` values name the file holding the value; single-quoted values stay literal
+    for (_, value, raw) in &mut entries {
+        if let Some(path) = value.as_ref().ok().and_then(|value| value.strip_prefix('@')).filter(|_| !raw.starts_with('\'')) {
+            *value = read_value_file(path);
+        }
+    }
+    
+    // Environment fallbacks, sorted so the result doesn't depend on the environment's order
+    let mut fallbacks: Vec<(String, String)> = env.into_iter().filter(|(key, _)| is_authn_key(key) && !keys.contains(key)).collect();
+    fallbacks.sort();
+    for (key, value) in fallbacks {
+        keys.insert(key.clone());
+        entries.push((key, Ok(value), ""));
+    }
+    
+    for (key, value, raw) in &entries {
+        let key = key.as_str();
+        // A value that fails to unquote is kept raw so its key doesn't also count as missing
+        let value = match value {
+            Ok(value) => value.as_str(),
+            Err(reason) => {
+                invalid.push(InvalidValue { key: key.to_string(), value: None, reason: reason.clone() });
+                raw
+            }
+        };
+        match key {
+            "AUTH_MODE" => {
+                auth_mode = match value {
+                    "email" => AuthMode::Email,
+                    "oauth" => AuthMode::OAuth,
+                    "both" => AuthMode::Both,
+                    _ => {
+                        invalid.push(InvalidValue {
+                            key: key.to_string(),
+                            value: Some(value.to_string()),
+                            reason: "must be one of email, oauth, both".to_string(),
+                        });
+                        auth_mode_valid = false;
+                        continue;
+                    }
+                };
+            }
+            _ => {
+                let provider = |suffix| key.strip_suffix(suffix).filter(|prefix| !prefix.is_empty());
+                if let Some((name, field)) = split_email_key(key) {
+                    email_keys.entry(name.to_string()).or_default().insert(field, value.to_string());
+                } else if let Some(prefix) = provider("_OAUTH_CLIENT_ID") {
+                    oauth_keys.entry(prefix.to_string()).or_default().client_id = Some(value.to_string());
+                } else if let Some(prefix) = provider("_OAUTH_CLIENT_SECRET") {
+                    oauth_keys.entry(prefix.to_string()).or_default().client_secret = Some(value.to_string());
+                } else if let Some(prefix) = provider("_OAUTH_SCOPES") {
+                    oauth_keys.entry(prefix.to_string()).or_default().scopes = Some(value.to_string());
+                } else if let Some(prefix) = provider("_OAUTH_REDIRECT_URL") {
+                    oauth_keys.entry(prefix.to_string()).or_default().redirect_url = Some(value.to_string());
+                } else if let Some(prefix) = provider("_OAUTH_PKCE") {
+                    oauth_keys.entry(prefix.to_string()).or_default().pkce = Some(value.to_string());
+                } else if let Some(path) = key.strip_prefix(CONFIG_KEY_PREFIX).filter(|path| !path.is_empty()) {
+                    config_values.push((path.to_string(), value.to_string()));
+                } else if !unknown_keys.iter().any(|unknown| unknown == key) {
+                    unknown_keys.push(key.to_string());
+                }
+            }
+        }
+    }
+    
+    // Collect every missing key before failing so one run reports them all. Which keys are required
+    // is unknown if AUTH_MODE itself is invalid.
+    let mut missing = Vec::new();
+    let mut required = |value: Option<String>, key: &str| {
+        if value.is_none() && auth_mode_valid {
+            missing.push(key.to_string());
+        }
+        value.unwrap_or_default()
+    };
+    
+    let mut oauth_providers = Vec::new();
+    if auth_mode.uses_oauth() {
+        for (prefix, provider) in oauth_keys {
+            // A secret without its client ID can't enable a provider; the binary reports these as orphans
+            let Some(client_id) = provider.client_id.clone() else { continue };
+            let pkce = match provider.pkce.as_deref() {
+                None | Some("false") => false,
+                Some("true") => true,
+                Some(other) => {
+                    invalid.push(InvalidValue {
+                        key: format!("{}_OAUTH_PKCE", prefix),
+                        value: Some(other.to_string()),
+                        reason: "must be true or false".to_string(),
+                    });
+                    false
+                }
+            };
+            // A public client has no secret, so one that is set anyway is a mistake either way
+            let secret_key = format!("{}_OAUTH_CLIENT_SECRET", prefix);
+            if pkce && provider.client_secret.is_some() {
+                invalid.push(InvalidValue {
+                    key: secret_key.clone(),
+                    value: None,
+                    reason: format!("must not be set when {}_OAUTH_PKCE=true, since a PKCE client has no secret", prefix),
+                });
+            }
+            for spec in OAUTH_KEY_FIELDS.iter().filter(|spec| !spec.allow_empty && (!pkce || spec.name != "CLIENT_SECRET")) {
+                if provider.get(spec.name).is_some_and(|value| value.trim().is_empty()) {
+                    invalid.push(blank_value(format!("{}_OAUTH_{}", prefix, spec.name)));
+                }
+            }
+            let client_secret = if pkce { None } else { Some(required(provider.client_secret, &secret_key)) };
+            let scopes = provider.scopes.and_then(|scopes| match parse_scopes(&scopes) {
+                Ok(parsed) => Some(parsed),
+                Err(reason) => {
+                    invalid.push(InvalidValue { key: format!("{}_OAUTH_SCOPES", prefix), value: Some(scopes), reason });
+                    None
+                }
+            });
+            let redirect_url = provider.redirect_url.filter(|url| {
+                let valid = url.starts_with("https://") || url.starts_with("http://");
+                if !valid {
+                    invalid.push(InvalidValue {
+                        key: format!("{}_OAUTH_REDIRECT_URL", prefix),
+                        value: Some(url.clone()),
+                        reason: "must be an http:// or https:// URL".to_string(),
+                    });
+                }
+                valid
+            });
+            oauth_providers.push(OAuthProvider { name: prefix.to_lowercase(), client_id, client_secret, scopes, redirect_url });
+        }
+        if oauth_providers.is_empty() {
+            required(None, NO_PROVIDER_KEY);
+        }
+    }
+    let mut email = None;
+    let mut named_emails = Vec::new();
+    if auth_mode.uses_email() {
+        let fields = email_keys.remove("").unwrap_or_default();
+        email = Some(email_settings("EMAIL_", fields, &mut required, &mut invalid, &mut warnings));
+        for (name, fields) in email_keys {
+            let settings = email_settings(&format!("EMAIL_{}_", name), fields, &mut required, &mut invalid, &mut warnings);
+            named_emails.push(NamedEmail { name: name.to_lowercase(), settings });
+        }
+    }
+    if !missing.is_empty() || !invalid.is_empty() {
+        return Err(GenError::Authn { missing, invalid, auth_mode });
+    }
+    
+    Ok(AuthnData { auth_mode, oauth_providers, email, named_emails, config_values, keys, unknown_keys, warnings })
+}
+
+/// A value that is set but blank although its key doesn't allow that. Only secrets are strict, and
+/// their values are never shown.
+fn blank_value(key: String) -> InvalidValue {
+    InvalidValue { key, value: None, reason: "must not be empty".to_string() }
+}
+
+/// Split a comma-separated scope list, e.g. `openid, email`. Whitespace inside a scope usually
+/// means a space-separated list was pasted, so it is rejected rather than sent as one scope.
+fn parse_scopes(scopes: &str) -> Result<Vec<String>, String> {
+    let scopes: Vec<String> = scopes.split(',').map(str::trim).filter(|scope| !scope.is_empty()).map(String::from).collect();
+    if scopes.is_empty() {
+        return Err("must list at least one scope".to_string());
+    }
+    if let Some(scope) = scopes.iter().find(|scope| scope.contains(char::is_whitespace)) {
+        return Err(format!("scopes are comma-separated, but '{}' contains whitespace", scope));
+    }
+    Ok(scopes)
+}
+
+/// One email identity's settings from its `<prefix><FIELD>` values (e.g. `EMAIL_SMTP_HOST` for
+/// prefix `EMAIL_`), recording missing and malformed keys
+fn email_settings(
+    prefix: &str,
+    mut fields: BTreeMap<&'static str, String>,
+    required: &mut impl FnMut(Option<String>, &str) -> String,
+    invalid: &mut Vec<InvalidValue>,
+    warnings: &mut Vec<String>,
+) -> EmailSettings {
+    let key = |field: &str| format!("{}{}", prefix, field);
+    for spec in EMAIL_KEY_FIELDS.iter().filter(|spec| !spec.allow_empty) {
+        if fields.get(spec.name).is_some_and(|value| value.trim().is_empty()) {
+            invalid.push(blank_value(key(spec.name)));
+        }
+    }
+    let smtp_host = fields.remove("SMTP_HOST");
+    if let Some(Err(reason)) = smtp_host.as_deref().filter(|host| !host.is_empty()).map(|host| check_smtp_host(host, &key("SMTP_PORT"))) {
+        invalid.push(InvalidValue { key: key("SMTP_HOST"), value: smtp_host.clone(), reason });
+    }
+    let smtp_host = required(smtp_host, &key("SMTP_HOST"));
+    let smtp_port = fields.remove("SMTP_PORT");
+    let port_set = smtp_port.is_some();
+    let smtp_port = required(smtp_port, &key("SMTP_PORT"));
+    let parsed_port = smtp_port.parse::<u16>();
+    match parsed_port {
+        Err(_) if port_set => invalid.push(InvalidValue {
+            key: key("SMTP_PORT"),
+            value: Some(smtp_port),
+            reason: "not a port number".to_string(),
+        }),
+        // Port 0 parses but nothing can connect to it
+        Ok(0) => invalid.push(InvalidValue {
+            key: key("SMTP_PORT"),
+            value: Some(smtp_port),
+            reason: "must be between 1 and 65535".to_string(),
+        }),
+        Ok(port) if !COMMON_SMTP_PORTS.contains(&port) => warnings.push(format!(
+            "{}={} is not a common SMTP port ({}); check that it is right",
+            key("SMTP_PORT"),
+            port,
+            COMMON_SMTP_PORTS.map(|port| port.to_string()).join(", ")
+        )),
+        _ => {}
+    }
+    let smtp_security = match fields.remove("SMTP_SECURITY").as_deref() {
+        Some("starttls") => Some(SmtpSecurity::StartTls),
+        Some("tls") => Some(SmtpSecurity::Tls),
+        Some("none") => Some(SmtpSecurity::None),
+        Some(other) => {
+            invalid.push(InvalidValue {
+                key: key("SMTP_SECURITY"),
+                value: Some(other.to_string()),
+                reason: "must be one of starttls, tls, none".to_string(),
+            });
+            None
+        }
+        None => parsed_port.clone().ok().and_then(SmtpSecurity::default_for_port),
+    };
+    let sender_address = fields.remove("SENDER_ADDRESS");
+    if let Some(Err(reason)) = sender_address.as_deref().map(check_email_address) {
+        invalid.push(InvalidValue { key: key("SENDER_ADDRESS"), value: sender_address.clone(), reason });
+    }
+    EmailSettings {
+        smtp_host,
+        smtp_port: parsed_port.unwrap_or_default(),
+        smtp_security,
+        smtp_username: required(fields.remove("SMTP_USERNAME"), &key("SMTP_USERNAME")),
+        smtp_password: required(fields.remove("SMTP_PASSWORD"), &key("SMTP_PASSWORD")),
+        sender_name: required(fields.remove("SENDER_NAME"), &key("SENDER_NAME")),
+        sender_address: required(sender_address, &key("SENDER_ADDRESS")),
+    }
+}
+
+/// Check that an SMTP host is a hostname or an IPv4 or IPv6 address, so a pasted URL or `host:port`
+/// is caught here rather than when TrailBase first connects. Only the syntax is checked, with no DNS
+/// lookup, so this works offline. Non-ASCII labels are accepted for internationalized names.
+fn check_smtp_host(host: &str, port_key: &str) -> Result<(), String> {
+    if let Some((scheme, _)) = host.split_once("://") {
+        return Err(format!("must be a hostname or IP address, not a URL; remove the '{}://' scheme", scheme));
+    }
+    if host.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("hostname must not contain whitespace".to_string());
+    }
+    let unbracketed = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    if unbracketed.parse::<std::net::Ipv6Addr>().is_ok() {
+        return Ok(());
+    }
+    if let Some((_, port)) = host.rsplit_once(':') {
+        if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("hostname must not include a port; set {} instead", port_key));
+        }
+        return Err("not a hostname or IP address".to_string());
+    }
+    if host.contains('/') {
+        return Err("hostname must not contain a path".to_string());
+    }
+    // A trailing dot marks a fully qualified name
+    let name = host.strip_suffix('.').unwrap_or(host);
+    let labels: Vec<&str> = name.split('.').collect();
+    if labels.iter().all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_digit())) {
+        return match name.parse::<std::net::Ipv4Addr>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("'{}' is not a valid IPv4 address", name)),
+        };
+    }
+    if name.len() > 253 {
+        return Err("hostname is longer than 253 characters".to_string());
+    }
+    for label in labels {
+        if label.is_empty() {
+            return Err(format!("hostname '{}' has an empty label", host));
+        }
+        if label.len() > 63 {
+            return Err(format!("hostname label '{}' is longer than 63 characters", label));
+        }
+        if let Some(c) = label.chars().find(|c| !c.is_alphanumeric() && *c != '-') {
+            return Err(format!("hostname must not contain '{}'", c));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!("hostname label '{}' must not start or end with '-'", label));
+        }
+    }
+    Ok(())
+}
+
+/// Check that an address looks like `local-part@domain` with a dot in the domain, so a typo such as
+/// `noreply@mail` is caught here rather than when TrailBase first sends mail. Deliberately loose:
+/// anything before the last `@` is accepted as the local part (quoted parts and `+` tags included),
+/// as is any non-ASCII domain or an `[address literal]`.
+fn check_email_address(address: &str) -> Result<(), String> {
+    if address.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("email address must not contain whitespace".to_string());
+    }
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return Err("not an email address, expected local-part@domain".to_string());
+    };
+    if local.is_empty() {
+        return Err("email address has nothing before the '@'".to_string());
+    }
+    if domain.starts_with('[') && domain.ends_with(']') && domain.len() > 2 {
+        return Ok(());
+    }
+    if !domain.contains('.') {
+        return Err(format!("email domain '{}' has no '.'", domain));
+    }
+    if domain.split('.').any(str::is_empty) {
+        return Err(format!("email domain '{}' has an empty label", domain));
+    }
+    Ok(())
+}
+
+/// A key the authn file can set, as listed by [`authn_file_template`]
+pub(crate) struct AuthnKeySpec {
+    /// The whole key, or the field after `<PROVIDER>_OAUTH_` or `EMAIL_`
+    pub(crate) name: &'static str,
+    /// Placeholder value for the template
+    example: &'static str,
+    description: &'static str,
+    /// Whether the key must be set when its block is used
+    required: bool,
+    /// Whether a blank value is accepted; a blank secret would only fail at login time
+    allow_empty: bool,
+}
+
+/// The keys besides the OAuth and email ones
+const GENERAL_KEYS: [AuthnKeySpec; 1] = [AuthnKeySpec {
+    name: "AUTH_MODE",
+    example: "both",
+    description: "Which auth blocks to emit: email, oauth or both (default both)",
+    required: false,
+    allow_empty: true,
+}];
+
+/// The fields of each provider's `<PROVIDER>_OAUTH_<FIELD>` keys
+pub(crate) const OAUTH_KEY_FIELDS: [AuthnKeySpec; 5] = [
+    AuthnKeySpec {
+        name: "CLIENT_ID",
+        example: "your-client-id",
+        description: "OAuth client ID, from the provider's developer console",
+        required: true,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "CLIENT_SECRET",
+        example: "your-client-secret",
+        description: "OAuth client secret; written to the vault, never to the config",
+        required: true,
+        allow_empty: false,
+    },
+    AuthnKeySpec {
+        name: "SCOPES",
+        example: "openid,email,profile",
+        description: "Comma-separated scopes to request; the template's are kept if unset",
+        required: false,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "REDIRECT_URL",
+        example: "https://example.com/api/auth/v1/oauth/google/callback",
+        description: "Redirect (callback) URL registered with the provider; the template's is kept if unset",
+        required: false,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "PKCE",
+        example: "true",
+        description: "true for a public (PKCE) client, which has no CLIENT_SECRET and no vault secret",
+        required: false,
+        allow_empty: true,
+    },
+];
+
+/// The fields every email identity has, as `EMAIL_<FIELD>` or `EMAIL_<NAME>_<FIELD>` keys
+pub(crate) const EMAIL_KEY_FIELDS: [AuthnKeySpec; 7] = [
+    AuthnKeySpec {
+        name: "SMTP_HOST",
+        example: "smtp.example.com",
+        description: "SMTP server host name",
+        required: true,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "SMTP_PORT",
+        example: "587",
+        description: "SMTP server port, usually 587 or 465",
+        required: true,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "SMTP_SECURITY",
+        example: "starttls",
+        description: "How the connection is secured: starttls, tls or none (defaults from the port)",
+        required: false,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "SMTP_USERNAME",
+        example: "mailer@example.com",
+        description: "SMTP login",
+        required: true,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "SMTP_PASSWORD",
+        example: "your-smtp-password",
+        description: "SMTP password; written to the vault, never to the config",
+        required: true,
+        allow_empty: false,
+    },
+    AuthnKeySpec {
+        name: "SENDER_NAME",
+        example: "TrailBase",
+        description: "Display name on sent mail",
+        required: true,
+        allow_empty: true,
+    },
+    AuthnKeySpec {
+        name: "SENDER_ADDRESS",
+        example: "noreply@example.com",
+        description: "From address on sent mail",
+        required: true,
+        allow_empty: true,
+    },
+];
+
+/// Provider used for the OAuth keys in [`authn_file_template`]
+const EXAMPLE_PROVIDER: &str = "GOOGLE";
+
+/// A commented authn file listing every key the generator reads, with placeholder values. Optional
+/// keys are commented out. It comes from the same key lists the parser uses, so it can't drift.
+pub fn authn_file_template() -> String {
+    let mut out = String::from(
+        "# Authn file for config-generator: replace the placeholder values and pass this file as\n\
+         # <authn-file>. Lines starting with # are comments; optional keys are commented out.\n",
+    );
+    // Each spec's key is its name after the section's prefix
+    let mut section = |heading: &str, prefix: &str, specs: &[AuthnKeySpec]| {
+        out.push_str(&format!("\n# {}\n", heading));
+        for spec in specs {
+            let comment_out = if spec.required { "" } else { "# " };
+            out.push_str(&format!("# {}\n{}{}{}={}\n", spec.description, comment_out, prefix, spec.name, spec.example));
+        }
+    };
+    section("General", "", &GENERAL_KEYS);
+    section(
+        "OAuth providers (AUTH_MODE oauth or both): one set per provider, e.g. GITHUB_OAUTH_* for GitHub",
+        &format!("{}_OAUTH_", EXAMPLE_PROVIDER),
+        &OAUTH_KEY_FIELDS,
+    );
+    section("Email (AUTH_MODE email or both); further identities use EMAIL_<NAME>_* keys", "EMAIL_", &EMAIL_KEY_FIELDS);
+    out.push_str(&format!(
+        "\n# Other config fields\n# Any non-secret config field, by its path in the config message\n# {}server.site_url=https://example.com\n",
+        CONFIG_KEY_PREFIX
+    ));
+    out
+}
+
+/// The OAuth providers the schema's `OAuthProviderId` enum names, each with its
+/// `<PROVIDER>_OAUTH_<FIELD>` keys and whether they are required. The client secret's line names
+/// the vault key `vault_key_template` gives it. Like [`authn_file_template`], it comes from the
+/// key list the parser uses.
+pub fn provider_listing(schema: &Schema, vault_key_template: &str) -> Result<String, GenError> {
+    let providers = schema
+        .oauth_provider
+        .get_field_by_name("provider_id")
+        .and_then(|field| field.kind().as_enum().cloned())
+        .ok_or_else(|| GenError::Schema(format!("{} has no provider_id enum field", schema.oauth_provider.full_name())))?;
+    let mut out = String::new();
+    for provider in providers.values().filter(|value| value.number() != 0) {
+        out.push_str(&format!("{}
+", provider.name()));
+        for spec in &OAUTH_KEY_FIELDS {
+            let key = format!("{}_OAUTH_{}", provider.name(), spec.name);
+            let required = match (spec.required, spec.name) {
+                (true, "CLIENT_SECRET") => "required unless PKCE",
+                (true, _) => "required",
+                (false, _) => "optional",
+            };
+            out.push_str(&format!("  {} ({})", key, required));
+            if spec.name == "CLIENT_SECRET" {
+                out.push_str(&format!("; written to the vault as {}", vault_key_template.replace(PROVIDER_PLACEHOLDER, provider.name())));
+            }
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// Split an email key into its identity name (empty for the unnamed `email` block) and field, e.g.
+/// `EMAIL_MARKETING_SMTP_HOST` into `MARKETING` and `SMTP_HOST`
+fn split_email_key(key: &str) -> Option<(&str, &'static str)> {
+    let rest = key.strip_prefix("EMAIL_")?;
+    EMAIL_KEY_FIELDS.iter().map(|spec| spec.name).find_map(|field| {
+        if rest == field {
+            return Some(("", field));
+        }
+        let name = rest.strip_suffix(field)?.strip_suffix('_')?;
+        (!name.is_empty()).then_some((name, field))
+    })
+}
+
+/// Whether an authn key's value ends up in the vault: `EMAIL_SMTP_PASSWORD`,
+/// `EMAIL_<NAME>_SMTP_PASSWORD` or `<PROVIDER>_OAUTH_CLIENT_SECRET`
+pub fn is_secret_authn_key(key: &str) -> bool {
+    split_email_key(key).is_some_and(|(_, field)| field == "SMTP_PASSWORD")
+        || key.strip_suffix("_OAUTH_CLIENT_SECRET").is_some_and(|prefix| !prefix.is_empty())
+}
+
+/// Fill the authn values into the parsed template: each provider's `client_id` in the
+/// `auth.oauth_providers` entry keyed by its name, and the SMTP settings in the template's `email`
+/// block. Template blocks the authn file has no values for are left as they are, and no block is
+/// added that the template doesn't have; authn values left unused that way are noted as unmatched.
+/// Named email identities are the exception and fail without their `<name>_email` block. With
+/// `expect_placeholders`, a client ID that wasn't its placeholder is noted before it is overwritten.
+fn fill_config(
+    config: &mut DynamicMessage,
+    email_descriptor: &MessageDescriptor,
+    authn_data: &AuthnData,
+    placeholder: &str,
+    expect_placeholders: bool,
+    notes: &mut Vec<FillNote>,
+) -> Result<(), String> {
+    let set = |message: &mut DynamicMessage, path: &str, field: &str, value: Value| {
+        message
+            .try_set_field_by_name(field, value)
+            .map_err(|e| format!("cannot set {}.{}: {}", path, field, redact_set_error(&e)))
+    };
+    
+    if config.has_field_by_name("auth") {
+        if let Some(Value::Message(auth)) = config.get_field_by_name_mut("auth") {
+            if auth.has_field_by_name("oauth_providers") {
+                if let Some(Value::Map(entries)) = auth.get_field_by_name_mut("oauth_providers") {
+                    for provider in &authn_data.oauth_providers {
+                        if let Some(Value::Message(entry)) = entries.get_mut(&MapKey::String(provider.name.clone())) {
+                            let path = format!("auth.oauth_providers[\"{}\"]", provider.name);
+                            if expect_placeholders {
+                                note_overwritten_client_id(entry, &path, &provider.name, placeholder, notes);
+                            }
+                            set(entry, &path, "client_id", Value::String(provider.client_id.clone()))?;
+                            notes.push(FillNote::Filled(format!("set client_id for {}", provider.name)));
+                            // TrailBase would otherwise look for a vault secret that was never written
+                            if provider.client_secret.is_none() && entry.has_field_by_name("client_secret") {
+                                entry.clear_field_by_name("client_secret");
+                                notes.push(FillNote::Filled(format!("removed client_secret for {} (PKCE client)", provider.name)));
+                            }
+                            fill_provider_options(entry, &path, provider, notes)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    if authn_data.email.is_some() && !config.has_field_by_name("email") {
+        notes.push(FillNote::Unmatched("the template has no email block, so the EMAIL_* settings were not used".to_string()));
+    }
+    if let Some(email) = authn_data.email.as_ref().filter(|_| config.has_field_by_name("email")) {
+        if let Some(Value::Message(block)) = config.get_field_by_name_mut("email") {
+            fill_email_block(block, "email", "EMAIL_", email, &authn_data.keys, placeholder, notes)?;
+        }
+    }
+    
+    // Further identities each go into their own `<name>_email` block, which must exist: unlike a
+    // missing `email` block, a declared identity with nowhere to go is a mistake in the template
+    for named in &authn_data.named_emails {
+        let block_name = format!("{}_email", named.name);
+        let prefix = format!("EMAIL_{}_", named.name.to_uppercase());
+        let is_email_block = config
+            .descriptor()
+            .get_field_by_name(&block_name)
+            .is_some_and(|field| field.kind().as_message().is_some_and(|message| message == email_descriptor));
+        if !is_email_block {
+            return Err(format!(
+                "email config '{}' ({}* keys) needs a {} field of type {} in the schema; pass a --descriptor-set that defines it",
+                named.name,
+                prefix,
+                block_name,
+                email_descriptor.full_name()
+            ));
+        }
+        if !config.has_field_by_name(&block_name) {
+            return Err(format!("email config '{}' ({}* keys) has no {} block in the template", named.name, prefix, block_name));
+        }
+        if let Some(Value::Message(block)) = config.get_field_by_name_mut(&block_name) {
+            fill_email_block(block, &block_name, &prefix, &named.settings, &authn_data.keys, placeholder, notes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Set one email identity's SMTP settings in its template block at `path`, with the password left
+/// as `placeholder`. `prefix` is the identity's authn key prefix, such as `EMAIL_`.
+fn fill_email_block(
+    block: &mut DynamicMessage,
+    path: &str,
+    prefix: &str,
+    email: &EmailSettings,
+    keys: &BTreeSet<String>,
+    placeholder: &str,
+    notes: &mut Vec<FillNote>,
+) -> Result<(), String> {
+    let set = |message: &mut DynamicMessage, field: &str, value: Value| {
+        message
+            .try_set_field_by_name(field, value)
+            .map_err(|e| format!("cannot set {}.{}: {}", path, field, redact_set_error(&e)))
+    };
+    
+    // Each key's field and its type come from the schema, which `Schema::load` checked
+    let mut fields = Vec::new();
+    for spec in EMAIL_KEY_FIELDS.iter().filter(|spec| spec.required) {
+        let name = email_field_name(spec.name);
+        let field = block
+            .descriptor()
+            .get_field_by_name(&name)
+            .ok_or_else(|| format!("the schema's {} block has no {} field for {}{}", path, name, prefix, spec.name))?;
+        let value = match spec.name {
+            "SMTP_PASSWORD" => Value::String(placeholder.to_string()),
+            "SMTP_PORT" => integer_value(&field.kind(), email.smtp_port)
+                .ok_or_else(|| format!("field {} is {}, expected an integer", field.full_name(), describe_field_type(&field)))?,
+            other => Value::String(email.text(other).unwrap_or_default().to_string()),
+        };
+        set(block, &name, value)?;
+        fields.push(name);
+    }
+    // Older schemas have no security field; TrailBase then picks the mode itself
+    let security_field = block.descriptor().get_field_by_name(SMTP_SECURITY_FIELD);
+    match (security_field, email.smtp_security) {
+        (Some(field), Some(security)) => {
+            let value = smtp_security_value(&field, security)?;
+            set(block, SMTP_SECURITY_FIELD, value)?;
+            fields.push(SMTP_SECURITY_FIELD.to_string());
+        }
+        // Only worth noting when set explicitly; the port-based default applies to every run
+        (None, Some(security)) if keys.contains(&format!("{}SMTP_SECURITY", prefix)) => {
+            notes.push(FillNote::Unmatched(format!(
+                "the schema's {} block has no {} field, so {}SMTP_SECURITY={} was not used",
+                path, SMTP_SECURITY_FIELD, prefix, security
+            )));
+        }
+        _ => {}
+    }
+    notes.push(FillNote::Filled(format!("filled {} block ({})", path, fields.join(", "))));
+    Ok(())
+}
+
+/// Whether the generator writes `field`'s value to the vault: an OAuth provider's `client_secret` or
+/// an email block's `smtp_password`
+fn is_vault_field(schema: &Schema, field: &prost_reflect::FieldDescriptor) -> bool {
+    (field.parent_message() == &schema.oauth_provider && field.name() == "client_secret")
+        || (field.parent_message() == &schema.email && field.name() == email_field_name("SMTP_PASSWORD"))
+}
+
+/// Check the authn file's `CONFIG_<field path>` values against the schema without a template:
+/// every path must name a field such a key can set, and every value must parse as its type
+pub fn check_config_values(schema: &Schema, authn: &AuthnData) -> Result<(), GenError> {
+    set_config_values(schema, &mut DynamicMessage::new(schema.config.clone()), authn, &mut Vec::new())
+}
+
+/// Set each `CONFIG_<field path>` value from the authn file in `config`, parsed as its field's type
+/// in the schema. Every key is checked before failing, so one run reports all bad paths and values.
+/// Secrets are refused, as the config keeps only placeholders for them.
+fn set_config_values(schema: &Schema, config: &mut DynamicMessage, authn: &AuthnData, notes: &mut Vec<FillNote>) -> Result<(), GenError> {
+    let mut invalid = Vec::new();
+    for (path, value) in &authn.config_values {
+        let key = format!("{}{}", CONFIG_KEY_PREFIX, path);
+        let field = match resolve_config_field(&schema.config, path) {
+            Ok(field) => field,
+            Err(reason) => {
+                invalid.push(InvalidValue { key, value: Some(value.clone()), reason });
+                continue;
+            }
+        };
+        if is_vault_field(schema, &field) || looks_like_secret(field.name()) {
+            invalid.push(InvalidValue {
+                key,
+                value: None,
+                reason: format!("{} is a secret, which must not be written to the config", field.full_name()),
+            });
+            continue;
+        }
+        let segments: Vec<&str> = path.split('.').collect();
+        match config_field_value(&field, value).and_then(|parsed| set_config_path(config, &segments, parsed)) {
+            Ok(()) => notes.push(FillNote::Filled(format!("set {} from {}", path, key))),
+            Err(reason) => invalid.push(InvalidValue { key, value: Some(value.clone()), reason }),
+        }
+    }
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(GenError::Authn { missing: Vec::new(), invalid, auth_mode: authn.auth_mode })
+    }
+}
+
+/// Beyond the fields the generator vaults itself, a field's name is the only hint that it holds a
+/// secret: one ending in `_password`, `_secret` or `_token`, or with a `secret` word anywhere (e.g.
+/// `secret_access_key`). Names such as `disable_password_auth` don't count.
+fn looks_like_secret(name: &str) -> bool {
+    let words: Vec<&str> = name.split('_').collect();
+    words.last().is_some_and(|last| ["password", "secret", "token"].contains(last)) || words.contains(&"secret")
+}
+
+/// The field a `CONFIG_` path names in `config`: singular message fields joined by `.`, ending in
+/// a singular scalar or enum field, e.g. `server.site_url`. Maps and repeated fields can't be set.
+fn resolve_config_field(config: &MessageDescriptor, path: &str) -> Result<prost_reflect::FieldDescriptor, String> {
+    let mut message = config.clone();
+    let mut segments = path.split('.').peekable();
+    while let Some(name) = segments.next() {
+        let field = message
+            .get_field_by_name(name)
+            .ok_or_else(|| format!("{} has no field '{}'", message.full_name(), name))?;
+        if field.is_map() || field.is_list() {
+            return Err(format!("{} is {}, which a CONFIG_ key can't set", field.full_name(), describe_field_type(&field)));
+        }
+        match (field.kind(), segments.peek()) {
+            (prost_reflect::Kind::Message(nested), Some(_)) => message = nested,
+            (prost_reflect::Kind::Message(nested), None) => {
+                return Err(format!("{} is a {} message; name one of its fields", field.full_name(), nested.full_name()))
+            }
+            (_, Some(next)) => return Err(format!("{} is {}, so it has no field '{}'", field.full_name(), describe_field_type(&field), next)),
+            (_, None) => return Ok(field),
+        }
+    }
+    unreachable!("a split path has at least one segment")
+}
+
+/// Parse a `CONFIG_` value as `field`'s type: text for strings and bytes, `true` or `false`, a
+/// number in range, or an enum value's name
+fn config_field_value(field: &prost_reflect::FieldDescriptor, value: &str) -> Result<Value, String> {
+    use prost_reflect::Kind;
+    let parsed = match field.kind() {
+        Kind::String => Some(Value::String(value.to_string())),
+        Kind::Bytes => Some(Value::Bytes(value.as_bytes().to_vec().into())),
+        Kind::Bool => match value {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => value.parse().ok().map(Value::I32),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => value.parse().ok().map(Value::I64),
+        Kind::Uint32 | Kind::Fixed32 => value.parse().ok().map(Value::U32),
+        Kind::Uint64 | Kind::Fixed64 => value.parse().ok().map(Value::U64),
+        Kind::Float => value.parse().ok().map(Value::F32),
+        Kind::Double => value.parse().ok().map(Value::F64),
+        Kind::Enum(enumeration) => {
+            return enumeration.get_value_by_name(value).map(|number| Value::EnumNumber(number.number())).ok_or_else(|| {
+                let names: Vec<String> = enumeration.values().map(|number| number.name().to_string()).collect();
+                format!("{} must be one of {}", field.full_name(), names.join(", "))
+            })
+        }
+        Kind::Message(_) => None,
+    };
+    parsed.ok_or_else(|| format!("not a valid value for {}, which is {}", field.full_name(), describe_field_type(field)))
+}
+
+/// Set the field at `segments` below `message`, creating the messages along the way
+fn set_config_path(message: &mut DynamicMessage, segments: &[&str], value: Value) -> Result<(), String> {
+    match segments {
+        [name] => message.try_set_field_by_name(name, value).map_err(|e| format!("cannot set {}: {}", name, redact_set_error(&e))),
+        [name, rest @ ..] => match message.get_field_by_name_mut(name) {
+            Some(Value::Message(nested)) => set_config_path(nested, rest, value),
+            _ => Err(format!("{} is not a message", name)),
+        },
+        [] => Ok(()),
+    }
+}
+
+/// Set a provider's optional scopes and redirect URL in its entry at `path`, for the fields the
+/// schema defines; values the schema has no field for are noted as unmatched
+fn fill_provider_options(entry: &mut DynamicMessage, path: &str, provider: &OAuthProvider, notes: &mut Vec<FillNote>) -> Result<(), String> {
+    let values = [
+        (OAUTH_SCOPES_FIELD, provider.scopes.as_ref().map(|scopes| scopes.join(","))),
+        (OAUTH_REDIRECT_URL_FIELD, provider.redirect_url.clone()),
+    ];
+    for (field_name, value) in values {
+        let Some(value) = value else { continue };
+        // `unusable_provider_options` warns about these
+        let Some(field) = entry.descriptor().get_field_by_name(field_name) else { continue };
+        // A repeated field takes the scopes one by one; a string field takes the list as written
+        let value = match (field.is_list(), field.kind()) {
+            (true, prost_reflect::Kind::String) if field_name == OAUTH_SCOPES_FIELD => {
+                Value::List(provider.scopes.iter().flatten().cloned().map(Value::String).collect())
+            }
+            (false, prost_reflect::Kind::String) => Value::String(value),
+            _ => {
+                return Err(format!(
+                    "field {} is {}, expected a string{}",
+                    field.full_name(),
+                    describe_field_type(&field),
+                    if field_name == OAUTH_SCOPES_FIELD { " or repeated string" } else { "" }
+                ))
+            }
+        };
+        entry
+            .try_set_field(&field, value)
+            .map_err(|e| format!("cannot set {}.{}: {}", path, field_name, redact_set_error(&e)))?;
+        notes.push(FillNote::Filled(format!("set {} for {}", field_name, provider.name)));
+    }
+    Ok(())
+}
+
+/// Describe each `<PROVIDER>_OAUTH_SCOPES` / `_REDIRECT_URL` key the authn file sets that the schema's
+/// provider message has no field for, as the bundled schema doesn't
+fn unusable_provider_options(schema: &Schema, authn_data: &AuthnData) -> Vec<String> {
+    let mut unusable = Vec::new();
+    for provider in &authn_data.oauth_providers {
+        let options = [(OAUTH_SCOPES_FIELD, "SCOPES", provider.scopes.is_some()), (OAUTH_REDIRECT_URL_FIELD, "REDIRECT_URL", provider.redirect_url.is_some())];
+        for (field_name, key, _) in options.into_iter().filter(|(field_name, _, set)| *set && schema.oauth_provider.get_field_by_name(field_name).is_none()) {
+            unusable.push(format!(
+                "{}_OAUTH_{} is set, but {} has no {} field, so it is not written to the config; pass a --descriptor-set that defines it",
+                provider.name.to_uppercase(),
+                key,
+                schema.oauth_provider.full_name(),
+                field_name
+            ));
+        }
+    }
+    unusable
+}
+
+/// Describe each OAuth provider only one side configures: a template `auth.oauth_providers` entry
+/// the authn file has no credentials for, then authn credentials the template has no entry for
+fn provider_mismatches(config: &DynamicMessage, authn_data: &AuthnData) -> Vec<String> {
+    let mut template_providers = BTreeSet::new();
+    if let Some(Value::Message(auth)) = config.get_field_by_name("auth").as_deref() {
+        if let Some(Value::Map(entries)) = auth.get_field_by_name("oauth_providers").as_deref() {
+            template_providers.extend(entries.keys().filter_map(|key| match key {
+                MapKey::String(name) => Some(name.clone()),
+                _ => None,
+            }));
+        }
+    }
+    let authn_providers: BTreeSet<&str> = authn_data.oauth_providers.iter().map(|provider| provider.name.as_str()).collect();
+    
+    let mut mismatches = Vec::new();
+    for name in template_providers.iter().filter(|name| !authn_providers.contains(name.as_str())) {
+        mismatches.push(format!(
+            "the authn file has no {}_OAUTH_CLIENT_ID, so the template's auth.oauth_providers entry \"{}\" was not filled",
+            name.to_uppercase(),
+            name
+        ));
+    }
+    for provider in authn_data.oauth_providers.iter().filter(|provider| !template_providers.contains(&provider.name)) {
+        mismatches.push(format!(
+            "the template has no auth.oauth_providers entry \"{}\", so {}_OAUTH_CLIENT_ID was not used",
+            provider.name,
+            provider.name.to_uppercase()
+        ));
+    }
+    mismatches
+}
+
+/// Note a provider entry whose `client_id` is about to be overwritten but wasn't its
+/// `<PROVIDER>_OAUTH_CLIENT_ID` placeholder (or the legacy vault placeholder, `<REDACTED>` by
+/// default), e.g. a hardcoded ID
+fn note_overwritten_client_id(entry: &DynamicMessage, path: &str, name: &str, vault_placeholder: &str, notes: &mut Vec<FillNote>) {
+    let placeholder = format!("<{}_OAUTH_CLIENT_ID>", name.to_uppercase());
+    if !entry.has_field_by_name("client_id") {
+        notes.push(FillNote::Unmatched(format!("{}.client_id is not in the template, expected {}", path, placeholder)));
+        return;
+    }
+    if let Some(Value::String(current)) = entry.get_field_by_name("client_id").as_deref() {
+        if *current != placeholder && current != vault_placeholder {
+            notes.push(FillNote::Unmatched(format!(
+                "{}.client_id was \"{}\", not the placeholder {}; overwritten",
+                path,
+                escape_textproto_string(current),
+                placeholder
+            )));
+        }
+    }
+}
+
+/// The value for the schema's SMTP security field: the enum value whose name is or ends with
+/// `_STARTTLS`, `_TLS` or `_NONE` (e.g. `SMTP_ENCRYPTION_TLS`), or the mode's name for a string field
+fn smtp_security_value(field: &prost_reflect::FieldDescriptor, security: SmtpSecurity) -> Result<Value, String> {
+    let suffix = security.name().to_uppercase();
+    match field.kind() {
+        prost_reflect::Kind::Enum(descriptor) => descriptor
+            .values()
+            .find(|value| value.name() == suffix || value.name().ends_with(&format!("_{}", suffix)))
+            .map(|value| Value::EnumNumber(value.number()))
+            .ok_or_else(|| format!("enum {} has no value for EMAIL_SMTP_SECURITY={}", descriptor.full_name(), security)),
+        prost_reflect::Kind::String => Ok(Value::String(security.name().to_string())),
+        _ => Err(format!(
+            "field {} is {}, expected an enum or string",
+            field.full_name(),
+            describe_field_type(field)
+        )),
+    }
+}
+
+/// Drop the config blocks the auth mode doesn't use (`email` and any other email identity
+/// blocks, or `auth.oauth_providers`). The default `both` mode leaves the config untouched.
+fn apply_auth_mode(config: &mut DynamicMessage, email_descriptor: &MessageDescriptor, auth_mode: AuthMode, notes: &mut Vec<FillNote>) {
+    if !auth_mode.uses_email() {
+        let email_blocks: Vec<_> = config
+            .descriptor()
+            .fields()
+            .filter(|field| field.kind().as_message() == Some(email_descriptor) && config.has_field(field))
+            .collect();
+        for field in email_blocks {
+            config.clear_field(&field);
+            notes.push(FillNote::Filled(format!("removed {} block (AUTH_MODE={})", field.name(), auth_mode)));
+        }
+    }
+    if !auth_mode.uses_oauth() && config.has_field_by_name("auth") {
+        if let Some(Value::Message(auth)) = config.get_field_by_name_mut("auth") {
+            if auth.has_field_by_name("oauth_providers") {
+                auth.clear_field_by_name("oauth_providers");
+                notes.push(FillNote::Filled(format!("removed auth.oauth_providers (AUTH_MODE={})", auth_mode)));
+            }
+        }
+    }
+}
+
+/// `message` with only its nested messages and message-valued map entries, every other field
+/// cleared, so filling it leaves exactly the fields the generator sets
+fn message_skeleton(message: &DynamicMessage) -> DynamicMessage {
+    let mut skeleton = DynamicMessage::new(message.descriptor());
+    for (field, value) in message.fields() {
+        let kept = match value {
+            Value::Message(nested) => Value::Message(message_skeleton(nested)),
+            Value::Map(entries) => Value::Map(
+                entries
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.clone(), Value::Message(message_skeleton(value.as_message()?)))))
+                    .collect(),
+            ),
+            _ => continue,
+        };
+        skeleton.set_field(&field, kept);
+    }
+    skeleton
+}
+
+/// Clear the nested messages and map entries of a [`message_skeleton`] that nothing was set in
+fn prune_empty_messages(message: &mut DynamicMessage) {
+    let fields: Vec<_> = message.fields().map(|(field, _)| field).collect();
+    for field in fields {
+        let empty = match message.get_field_mut(&field) {
+            Value::Message(nested) => {
+                prune_empty_messages(nested);
+                nested.fields().next().is_none()
+            }
+            Value::Map(entries) => {
+                entries.retain(|_, value| match value {
+                    Value::Message(nested) => {
+                        prune_empty_messages(nested);
+                        nested.fields().next().is_some()
+                    }
+                    _ => true,
+                });
+                entries.is_empty()
+            }
+            _ => false,
+        };
+        if empty {
+            message.clear_field(&field);
+        }
+    }
+}
+
+/// Format a message like `to_text_format_with_options(&FORMAT_OPTIONS)`, but with map entries
+/// sorted by key so the output doesn't depend on hash map iteration order.
+///
+/// Fields that contain no maps are delegated to prost-reflect's own formatter (via a
+/// single-field copy of the message) so escaping and scalar formatting stay identical.
+pub fn to_canonical_text(message: &DynamicMessage) -> String {
+    let mut out = String::new();
+    write_canonical_fields(message, 0, &mut out);
+    out
+}
+
+fn write_canonical_fields(message: &DynamicMessage, indent: usize, out: &mut String) {
+    let newline = format!("\n{}", " ".repeat(indent));
+    for (index, (field, value)) in message.fields().enumerate() {
+        if index > 0 {
+            out.push_str(&newline);
+        }
+        if !contains_map(value) {
+            let mut single = DynamicMessage::new(message.descriptor());
+            single.set_field(&field, value.clone());
+            out.push_str(&single.to_text_format_with_options(&FORMAT_OPTIONS).replace('\n', &newline));
+            continue;
+        }
+        
+        out.push_str(field.name());
+        match value {
+            Value::Message(nested) => {
+                out.push_str(" {");
+                write_canonical_block_body(nested, indent, out);
+                out.push('}');
+            }
+            Value::List(items) => {
+                let messages: Vec<&DynamicMessage> = items.iter().filter_map(Value::as_message).collect();
+                write_canonical_list(&messages, indent, out);
+            }
+            Value::Map(entries) => {
+                let entry_descriptor = field.kind().as_message().expect("map field has an entry message").clone();
+                let mut sorted: Vec<_> = entries.iter().collect();
+                sorted.sort_by(|a, b| a.0.cmp(b.0));
+                let entries: Vec<DynamicMessage> = sorted
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let mut entry = DynamicMessage::new(entry_descriptor.clone());
+                        entry.set_field(&entry_descriptor.map_entry_key_field(), key.clone().into());
+                        entry.set_field(&entry_descriptor.map_entry_value_field(), value.clone());
+                        entry
+                    })
+                    .collect();
+                write_canonical_list(&entries.iter().collect::<Vec<_>>(), indent, out);
+            }
+            _ => unreachable!("scalars contain no maps"),
+        }
+    }
+}
+
+/// Write `: [{...}, {...}]` for a list of messages
+fn write_canonical_list(messages: &[&DynamicMessage], indent: usize, out: &mut String) {
+    out.push_str(": [");
+    for (index, message) in messages.iter().enumerate() {
+        if index > 0 {
+            out.push_str(", ");
+        }
+        out.push('{');
+        write_canonical_block_body(message, indent, out);
+        out.push('}');
+    }
+    out.push(']');
+}
+
+/// Write the inside of a `{ ... }` block: nothing for an empty message, otherwise the fields on
+/// their own lines indented one level deeper
+fn write_canonical_block_body(message: &DynamicMessage, indent: usize, out: &mut String) {
+    if message.fields().next().is_none() {
+        return;
+    }
+    out.push('\n');
+    out.push_str(&" ".repeat(indent + 2));
+    write_canonical_fields(message, indent + 2, out);
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+}
+
+fn contains_map(value: &Value) -> bool {
+    match value {
+        Value::Map(_) => true,
+        Value::List(items) => items.iter().any(contains_map),
+        Value::Message(message) => message.fields().any(|(_, value)| contains_map(value)),
+        _ => false,
+    }
+}
+
+/// A config textproto as proto3 JSON, as `--config-format json` writes it: fields under their JSON
+/// names, 64-bit integers as strings, enums by name, maps as objects sorted by key and bytes as
+/// base64. Only set fields are written, so it holds exactly what the textproto does.
+pub fn config_to_json(schema: &Schema, config: &str) -> Result<String, GenError> {
+    let message = DynamicMessage::parse_text_format(schema.config.clone(), config)
+        .map_err(|e| GenError::Step(format!("config is not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e))))?;
+    let mut out = String::new();
+    write_json_message(&message, 0, &mut out);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Quote a string as a JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write `{ ... }` with one field per line, indented one level deeper than `indent`
+fn write_json_message(message: &DynamicMessage, indent: usize, out: &mut String) {
+    let fields: Vec<_> = message.fields().collect();
+    if fields.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    out.push_str("{\n");
+    let count = fields.len();
+    for (index, (field, value)) in fields.into_iter().enumerate() {
+        out.push_str(&format!("{}{}: ", " ".repeat(indent + 2), json_string(field.json_name())));
+        write_json_value(value, &field.kind(), indent + 2, out);
+        out.push_str(if index + 1 < count { ",\n" } else { "\n" });
+    }
+    out.push_str(&" ".repeat(indent));
+    out.push('}');
+}
+
+/// Write one field value; `kind` is the field's (or for a map, its entries') type
+fn write_json_value(value: &Value, kind: &prost_reflect::Kind, indent: usize, out: &mut String) {
+    let float = |v: f64| match v {
+        v if v.is_nan() => "\"NaN\"".to_string(),
+        v if v.is_infinite() => if v > 0.0 { "\"Infinity\"" } else { "\"-Infinity\"" }.to_string(),
+        v => v.to_string(),
+    };
+    match value {
+        Value::Message(nested) => write_json_message(nested, indent, out),
+        Value::List(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                out.push_str(if index > 0 { ", " } else { "" });
+                write_json_value(item, kind, indent, out);
+            }
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            let value_kind = kind.as_message().map(|entry| entry.map_entry_value_field().kind()).unwrap_or(kind.clone());
+            let mut sorted: Vec<_> = entries.iter().collect();
+            sorted.sort_by(|a, b| a.0.cmp(b.0));
+            out.push_str("{\n");
+            for (index, (key, value)) in sorted.iter().enumerate() {
+                let key = match key {
+                    MapKey::Bool(v) => v.to_string(),
+                    MapKey::I32(v) => v.to_string(),
+                    MapKey::I64(v) => v.to_string(),
+                    MapKey::U32(v) => v.to_string(),
+                    MapKey::U64(v) => v.to_string(),
+                    MapKey::String(v) => v.clone(),
+                };
+                out.push_str(&format!("{}{}: ", " ".repeat(indent + 2), json_string(&key)));
+                write_json_value(value, &value_kind, indent + 2, out);
+                out.push_str(if index + 1 < sorted.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push('}');
+        }
+        Value::Bool(v) => out.push_str(&v.to_string()),
+        Value::I32(v) => out.push_str(&v.to_string()),
+        Value::U32(v) => out.push_str(&v.to_string()),
+        Value::I64(v) => out.push_str(&json_string(&v.to_string())),
+        Value::U64(v) => out.push_str(&json_string(&v.to_string())),
+        Value::F32(v) => out.push_str(&float(f64::from(*v))),
+        Value::F64(v) => out.push_str(&float(*v)),
+        Value::String(v) => out.push_str(&json_string(v)),
+        Value::Bytes(v) => out.push_str(&json_string(&encode_base64(v))),
+        Value::EnumNumber(number) => match kind.as_enum().and_then(|e| e.get_value(*number)) {
+            Some(value) => out.push_str(&json_string(value.name())),
+            None => out.push_str(&number.to_string()),
+        },
+    }
+}
+
+/// Standard base64 with padding, as proto3 JSON writes `bytes`
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| group | (u32::from(*byte) << (16 - 8 * index)));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Map the secrets onto the vault keys TrailBase loads them from. A key in `vault_keys` wins;
+/// other provider client secrets are named by `key_template`, which must contain `{PROVIDER}` when
+/// several providers use it so keys stay distinct. A named email identity's password goes to
+/// `TRAIL_EMAIL_<NAME>_SMTP_PASSWORD`. Secrets listed in `secret_encodings` are decoded first.
+/// PKCE clients have no secret and get no key.
+fn vault_secrets(authn_data: &AuthnData, options: &GenerateOptions) -> Result<(BTreeMap<String, String>, Vec<String>), String> {
+    let (key_template, vault_keys) = (options.vault_key_template.as_str(), &options.vault_keys);
+    let providers: Vec<(String, &str, &OAuthProvider)> = authn_data
+        .oauth_providers
+        .iter()
+        .filter_map(|provider| {
+            let secret = provider.client_secret.as_deref()?;
+            Some((format!("{}_OAUTH_CLIENT_SECRET", provider.name.to_uppercase()), secret, provider))
+        })
+        .collect();
+    let templated = providers.iter().filter(|(authn_key, _, _)| !vault_keys.contains_key(authn_key)).count();
+    if templated > 1 && !key_template.contains(PROVIDER_PLACEHOLDER) {
+        return Err(format!(
+            "--vault-key-template '{}' must contain {} when {} OAuth providers are configured",
+            key_template,
+            PROVIDER_PLACEHOLDER,
+            templated
+        ));
+    }
+    
+    let mut secrets = BTreeMap::new();
+    let mut decoded = BTreeSet::new();
+    let mut warnings = Vec::new();
+    let mut insert = |authn_key: &str, key: String, value: &str| {
+        // The value as resolved, so padding inside quotes, an `@path` file or the environment counts
+        let value = if options.normalize_secrets && value.trim() != value {
+            warnings.push(format!("trimmed surrounding whitespace from {}; check the source it was copied from", authn_key));
+            value.trim()
+        } else {
+            value
+        };
+        let value = match options.secret_encodings.get(authn_key) {
+            Some(&encoding) => {
+                decoded.insert(authn_key.to_string());
+                decode_secret(value, encoding).map_err(|e| format!("cannot decode {} as {}: {}", authn_key, encoding, e))?
+            }
+            None => value.to_string(),
+        };
+        if secrets.insert(key.clone(), value).is_some() {
+            return Err(format!("vault key '{}' is used for more than one secret", key));
+        }
+        Ok(())
+    };
+    for (authn_key, secret, provider) in &providers {
+        let key = match vault_keys.get(authn_key) {
+            Some(key) => key.clone(),
+            None => key_template.replace(PROVIDER_PLACEHOLDER, &provider.name.to_uppercase()),
+        };
+        if key.trim().is_empty() {
+            return Err("--vault-key-template produces an empty vault key".to_string());
+        }
+        insert(authn_key, key, secret)?;
+    }
+    if let Some(email) = &authn_data.email {
+        let key = vault_keys.get("EMAIL_SMTP_PASSWORD").map_or(DEFAULT_EMAIL_PASSWORD_VAULT_KEY, String::as_str);
+        insert("EMAIL_SMTP_PASSWORD", key.to_string(), &email.smtp_password)?;
+    }
+    for named in &authn_data.named_emails {
+        let authn_key = format!("EMAIL_{}_SMTP_PASSWORD", named.name.to_uppercase());
+        let key = match vault_keys.get(&authn_key) {
+            Some(key) => key.clone(),
+            None => format!("TRAIL_{}", authn_key),
+        };
+        insert(&authn_key, key, &named.settings.smtp_password)?;
+    }
+    // A listed key that names no secret is most likely a typo, which would leave a value encoded
+    if let Some((authn_key, encoding)) = options.secret_encodings.iter().find(|(authn_key, _)| !decoded.contains(*authn_key)) {
+        return Err(format!("{} is listed for {} decoding, but the authn file has no such secret", authn_key, encoding));
+    }
+    if let Some(previous) = &options.previous_secrets {
+        keep_previous_secrets(&mut secrets, previous)?;
+    }
+    Ok((secrets, warnings))
+}
+
+/// Add a `<KEY>_PREVIOUS` entry for each secret that is being rotated, or whose earlier rotation is
+/// still in `previous`, the existing vault
+fn keep_previous_secrets(secrets: &mut BTreeMap<String, String>, previous: &BTreeMap<String, String>) -> Result<(), String> {
+    let mut kept = Vec::new();
+    for (key, value) in secrets.iter() {
+        let previous_key = format!("{}{}", key, PREVIOUS_SECRET_SUFFIX);
+        let previous_value = match previous.get(key) {
+            Some(old) if old != value => Some(old),
+            // Regenerating with the same secret keeps the grace period going
+            Some(_) => previous.get(&previous_key),
+            None => None,
+        };
+        if let Some(previous_value) = previous_value {
+            kept.push((previous_key, previous_value.clone()));
+        }
+    }
+    for (key, value) in kept {
+        if secrets.contains_key(&key) {
+            return Err(format!("vault key '{}' is used for more than one secret", key));
+        }
+        secrets.insert(key, value);
+    }
+    Ok(())
+}
+
+/// Decode a secret's authn value, which must decode to UTF-8 text as vault values are strings.
+/// Errors describe the problem without quoting the value.
+fn decode_secret(value: &str, encoding: SecretEncoding) -> Result<String, String> {
+    let bytes = match encoding {
+        SecretEncoding::Base64 => decode_base64(value)?,
+        SecretEncoding::Hex => decode_hex(value)?,
+    };
+    String::from_utf8(bytes).map_err(|_| "the decoded bytes are not UTF-8 text".to_string())
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, String> {
+    let digits = value.trim_end_matches('=');
+    let padding = value.len() - digits.len();
+    if padding > 2 || (padding > 0 && !value.len().is_multiple_of(4)) || digits.len() % 4 == 1 {
+        return Err("invalid base64 length or padding".to_string());
+    }
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for (index, c) in digits.chars().enumerate() {
+        let sextet = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return Err(format!("invalid base64 character at position {}", index + 1)),
+        };
+        buffer = (buffer << 6) | sextet;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(value.len() / 2);
+    let mut high = None;
+    for (index, c) in value.chars().enumerate() {
+        let digit = c.to_digit(16).ok_or_else(|| format!("invalid hex digit at position {}", index + 1))? as u8;
+        match high.take() {
+            Some(high) => bytes.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    if high.is_some() {
+        return Err("odd number of hex digits".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Generate the vault textproto file with OAuth client secrets and email password
+/// Note: Client ID and email non-secrets are stored in the main config file, not in the vault,
+/// because traildepot only supports loading secrets (not client IDs or email non-secrets) from vault.
+fn generate_vault_file(
+    schema: &Schema,
+    secrets: &BTreeMap<String, String>,
+    header: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let vault = vault_message(schema, secrets)?;
+    
+    // Serialize to textproto using the same approach as TrailBase. The map is hash-ordered, so
+    // the canonical formatter sorts it by key to keep the file stable from run to run.
+    let text: String = to_canonical_text(&vault);
+    
+    Ok(format!("{}{}", preface(&schema.vault, header), text))
+}
+
+/// A Vault message with the client secret and email password. The message is built dynamically
+/// so a runtime --descriptor-set is honoured.
+fn vault_message(schema: &Schema, secrets: &BTreeMap<String, String>) -> Result<DynamicMessage, String> {
+    let secrets = secrets
+        .iter()
+        .map(|(key, value)| (MapKey::String(key.clone()), Value::String(value.clone())))
+        .collect();
+    let mut vault = DynamicMessage::new(schema.vault.clone());
+    vault
+        .try_set_field_by_name("secrets", Value::Map(secrets))
+        .map_err(|e| format!("cannot set secrets: {}", redact_set_error(&e)))?;
+    Ok(vault)
+}
+
+/// Serialize `secrets` as a vault file in `format`: the textproto of [`GeneratedVault::vault`]
+/// with its `# Auto-generated ...` preface and `header`, or the Vault message's binary wire form,
+/// which has no room for comments. Binary output is decoded again to check every secret survives.
+pub fn encode_vault(
+    schema: &Schema,
+    secrets: &BTreeMap<String, String>,
+    format: VaultFormat,
+    header: Option<&str>,
+) -> Result<Vec<u8>, GenError> {
+    match format {
+        VaultFormat::Textproto => generate_vault_file(schema, secrets, header)
+            .map(String::into_bytes)
+            .map_err(|e| GenError::Serialize(format!("failed to generate vault file: {}", e))),
+        VaultFormat::Binary => {
+            let bytes = vault_message(schema, secrets)
+                .map_err(|e| GenError::Serialize(format!("failed to generate vault file: {}", e)))?
+                .encode_to_vec();
+            let decoded = decode_binary_vault(schema, &bytes)
+                .map_err(|e| GenError::Validation(format!("generated vault failed validation: {}", e)))?;
+            for (key, value) in secrets {
+                if decoded.secrets.get(key) != Some(value) {
+                    return Err(GenError::Validation(format!(
+                        "generated vault failed validation: secret '{}' does not round-trip: \"{}\"",
+                        key,
+                        redact(value)
+                    )));
+                }
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Decode a binary vault into a `Vault`; the inverse of [`encode_vault`] with [`VaultFormat::Binary`]
+fn decode_binary_vault(schema: &Schema, bytes: &[u8]) -> Result<Vault, String> {
+    DynamicMessage::decode(schema.vault.clone(), bytes)
+        .map_err(|e| format!("not a valid binary {} message: {}", schema.vault.full_name(), e))?
+        .transcode_to::<Vault>()
+        .map_err(|e| format!("{} does not decode as the built-in Vault: {}", schema.vault.full_name(), e))
+}
+
+/// The comment lines an output starts with: `# Auto-generated <message> textproto`, then `header`
+/// with each line commented out
+fn preface(descriptor: &MessageDescriptor, header: Option<&str>) -> String {
+    let mut preface = format!("# Auto-generated {} textproto\n", descriptor.full_name());
+    for line in header.unwrap_or_default().lines() {
+        preface.push_str(format!("# {}", line).trim_end());
+        preface.push('\n');
+    }
+    preface
+}
+
+/// Mask a value for display, keeping a short prefix only when the value is long
+/// enough that the prefix doesn't give most of it away. Every message that can echo an authn
+/// value, or a file holding one, goes through this.
+pub fn redact(value: &str) -> String {
+    if value.chars().count() >= 12 {
+        let prefix: String = value.chars().take(4).collect();
+        format!("{prefix}…")
+    } else {
+        "••••".to_string()
+    }
+}
+
+/// Describe a textproto parse error with the offending token redacted. The token may be part of
+/// a secret, e.g. an unquoted value in a hand-edited vault.
+pub fn redact_parse_error(error: &ParseError) -> String {
+    let message = error.to_string();
+    match message.split_once(", but found '") {
+        Some((expected, found)) => {
+            format!("{}, but found '{}'", expected, redact(found.strip_suffix('\'').unwrap_or(found)))
+        }
+        None => message,
+    }
+}
+
+/// Describe a failure to set a field without the value, which prost-reflect includes in full
+fn redact_set_error(error: &SetFieldError) -> String {
+    match error {
+        SetFieldError::NotFound => "field not found".to_string(),
+        SetFieldError::InvalidType { field, value } => {
+            format!("expected a value of type {:?}, but found '{}'", field.kind(), redact(&value.to_string()))
+        }
+    }
+}
+
+/// Escape a value for use inside a double-quoted textproto string, per the protobuf text
+/// format: `"`, `'` and `\` are backslash-escaped, `\n`, `\r` and `\t` use their short escapes and
+/// other control characters are written as octal. Anything else, including non-ASCII, is kept.
+pub fn escape_textproto_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Validate the generated config against the `config.Config` descriptor: the output as written
+/// must parse, and no template placeholder may be left unfilled.
+fn validate_config(schema: &Schema, config: &str, placeholder: &str) -> Result<(), String> {
+    let parsed = DynamicMessage::parse_text_format(schema.config.clone(), config)
+        .map_err(|e| format!("not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e)))?;
+    
+    let mut unfilled = Vec::new();
+    find_unfilled_placeholders(schema, &parsed, "", placeholder, &mut unfilled);
+    if !unfilled.is_empty() {
+        return Err(format!("template placeholders were not filled: {}", unfilled.join(", ")));
+    }
+    Ok(())
+}
+
+/// Collect `path = "<PLACEHOLDER>"` for every string field still holding a `<UPPER_CASE>`
+/// placeholder or the vault placeholder, i.e. a template value the authn file didn't fill. The vault
+/// placeholder is expected in the secret fields (`client_secret`, `smtp_password`) and reported
+/// anywhere else.
+fn find_unfilled_placeholders(
+    schema: &Schema,
+    message: &DynamicMessage,
+    prefix: &str,
+    vault_placeholder: &str,
+    unfilled: &mut Vec<String>,
+) {
+    let is_placeholder = |value: &str| {
+        value == vault_placeholder
+            || value
+                .strip_prefix('<')
+                .and_then(|rest| rest.strip_suffix('>'))
+                .is_some_and(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+    };
+    let mut check = |field: &prost_reflect::FieldDescriptor, path: String, value: &Value| match value {
+        Value::String(text) if is_placeholder(text) && !(text == vault_placeholder && is_vault_field(schema, field)) => {
+            unfilled.push(format!("{} = \"{}\"", path, text));
+        }
+        Value::Message(nested) => find_unfilled_placeholders(schema, nested, &format!("{}.", path), vault_placeholder, unfilled),
+        _ => {}
+    };
+    
+    for (field, value) in message.fields() {
+        let path = format!("{}{}", prefix, field.name());
+        match value {
+            Value::List(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    check(&field, format!("{}[{}]", path, index), item);
+                }
+            }
+            Value::Map(entries) => {
+                let value_field = field.kind().as_message().expect("map field has an entry message").map_entry_value_field();
+                let mut sorted: Vec<_> = entries.iter().collect();
+                sorted.sort_by(|a, b| a.0.cmp(b.0));
+                for (key, item) in sorted {
+                    let key = match key {
+                        MapKey::String(key) => format!("{:?}", key),
+                        other => format!("{:?}", other),
+                    };
+                    check(&value_field, format!("{}[{}]", path, key), item);
+                }
+            }
+            value => check(&field, path, value),
+        }
+    }
+}
+
+/// Validate the generated vault against the vault descriptor, checking that
+/// every secret survives the round trip unchanged
+fn validate_vault(schema: &Schema, vault: &str, expected: &BTreeMap<String, String>) -> Result<(), String> {
+    let vault = parse_vault_with(schema, vault).map_err(|e| e.to_string())?;
+    
+    for (key, value) in expected {
+        if vault.secrets.get(key) != Some(value) {
+            return Err(format!("secret '{}' does not round-trip: \"{}\"", key, redact(value)));
+        }
+    }
+    Ok(())
+}
@@ -1,258 +1,2437 @@
 //! Config generator for TrailBase server configuration
 //!
 //! Reads a template config file and an authn file, then generates:
-//! - A config.textproto file with OAuth client ID and email configuration inserted, with <REDACTED> placeholders for secrets
-//! - A secrets.textproto vault file with OAuth client secret and email password (client ID and email non-secrets are in config, not vault)
+//! - A config.textproto file with OAuth client IDs and email configuration inserted, with <REDACTED> placeholders for secrets
+//! - A secrets.textproto vault file with OAuth client secrets and email password (client IDs and email non-secrets are in config, not vault)
+//!
+//! Parsing and generation live in the `config_generator` library; this binary adds the CLI around
+//! it. The options are listed in the usage message (`print_usage`) and the README.
 
-use lazy_static::lazy_static;
-use prost_reflect::text_format::FormatOptions;
-use prost_reflect::{DescriptorPool, MessageDescriptor, ReflectMessage};
-use std::collections::HashMap;
+use config_generator::{
+    authn_file_template, authn_lines, check_config_values, config_to_json, encode_vault, generate_vault_with, generate_with, is_secret_authn_key, json_string, merge_vault_with, merge_with, parse_authn_as, parse_authn_layers_with, parse_vault_key_map, provider_listing, redact, redact_parse_error, to_canonical_text,
+    AuthnData, AuthnFormat, EmailSettings, FillNote, GenError, GenerateOptions, GeneratedOutput, MergeStrategy, Schema, SecretEncoding, VaultFormat, DEFAULT_PLACEHOLDER, DEFAULT_VAULT_KEY_TEMPLATE, DEFAULT_VAULT_MESSAGE, PREVIOUS_SECRET_SUFFIX,
+};
+use prost_reflect::{DynamicMessage, MapKey, MessageDescriptor, ReflectMessage, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
-use std::process;
-use std::sync::LazyLock;
+use std::process::{self, ExitCode};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-// Include generated protobuf code
-include!(concat!(env!("OUT_DIR"), "/config.rs"));
+/// Substrings that mark leftover template content, checked by `--verify-no-template-leftovers`
+const DEFAULT_FORBIDDEN_SUBSTRINGS: &[&str] = &["TODO", "FIXME", "example.com"];
 
-// Load descriptor pool from generated file descriptor set
-static FILE_DESCRIPTOR_SET: &[u8] =
-    include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+/// What an invocation does
+enum Command {
+    /// Generate the config and vault from a template and authn file
+    Generate(Box<Options>),
+    /// Rewrite an existing config or vault file in canonical form
+    Canonicalize {
+        path: String,
+        descriptor_set_path: Option<String>,
+        vault_message: String,
+        quiet: bool,
+    },
+    /// Print a commented authn file listing every key the generator reads
+    GenerateTemplate,
+    /// Print the schema's OAuth providers with the authn keys each one reads
+    ListProviders {
+        descriptor_set_path: Option<String>,
+        vault_key_template: String,
+    },
+    /// Render an authn template's `${VAR}` references from the environment into an authn file
+    RenderAuthn {
+        template_path: String,
+        output_path: String,
+        quiet: bool,
+    },
+}
 
-static DESCRIPTOR_POOL: LazyLock<DescriptorPool> = LazyLock::new(|| {
-    DescriptorPool::decode(FILE_DESCRIPTOR_SET)
-        .expect("Failed to load file descriptor set")
-});
+/// How a successful generation run reports what it did
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Only the messages on stderr
+    Human,
+    /// Also a JSON summary on stdout
+    Json,
+}
 
-lazy_static! {
-    static ref VAULT_DESCRIPTOR: MessageDescriptor = DESCRIPTOR_POOL
-        .get_message_by_name("config.Vault")
-        .expect("Vault message descriptor not found");
-    static ref FORMAT_OPTIONS: FormatOptions = FormatOptions::new().pretty(true).expand_any(true);
+/// One of the two generated files, for `--only`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputKind {
+    Config,
+    Vault,
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Options for generation
+struct Options {
+    /// Empty with `--only vault` when no template was given
+    template_path: String,
+    /// The authn files, merged in order: a later file's keys override an earlier one's
+    authn_paths: Vec<String>,
+    /// JSON array of OAuth provider credentials, merged after the authn files
+    providers_json_path: Option<String>,
+    /// Whether a later authn file's list-valued keys replace or extend an earlier file's
+    merge_strategy: MergeStrategy,
+    /// Empty with `--only vault` when no config output was given
+    config_output_path: String,
+    /// Where `--config-format json` writes the config as proto3 JSON, next to the textproto
+    config_json_path: Option<String>,
+    vault_output_path: String,
+    /// Re-parse both outputs against their descriptors before writing
+    validate: bool,
+    /// Substrings that must not appear in the generated config, if the leftover check is enabled
+    forbidden_substrings: Option<Vec<String>>,
+    /// File listing config field paths that must never be emitted in plaintext
+    redaction_policy_path: Option<String>,
+    /// Existing config to diff the generated one against instead of writing outputs
+    compare_config_path: Option<String>,
+    /// Shell command run before any input is read; generation aborts if it fails
+    pre_hook: Option<String>,
+    /// Encoded `FileDescriptorSet` to use instead of the one embedded at build time
+    descriptor_set_path: Option<String>,
+    /// Full name of the vault message in the schema
+    vault_message: String,
+    /// How the vault output is serialized
+    vault_format: VaultFormat,
+    /// Abort generation if it hasn't finished within this long
+    time_budget: Option<Duration>,
+    /// Vault key name for each OAuth provider's client secret; `{PROVIDER}` is the upper-cased provider name
+    vault_key_template: String,
+    /// File mapping secret authn keys to the vault keys they are written under
+    vault_key_map_path: Option<String>,
+    /// Secrets decoded from base64 or hex before they go into the vault, by authn key
+    secret_encodings: BTreeMap<String, SecretEncoding>,
+    /// Sentinel the config carries for values that live in the vault
+    placeholder: String,
+    /// Skip the check for provider secrets whose client ID is missing
+    allow_orphan_secrets: bool,
+    /// Fail instead of warning when the template and authn file configure different OAuth providers
+    strict_providers: bool,
+    /// Turn every warning into an error
+    strict: bool,
+    /// Comment block for the top of both outputs, with `{template}` and `{timestamp}` filled in
+    header: Option<String>,
+    /// Warn about secret values whose whitespace was trimmed when reading the authn file
+    normalize_secrets: bool,
+    /// Existing config to print a textproto patch of changed fields against instead of writing
+    config_patch_path: Option<String>,
+    /// Print JSON counts of field changes against the existing outputs instead of writing
+    print_diff_summary: bool,
+    /// Path to write a JSON inventory of identifying (non-secret) fields and vault key names to
+    inventory_path: Option<String>,
+    /// Don't write the vault file when there are no secrets to put in it
+    no_vault_if_empty: bool,
+    /// Record output checksums in sidecar files and refuse to overwrite outputs edited since
+    checksum_guard: bool,
+    /// Overwrite outputs even if the checksum guard detects a manual edit
+    force: bool,
+    /// Print the outputs instead of writing any file
+    dry_run: bool,
+    /// Print the dry-run vault's secret values in plaintext instead of masked
+    show_secrets: bool,
+    /// Show how existing outputs would change and confirm before overwriting them
+    diff: bool,
+    /// Write after `--diff` without asking
+    yes: bool,
+    /// Rename existing outputs to `<output>.bak` before writing
+    backup: bool,
+    /// Log each substitution and warn about authn values the template had no place for
+    verbose: bool,
+    /// Leave out the success messages, e.g. "Successfully generated ..."
+    quiet: bool,
+    /// Compare the existing outputs with what would be generated instead of writing, failing on drift
+    check: bool,
+    /// Update the existing config output rather than regenerating it from the template
+    merge: bool,
+    /// Write only the config fields the generator sets, not the template's other values
+    minimal: bool,
+    /// Keep the existing vault's other secrets, replacing only the generated ones
+    merge_vault: bool,
+    /// Keep the existing vault's value of each changed secret under `<KEY>_PREVIOUS`
+    rotate: bool,
+    /// Whether to print a JSON summary of the run to stdout
+    format: OutputFormat,
+    /// Write only this output, leaving the other untouched
+    only: Option<OutputKind>,
+    /// Check the authn file and print what it holds instead of reading a template or writing outputs
+    validate_only: bool,
+}
+
+/// Prefix of the environment variables that supply option defaults
+const ENV_PREFIX: &str = "TRAIL_GEN_";
+
+/// Environment variable names (after the prefix) for the positional arguments, in order
+const POSITIONAL_ENV_VARS: [&str; 4] = ["TEMPLATE", "AUTHN", "CONFIG_OUTPUT", "VAULT_OUTPUT"];
+
+/// The positional arguments for `--only vault` when the template and config output are left out
+const VAULT_ONLY_ENV_VARS: [&str; 2] = ["AUTHN", "VAULT_OUTPUT"];
+
+/// The positional argument for `--validate-only` when the template and outputs are left out
+const VALIDATE_ONLY_ENV_VARS: [&str; 1] = ["AUTHN"];
+
+/// The positional arguments `--output-dir` replaces
+const OUTPUT_ENV_VARS: [&str; 2] = ["CONFIG_OUTPUT", "VAULT_OUTPUT"];
+
+/// Where `--output-dir` puts the outputs, relative to it
+const DEFAULT_CONFIG_OUTPUT: &str = "config.textproto";
+const DEFAULT_VAULT_OUTPUT: &str = "secrets/secrets.textproto";
+
+/// Flags that take no value
+const SWITCH_FLAGS: &[&str] = &["--no-validate", "--verify-no-template-leftovers", "--checksum-guard", "--force", "--print-diff-summary", "--normalize-secrets", "--allow-orphan-secrets", "--strict-providers", "--strict", "--no-vault-if-empty", "--dry-run", "--show-secrets", "--diff", "--yes", "--backup", "--verbose", "--quiet", "--check", "--generate-template", "--list-providers", "--merge", "--minimal", "--merge-vault", "--rotate", "--validate-only"];
+
+/// Flags that take a value as the following argument
+const VALUE_FLAGS: &[&str] = &[
+    "--forbidden-substrings",
+    "--compare-config",
+    "--pre-hook",
+    "--canonicalize",
+    "--descriptor-set",
+    "--vault-message",
+    "--vault-format",
+    "--authn-template",
+    "--providers-json",
+    "--merge-strategy",
+    "--config-format",
+    "--redaction-policy",
+    "--config-patch",
+    "--inventory",
+    "--vault-key-template",
+    "--vault-key-map",
+    "--decode-base64",
+    "--decode-hex",
+    "--placeholder",
+    "--time-budget",
+    "--format",
+    "--only",
+    "--output-dir",
+    "--header",
+];
+
+/// Environment variable that supplies the default for a flag: `--no-validate` -> `TRAIL_GEN_NO_VALIDATE`
+fn flag_env_var(flag: &str) -> String {
+    format!("{}{}", ENV_PREFIX, flag.trim_start_matches("--").to_uppercase().replace('-', "_"))
+}
+
+/// Parse command-line arguments; flags may appear in any position.
+///
+/// Every flag and positional argument that isn't passed falls back to its `TRAIL_GEN_*`
+/// environment variable (looked up through `env`); explicit arguments always take precedence.
+fn parse_args(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<Command, String> {
+    let mut positional = Vec::new();
+    let mut explicit: HashMap<&'static str, String> = HashMap::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(flag) = SWITCH_FLAGS.iter().find(|flag| **flag == arg) {
+            explicit.insert(flag, "true".to_string());
+        } else if let Some(flag) = VALUE_FLAGS.iter().find(|flag| **flag == arg) {
+            let value = args.next().ok_or_else(|| format!("{} requires a value", flag))?;
+            explicit.insert(flag, value.clone());
+        } else if arg.starts_with("--") {
+            return Err(format!("unknown option '{}'", arg));
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+
+    let value = |flag: &str| explicit.get(flag).cloned().or_else(|| env(&flag_env_var(flag)));
+    let switch = |flag: &str| -> Result<bool, String> {
+        match value(flag).as_deref() {
+            None => Ok(false),
+            Some("1" | "true" | "yes") => Ok(true),
+            Some("" | "0" | "false" | "no") => Ok(false),
+            Some(other) => Err(format!("{} must be a boolean (1/0, true/false, yes/no), got '{}'", flag_env_var(flag), other)),
+        }
+    };
+    
+    let vault_message = || match value("--vault-message") {
+        Some(name) if name.trim().is_empty() => Err("--vault-message must not be empty".to_string()),
+        Some(name) => Ok(name),
+        None => Ok(DEFAULT_VAULT_MESSAGE.to_string()),
+    };
     
-    if args.len() != 5 {
-        eprintln!("Usage: {} <template-file> <authn-file> <config-output> <vault-output>", args[0]);
-        eprintln!("  template-file: Path to config.textproto.template");
-        eprintln!("  authn-file: Path to .authn file with OAuth credentials and email configuration");
-        eprintln!("  config-output: Path to write the generated config.textproto");
-        eprintln!("  vault-output: Path to write the generated secrets.textproto");
-        process::exit(1);
+    if switch("--generate-template")? {
+        if !positional.is_empty() {
+            return Err("--generate-template takes no other arguments".to_string());
+        }
+        return Ok(Command::GenerateTemplate);
     }
     
-    let template_path = &args[1];
-    let authn_path = &args[2];
-    let config_output_path = &args[3];
-    let vault_output_path = &args[4];
+    if switch("--list-providers")? {
+        if !positional.is_empty() {
+            return Err("--list-providers takes no other arguments".to_string());
+        }
+        return Ok(Command::ListProviders {
+            descriptor_set_path: value("--descriptor-set"),
+            vault_key_template: value("--vault-key-template").unwrap_or_else(|| DEFAULT_VAULT_KEY_TEMPLATE.to_string()),
+        });
+    }
     
-    // Read template file
-    let template = match fs::read_to_string(template_path) {
-        Ok(content) => content,
+    if let Some(path) = value("--canonicalize") {
+        if !positional.is_empty() {
+            return Err("--canonicalize takes no other arguments".to_string());
+        }
+        return Ok(Command::Canonicalize {
+            path,
+            descriptor_set_path: value("--descriptor-set"),
+            vault_message: vault_message()?,
+            quiet: switch("--quiet")?,
+        });
+    }
+
+    if let Some(template_path) = value("--authn-template") {
+        // The rendered file goes where generation would read it from unless given explicitly
+        let output_path = match positional.as_slice() {
+            [output] => output.clone(),
+            [] => env(&format!("{}AUTHN", ENV_PREFIX))
+                .ok_or_else(|| format!("--authn-template requires an <authn-output> argument (or {}AUTHN)", ENV_PREFIX))?,
+            _ => return Err("--authn-template takes a single <authn-output> argument".to_string()),
+        };
+        return Ok(Command::RenderAuthn { template_path, output_path, quiet: switch("--quiet")? });
+    }
+
+    let only = match value("--only").as_deref() {
+        None => None,
+        Some("config") => Some(OutputKind::Config),
+        Some("vault") => Some(OutputKind::Vault),
+        Some(other) => return Err(format!("--only must be 'config' or 'vault', got '{}'", other)),
+    };
+    
+    // --output-dir stands in for the two output paths
+    let output_dir = value("--output-dir");
+    if output_dir.as_deref().is_some_and(|dir| dir.trim().is_empty()) {
+        return Err("--output-dir must not be empty".to_string());
+    }
+    let names: Vec<&str> =
+        POSITIONAL_ENV_VARS.into_iter().filter(|name| output_dir.is_none() || !OUTPUT_ENV_VARS.contains(name)).collect();
+    if positional.len() > names.len() {
+        return Err(match output_dir {
+            Some(_) => format!("--output-dir takes only <template-file> <authn-file>, got {} arguments", positional.len()),
+            None => format!("expected at most {} arguments, got {}", names.len(), positional.len()),
+        });
+    }
+    // The vault alone needs neither a template nor a config path, unless they are given anyway
+    let vault_only_args = only == Some(OutputKind::Vault) && positional.len() < names.len();
+    let names: Vec<&str> = if vault_only_args { names.into_iter().filter(|name| VAULT_ONLY_ENV_VARS.contains(name)).collect() } else { names };
+    if positional.len() > names.len() {
+        return Err("--only vault takes <authn-file> <vault-output>, or all four arguments".to_string());
+    }
+    // Validating the authn file needs nothing else either
+    let validate_only = switch("--validate-only")?;
+    let validate_only_args = validate_only && positional.len() < names.len();
+    let names: Vec<&str> = if validate_only_args { names.into_iter().filter(|name| VALIDATE_ONLY_ENV_VARS.contains(name)).collect() } else { names };
+    if positional.len() > names.len() {
+        return Err("--validate-only takes <authn-file>, or all four arguments".to_string());
+    }
+    let mut paths: HashMap<&str, String> = HashMap::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let path = match positional.get(index) {
+            Some(path) => path.clone(),
+            None => {
+                let var = format!("{}{}", ENV_PREFIX, name);
+                env(&var).ok_or_else(|| format!("missing <{}> argument (or {})", name.to_lowercase().replace('_', "-"), var))?
+            }
+        };
+        paths.insert(name, path);
+    }
+    let mut path = |name: &str| paths.remove(name).unwrap_or_default();
+    let template_path = path("TEMPLATE");
+    let authn_path = path("AUTHN");
+    let (config_output_path, vault_output_path) = match &output_dir {
+        Some(dir) => (
+            Path::new(dir).join(DEFAULT_CONFIG_OUTPUT).display().to_string(),
+            Path::new(dir).join(DEFAULT_VAULT_OUTPUT).display().to_string(),
+        ),
+        None => (path("CONFIG_OUTPUT"), path("VAULT_OUTPUT")),
+    };
+    let authn_paths: Vec<String> = authn_path.split(',').map(String::from).collect();
+    if authn_paths.len() > 1 && authn_paths.iter().any(|path| path.trim().is_empty()) {
+        return Err(format!("<authn-file> list '{}' has an empty entry", authn_path));
+    }
+    let stdin_inputs = authn_paths.iter().chain([&template_path]).filter(|path| *path == STDIN_PATH).count();
+    if stdin_inputs > 1 {
+        return Err("only one of <template-file> and <authn-file> can be read from stdin ('-')".to_string());
+    }
+    let providers_json_path = value("--providers-json");
+    if stdin_inputs > 0 && providers_json_path.as_deref() == Some(STDIN_PATH) {
+        return Err("--providers-json can't read stdin ('-') when <template-file> or <authn-file> does".to_string());
+    }
+
+    // Overriding the list implies the check is wanted
+    let forbidden_substrings = match value("--forbidden-substrings") {
+        Some(list) => Some(
+            list.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+        ),
+        None if switch("--verify-no-template-leftovers")? => {
+            Some(DEFAULT_FORBIDDEN_SUBSTRINGS.iter().map(|s| s.to_string()).collect())
+        }
+        None => None,
+    };
+
+    let time_budget = match value("--time-budget") {
+        Some(seconds) => match seconds.trim().parse::<f64>() {
+            Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Some(Duration::from_secs_f64(seconds)),
+            _ => return Err(format!("--time-budget must be a positive number of seconds, got '{}'", seconds)),
+        },
+        None => None,
+    };
+    
+    let placeholder = value("--placeholder").unwrap_or_else(|| DEFAULT_PLACEHOLDER.to_string());
+    if placeholder.trim().is_empty() {
+        return Err("--placeholder must not be empty".to_string());
+    }
+    
+    let mut secret_encodings = BTreeMap::new();
+    for (flag, encoding) in [("--decode-base64", SecretEncoding::Base64), ("--decode-hex", SecretEncoding::Hex)] {
+        for key in value(flag).iter().flat_map(|list| list.split(',')).map(str::trim).filter(|key| !key.is_empty()) {
+            if !is_secret_authn_key(key) {
+                return Err(format!(
+                    "{}: '{}' is not a secret authn key (EMAIL_SMTP_PASSWORD, EMAIL_<NAME>_SMTP_PASSWORD or <PROVIDER>_OAUTH_CLIENT_SECRET)",
+                    flag, key
+                ));
+            }
+            if secret_encodings.insert(key.to_string(), encoding).is_some_and(|previous| previous != encoding) {
+                return Err(format!("{} is listed in both --decode-base64 and --decode-hex", key));
+            }
+        }
+    }
+    
+    let print_diff_summary = switch("--print-diff-summary")?;
+    let check = switch("--check")?;
+    let comparisons = [
+        ("--compare-config", value("--compare-config").is_some()),
+        ("--config-patch", value("--config-patch").is_some()),
+        ("--print-diff-summary", print_diff_summary),
+        ("--check", check),
+    ];
+    let selected: Vec<&str> = comparisons.iter().filter(|(_, set)| *set).map(|(flag, _)| *flag).collect();
+    if selected.len() > 1 {
+        return Err(format!("{} cannot be combined", selected.join(" and ")));
+    }
+    
+    let dry_run = switch("--dry-run")?;
+    let diff = switch("--diff")?;
+    let yes = switch("--yes")?;
+    if diff {
+        if let Some(flag) = selected.first().copied().or(dry_run.then_some("--dry-run")) {
+            return Err(format!("--diff cannot be combined with {}, which doesn't write", flag));
+        }
+    } else if yes {
+        return Err("--yes only applies with --diff".to_string());
+    }
+    let show_secrets = switch("--show-secrets")?;
+    if show_secrets && !dry_run {
+        return Err("--show-secrets only applies with --dry-run".to_string());
+    }
+    // A merged config keeps every field it has, so there is nothing to leave out
+    if switch("--minimal")? && switch("--merge")? {
+        return Err("--minimal cannot be combined with --merge".to_string());
+    }
+    let quiet = switch("--quiet")?;
+    let verbose = switch("--verbose")?;
+    if quiet && verbose {
+        return Err("--quiet cannot be combined with --verbose".to_string());
+    }
+    let format = match value("--format").as_deref() {
+        None | Some("human") => OutputFormat::Human,
+        Some("json") => OutputFormat::Json,
+        Some(other) => return Err(format!("--format must be 'human' or 'json', got '{}'", other)),
+    };
+    let merge_strategy = match value("--merge-strategy").as_deref() {
+        None | Some("override") => MergeStrategy::Override,
+        Some("append") => MergeStrategy::Append,
+        Some(other) => return Err(format!("--merge-strategy must be 'override' or 'append', got '{}'", other)),
+    };
+    let vault_format = match value("--vault-format").as_deref() {
+        None | Some("textproto") => VaultFormat::Textproto,
+        Some("binary") => VaultFormat::Binary,
+        Some(other) => return Err(format!("--vault-format must be 'textproto' or 'binary', got '{}'", other)),
+    };
+    // Without a config, nothing that reads or writes one can run
+    if only == Some(OutputKind::Vault) {
+        let config_flags = [
+            ("--merge", switch("--merge")?),
+            ("--minimal", switch("--minimal")?),
+            ("--config-format", value("--config-format").is_some()),
+            ("--compare-config", value("--compare-config").is_some()),
+            ("--config-patch", value("--config-patch").is_some()),
+            ("--redaction-policy", value("--redaction-policy").is_some()),
+            ("--verify-no-template-leftovers", forbidden_substrings.is_some()),
+            ("--inventory", value("--inventory").is_some()),
+        ];
+        if let Some((flag, _)) = config_flags.iter().find(|(_, set)| *set) {
+            return Err(format!("{} cannot be combined with --only vault", flag));
+        }
+    }
+    
+    // Nothing is generated to compare, preview or write
+    if validate_only {
+        let output_flags = [
+            ("--only", only.is_some()),
+            ("--check", check),
+            ("--compare-config", value("--compare-config").is_some()),
+            ("--config-patch", value("--config-patch").is_some()),
+            ("--print-diff-summary", print_diff_summary),
+            ("--dry-run", dry_run),
+            ("--diff", diff),
+            ("--merge", switch("--merge")?),
+            ("--minimal", switch("--minimal")?),
+            ("--config-format", value("--config-format").is_some()),
+            ("--merge-vault", switch("--merge-vault")?),
+            ("--rotate", switch("--rotate")?),
+            ("--inventory", value("--inventory").is_some()),
+            ("--format json", format == OutputFormat::Json),
+        ];
+        if let Some((flag, _)) = output_flags.iter().find(|(_, set)| *set) {
+            return Err(format!("--validate-only cannot be combined with {}, which needs generated outputs", flag));
+        }
+    }
+    
+    // Those modes print their own results to stdout
+    if format == OutputFormat::Json {
+        if let Some(flag) = selected.first().copied().or(dry_run.then_some("--dry-run")).or(diff.then_some("--diff")) {
+            return Err(format!("--format json cannot be combined with {}", flag));
+        }
+    }
+    
+    // Each format goes to the config path with that format's extension
+    let (config_output_path, config_json_path) = match value("--config-format") {
+        None => (config_output_path, None),
+        Some(list) => {
+            let mut formats = Vec::new();
+            for format in list.split(',').map(str::trim) {
+                match format {
+                    "textproto" | "json" if !formats.contains(&format) => formats.push(format),
+                    "textproto" | "json" => {}
+                    other => return Err(format!("--config-format entries must be 'textproto' or 'json', got '{}'", other)),
+                }
+            }
+            // --check, --merge and the checksum guard read the textproto back
+            if !formats.contains(&"textproto") {
+                return Err(format!("--config-format must include textproto, which TrailBase and --check read, got '{}'", list));
+            }
+            let base = Path::new(&config_output_path);
+            let json = formats.contains(&"json").then(|| base.with_extension("json").display().to_string());
+            (base.with_extension("textproto").display().to_string(), json)
+        }
+    };
+    
+    Ok(Command::Generate(Box::new(Options {
+        template_path,
+        authn_paths,
+        providers_json_path,
+        merge_strategy,
+        config_output_path,
+        config_json_path,
+        vault_output_path,
+        validate: !switch("--no-validate")?,
+        forbidden_substrings,
+        redaction_policy_path: value("--redaction-policy"),
+        compare_config_path: value("--compare-config"),
+        config_patch_path: value("--config-patch"),
+        pre_hook: value("--pre-hook"),
+        descriptor_set_path: value("--descriptor-set"),
+        vault_message: vault_message()?,
+        vault_format,
+        time_budget,
+        print_diff_summary,
+        normalize_secrets: switch("--normalize-secrets")?,
+        allow_orphan_secrets: switch("--allow-orphan-secrets")?,
+        strict_providers: switch("--strict-providers")?,
+        strict: switch("--strict")?,
+        header: value("--header"),
+        vault_key_template: value("--vault-key-template").unwrap_or_else(|| DEFAULT_VAULT_KEY_TEMPLATE.to_string()),
+        vault_key_map_path: value("--vault-key-map"),
+        secret_encodings,
+        placeholder,
+        inventory_path: value("--inventory"),
+        no_vault_if_empty: switch("--no-vault-if-empty")?,
+        checksum_guard: switch("--checksum-guard")?,
+        force: switch("--force")?,
+        dry_run,
+        show_secrets,
+        diff,
+        yes,
+        backup: switch("--backup")?,
+        verbose,
+        quiet,
+        check,
+        merge: switch("--merge")?,
+        minimal: switch("--minimal")?,
+        merge_vault: switch("--merge-vault")?,
+        rotate: switch("--rotate")?,
+        format,
+        only,
+        validate_only,
+    })))
+}
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [options] <template-file> <authn-file> <config-output> <vault-output>", program);
+    eprintln!("       {} --output-dir <dir> [options] <template-file> <authn-file>", program);
+    eprintln!("       {} --only vault [options] <authn-file> <vault-output>", program);
+    eprintln!("       {} --validate-only [options] <authn-file>", program);
+    eprintln!("       {} --canonicalize <file>", program);
+    eprintln!("       {} --authn-template <authn-template> <authn-output>", program);
+    eprintln!("       {} --generate-template", program);
+    eprintln!("       {} --list-providers", program);
+    eprintln!("  template-file: Path to config.textproto.template, or - to read it from stdin");
+    eprintln!("  authn-file: Path to .authn file with OAuth credentials and email configuration, or - to read it from stdin;");
+    eprintln!("              a comma-separated list of files is merged in order, later files overriding earlier ones key by key");
+    eprintln!("  config-output: Path to write the generated config.textproto");
+    eprintln!("  vault-output: Path to write the generated secrets.textproto");
+    eprintln!("Options:");
+    eprintln!("  --output-dir <dir>: Write <dir>/{} and <dir>/{} instead of taking the two output paths", DEFAULT_CONFIG_OUTPUT, DEFAULT_VAULT_OUTPUT);
+    eprintln!("  --no-validate: Skip re-parsing the generated files against the proto schema");
+    eprintln!("  --verify-no-template-leftovers: Fail if the generated config still contains {}", DEFAULT_FORBIDDEN_SUBSTRINGS.join(", "));
+    eprintln!("  --forbidden-substrings <a,b,...>: Comma-separated substrings to check for instead (implies --verify-no-template-leftovers)");
+    eprintln!("  --redaction-policy <file>: Fail if any config field path listed in this file is emitted in plaintext");
+    eprintln!("  --compare-config <config-file>: Report field differences against an existing config instead of writing; exits 1 if any");
+    eprintln!("  --config-patch <config-file>: Print a textproto patch of the fields that differ from an existing config instead of writing");
+    eprintln!("  --print-diff-summary: Print JSON change counts for config and vault against the existing outputs instead of writing");
+    eprintln!("  --check: Compare the existing outputs with what would be generated instead of writing; exits 1 on any drift");
+    eprintln!("  --time-budget <seconds>: Abort with an error naming the running phase if generation takes longer");
+    eprintln!("  --pre-hook <command>: Run a shell command (e.g. a secret refresh) before reading inputs; abort if it fails");
+    eprintln!("  --canonicalize <file>: Rewrite an existing config or vault file in the generator's canonical format");
+    eprintln!("  --descriptor-set <file>: Use this encoded FileDescriptorSet instead of the schema built into the binary");
+    eprintln!("  --vault-message <name>: Full name of the vault message in the schema (default {})", DEFAULT_VAULT_MESSAGE);
+    eprintln!("  --vault-format <textproto|binary>: Write the vault as textproto (default) or as the message's binary wire form");
+    eprintln!("  --authn-template <file>: Render ${{VAR}} references in an authn template from the environment into <authn-output>");
+    eprintln!("  --merge-strategy <override|append>: How a later authn file's <PROVIDER>_OAUTH_SCOPES combine with an earlier file's;");
+    eprintln!("                                      override (default) replaces them, append adds new scopes. Other keys always override");
+    eprintln!("  --providers-json <file>: Read OAuth providers from a JSON array of {{provider, client_id, client_secret, redirect_url, scopes}}");
+    eprintln!("                           objects, merged after <authn-file>; client secrets go to the vault as usual");
+    eprintln!("  --generate-template: Print a commented authn file listing every key the generator reads");
+    eprintln!("  --list-providers: Print each OAuth provider the schema knows, with its authn keys and the vault key of its client secret");
+    eprintln!("  --vault-key-template <template>: Vault key for provider client secrets (default {}); {{PROVIDER}} is the upper-cased provider name", DEFAULT_VAULT_KEY_TEMPLATE);
+    eprintln!("  --vault-key-map <file>: AUTHN_KEY=VAULT_KEY lines renaming individual secrets in the vault, e.g. EMAIL_SMTP_PASSWORD=SMTP_PASSWORD");
+    eprintln!("  --decode-base64 <keys>: Comma-separated secret authn keys whose values are base64 and are decoded for the vault");
+    eprintln!("  --decode-hex <keys>: Comma-separated secret authn keys whose values are hex and are decoded for the vault");
+    eprintln!("  --placeholder <token>: Sentinel left in the config for vault-held secrets and accepted only there (default {})", DEFAULT_PLACEHOLDER);
+    eprintln!("  --allow-orphan-secrets: Don't fail when a provider's client secret is set without its client ID");
+    eprintln!("  --strict-providers: Fail instead of warning when the template and authn file configure different OAuth providers");
+    eprintln!("  --header <text>: Comment block for the top of both outputs; {{template}} and {{timestamp}} are filled in, and \"\" leaves it out");
+    eprintln!("  --strict: Treat every warning as an error, including unknown authn keys and values the template doesn't use");
+    eprintln!("  --normalize-secrets: Trim surrounding whitespace from secrets, including quoted, @file and env values, with a warning for each");
+    eprintln!("  --inventory <file>: Also write a JSON inventory of client IDs, SMTP identity and vault key names (no secret values)");
+    eprintln!("  --no-vault-if-empty: Skip writing the vault file when there are no secrets to put in it");
+    eprintln!("  --checksum-guard: Record output checksums in <output>.checksum and refuse to overwrite edited outputs");
+    eprintln!("  --force: Overwrite outputs even if --checksum-guard detects a manual edit");
+    eprintln!("  --dry-run: Print the config to stdout and the vault, secrets masked, to stderr instead of writing any file");
+    eprintln!("  --show-secrets: With --dry-run, print the vault's secret values in plaintext");
+    eprintln!("  --diff: Print a diff of each existing output against its replacement (secrets masked) and ask before writing");
+    eprintln!("  --yes: With --diff, write without asking; required when there is no terminal to ask on");
+    eprintln!("  --backup: Rename existing outputs to <output>.bak before writing; abort if that fails");
+    eprintln!("  --verbose: Log each substitution, and warn about authn values the template has no placeholder for");
+    eprintln!("  --quiet: Don't print success messages such as \"Successfully generated ...\"; errors and warnings still go to stderr");
+    eprintln!("  --format <human|json>: With json, also print a summary of providers, vault keys and written outputs to stdout");
+    eprintln!("  --only <config|vault>: Write only that output, leaving the other and its directory untouched");
+    eprintln!("  --merge: Set only client IDs, email settings and CONFIG_ values in the existing <config-output>, keeping its other fields; uses the template if it doesn't exist");
+    eprintln!("  --config-format <textproto[,json]>: Also write the config as proto3 JSON; each format goes to <config-output> with its extension");
+    eprintln!("  --minimal: Write only the config fields the generator sets (client IDs, email settings, CONFIG_ values), not the template's other values");
+    eprintln!("  --merge-vault: Insert or update the generated secrets in the existing <vault-output>, keeping the other secrets in it");
+    eprintln!("  --rotate: Keep the existing <vault-output>'s value of each changed secret under <KEY>{}", PREVIOUS_SECRET_SUFFIX);
+    eprintln!("  --validate-only: Check the authn file's keys and values and print a summary of what it holds; no template is read and nothing is written");
+    eprintln!("Each argument and option defaults to a {}* environment variable when not passed,", ENV_PREFIX);
+    eprintln!("e.g. {}TEMPLATE, {}AUTHN, {}", ENV_PREFIX, ENV_PREFIX, flag_env_var("--no-validate"));
+    eprintln!("Exit codes: 0 success, {} differences found (or --diff not confirmed), {} bad arguments,", EXIT_DIFFERENCES, EXIT_USAGE);
+    eprintln!("{} invalid input or failed validation, {} file read/write failure, {} pre-hook or time budget failure", EXIT_INVALID, EXIT_IO, EXIT_ABORTED);
+}
+
+/// Exit code when a comparison (`--check`, `--compare-config`, ...) finds differences, or `--diff`
+/// isn't confirmed
+const EXIT_DIFFERENCES: u8 = 1;
+/// Exit code for unusable arguments or options
+const EXIT_USAGE: u8 = 2;
+/// Exit code when an input doesn't parse, or an output fails validation or a requested check
+const EXIT_INVALID: u8 = 3;
+/// Exit code when a file can't be read or written
+const EXIT_IO: u8 = 4;
+/// Exit code when the pre-hook fails or the time budget runs out
+const EXIT_ABORTED: u8 = 5;
+
+/// The exit code a run that failed with `error` reports
+fn error_exit_code(error: &GenError) -> ExitCode {
+    match error {
+        GenError::TemplateRead { .. } | GenError::AuthnRead { .. } | GenError::Write { .. } | GenError::Io { .. } => {
+            ExitCode::from(EXIT_IO)
+        }
+        _ => ExitCode::from(EXIT_INVALID),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    
+    let options = match parse_args(&args[1..], |name| env::var(name).ok()) {
+        Ok(Command::Generate(options)) => options,
+        Ok(Command::Canonicalize { path, descriptor_set_path, vault_message, quiet }) => {
+            let schema = match Schema::load_with(descriptor_set_path.as_deref(), &vault_message) {
+                Ok(schema) => schema,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return error_exit_code(&e);
+                }
+            };
+            if let Err(e) = canonicalize_file(&schema, &path) {
+                eprintln!("Error canonicalizing '{}': {}", path, e);
+                return error_exit_code(&e);
+            }
+            if !quiet {
+                eprintln!("Successfully canonicalized: {}", path);
+            }
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::GenerateTemplate) => {
+            print!("{}", authn_file_template());
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::ListProviders { descriptor_set_path, vault_key_template }) => {
+            match Schema::load(descriptor_set_path.as_deref()).and_then(|schema| provider_listing(&schema, &vault_key_template)) {
+                Ok(listing) => print!("{}", listing),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return error_exit_code(&e);
+                }
+            }
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::RenderAuthn { template_path, output_path, quiet }) => {
+            let template = match fs::read_to_string(&template_path) {
+                Ok(template) => template,
+                Err(e) => {
+                    eprintln!("Error reading authn template '{}': {}", template_path, e);
+                    return ExitCode::from(EXIT_IO);
+                }
+            };
+            let rendered = match render_authn_template(&template, |name| env::var(name).ok()) {
+                Ok(rendered) => rendered,
+                Err(e) => {
+                    eprintln!("Error rendering authn template '{}': {}", template_path, e);
+                    return ExitCode::from(EXIT_INVALID);
+                }
+            };
+            // The rendered file holds the secrets, like the vault
+            if let Err(e) = write_atomically(&output_path, rendered.as_bytes(), true) {
+                eprintln!("Error writing authn file '{}': {}", output_path, e);
+                return ExitCode::from(EXIT_IO);
+            }
+            if !quiet {
+                eprintln!("Successfully rendered authn file: {}", output_path);
+            }
+            return ExitCode::SUCCESS;
+        }
         Err(e) => {
-            eprintln!("Error reading template file '{}': {}", template_path, e);
-            process::exit(1);
+            eprintln!("Error: {}", e);
+            print_usage(&args[0]);
+            return ExitCode::from(EXIT_USAGE);
         }
     };
     
-    // Read authn file and parse OAuth credentials and email configuration
-    let authn_content = match fs::read_to_string(authn_path) {
-        Ok(content) => content,
+    match run(&options) {
+        Ok(code) => code,
+        Err(GenError::Template(e)) => {
+            eprintln!("Error in template file '{}': {}", input_name(&options.template_path), e);
+            ExitCode::from(EXIT_INVALID)
+        }
         Err(e) => {
-            eprintln!("Error reading authn file '{}': {}", authn_path, e);
-            process::exit(1);
+            eprintln!("Error: {}", e);
+            error_exit_code(&e)
         }
+    }
+}
+
+/// Generate the outputs, or do what a comparison option asks instead. A comparison that finds
+/// differences returns a failing exit code rather than an error.
+fn run(options: &Options) -> Result<ExitCode, GenError> {
+    if let Some(budget) = options.time_budget {
+        start_watchdog(budget);
+    }
+    let deadline = options.time_budget.map(|budget| (Instant::now() + budget, budget));
+    
+    set_phase("loading schema");
+    let schema = Schema::load_with(options.descriptor_set_path.as_deref(), &options.vault_message)?;
+    
+    // Give credential-refresh scripts a chance to (re)write the inputs first
+    if let Some(command) = &options.pre_hook {
+        set_phase(PRE_HOOK_PHASE);
+        if let Err(e) = run_pre_hook(command, deadline) {
+            eprintln!("Error: {}; aborting generation", e);
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    }
+    
+    let template_path = &options.template_path;
+    let config_output_path = &options.config_output_path;
+    let vault_output_path = &options.vault_output_path;
+    
+    set_phase("reading inputs");
+    
+    // A config being merged into replaces the template, which is then only needed for a first run
+    let merge_base = if options.merge { read_merge_base("config", config_output_path)? } else { None };
+    let write_config = options.only != Some(OutputKind::Vault);
+    let template = match merge_base {
+        Some(_) => String::new(),
+        None if !write_config || options.validate_only => String::new(),
+        None => read_input(template_path)
+            .map_err(|source| GenError::TemplateRead { path: input_name(template_path).to_string(), source })?,
     };
+    let mut authn_contents = Vec::new();
+    for authn_path in &options.authn_paths {
+        let content = read_input(authn_path)
+            .map_err(|source| GenError::AuthnRead { path: input_name(authn_path).to_string(), source })?;
+        authn_contents.push((input_name(authn_path), content, AuthnFormat::from_path(authn_path)));
+    }
+    // The provider array is the last layer, so its credentials win over the authn files'
+    if let Some(path) = &options.providers_json_path {
+        let content = read_input(path).map_err(|source| GenError::AuthnRead { path: input_name(path).to_string(), source })?;
+        authn_contents.push((input_name(path), content, AuthnFormat::ProvidersJson));
+    }
     
-    let authn_data = parse_authn_file(&authn_content);
+    set_phase("generating outputs");
     
-    // Replace <REDACTED> placeholder for client_id with actual value
-    // Client secret remains <REDACTED> as it will be loaded from vault
-    let mut config = template.replace("client_id: \"<REDACTED>\"", &format!("client_id: \"{}\"", authn_data.client_id));
+    // Keys missing from the file fall back to the environment; non-UTF-8 variables can't be authn values
+    let env_vars = env::vars_os().filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+    let authn_data = match authn_contents.as_slice() {
+        [(_, content, format)] => parse_authn_as(content, *format, env_vars)?,
+        layers => {
+            let layers: Vec<(&str, &str, AuthnFormat)> = layers.iter().map(|(name, content, format)| (*name, content.as_str(), *format)).collect();
+            parse_authn_layers_with(&layers, options.merge_strategy, env_vars)?
+        }
+    };
+    let mut warnings = Warnings { strict: options.strict, collected: Vec::new() };
+    for warning in &authn_data.warnings {
+        warnings.warn(warning);
+    }
     
-    // Replace empty email {} section with populated email configuration
-    // Email password remains <REDACTED> as it will be loaded from vault
-    let email_section = format!(
-        "email {{\n  smtp_host: \"{}\"\n  smtp_port: {}\n  smtp_username: \"{}\"\n  smtp_password: \"<REDACTED>\"\n  sender_name: \"{}\"\n  sender_address: \"{}\"\n}}",
-        authn_data.email_smtp_host,
-        authn_data.email_smtp_port,
-        authn_data.email_smtp_username,
-        authn_data.email_sender_name,
-        authn_data.email_sender_address
-    );
-    config = config.replace("email {}", &email_section);
+    // A provider secret without its client ID can't enable the provider, so vaulting it is misleading
+    if !options.allow_orphan_secrets {
+        let orphans = find_orphan_provider_secrets(&authn_data.keys);
+        if !orphans.is_empty() {
+            let mut message = String::from("authn file has OAuth client secrets without a client ID:");
+            for (secret_key, id_key) in orphans {
+                message.push_str(&format!("\n  {} is set but {} is not", secret_key, id_key));
+            }
+            message.push_str("\nAdd the client ID, remove the secret, or pass --allow-orphan-secrets");
+            return Err(GenError::Rejected(message));
+        }
+    }
     
-    // Generate vault file with client secret and email password (client ID and email non-secrets are in config file, not vault)
-    let vault_content = match generate_vault_file(&authn_data.client_secret, &authn_data.email_smtp_password) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error generating vault file: {}", e);
-            process::exit(1);
+    // The parser trims unquoted values, which only the raw line shows; the library trims and reports
+    // secrets that are still padded once quotes, `@path` files and fallbacks are resolved
+    if options.normalize_secrets {
+        for (name, content, _) in authn_contents.iter().filter(|(_, _, format)| *format == AuthnFormat::KeyValue) {
+            // Layered files are told apart by name
+            let file = if authn_contents.len() > 1 { format!("{} ", name) } else { String::new() };
+            for (line_number, key) in find_padded_secrets(content) {
+                warnings.warn(&format!(
+                    "trimmed surrounding whitespace from {} ({}line {}); check the source it was copied from",
+                    key, file, line_number
+                ));
+            }
+        }
+    }
+    
+    let vault_keys = match &options.vault_key_map_path {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .map_err(|source| GenError::Io { context: format!("failed to read vault key map '{}'", path), source })?;
+            parse_vault_key_map(&content).map_err(|e| GenError::Step(format!("invalid vault key map '{}': {}", path, e)))?
+        }
+        None => BTreeMap::new(),
+    };
+    // Without an existing vault there is nothing to rotate from, so the secrets are written as new
+    let previous_secrets = if options.rotate && options.only != Some(OutputKind::Config) {
+        let existing = read_existing_vault(&schema, vault_output_path, options.vault_format, None)?
+            .map(|text| vault_secret_map(&schema, &text))
+            .transpose()
+            .map_err(|e| GenError::Step(format!("cannot rotate secrets in '{}': {}", vault_output_path, e)))?;
+        Some(existing.unwrap_or_default())
+    } else {
+        None
+    };
+    let generate_options = GenerateOptions {
+        vault_key_template: options.vault_key_template.clone(),
+        vault_keys,
+        placeholder: options.placeholder.clone(),
+        validate: options.validate,
+        strict_providers: options.strict_providers,
+        secret_encodings: options.secret_encodings.clone(),
+        header: Some(output_header(options.header.as_deref(), (merge_base.is_none() && write_config).then_some(template_path.as_str()), merge_base.is_some())),
+        previous_secrets,
+        normalize_secrets: options.normalize_secrets,
+        minimal: options.minimal,
+    };
+    
+    // Everything the authn file alone decides has been checked, apart from the secrets' vault keys
+    // and the CONFIG_ values against the schema
+    if options.validate_only {
+        let vault = generate_vault_with(&schema, &authn_data, &generate_options)?;
+        for warning in &vault.warnings {
+            warnings.warn(warning);
+        }
+        check_config_values(&schema, &authn_data)?;
+        warnings.check()?;
+        if !options.quiet {
+            print!("{}", authn_summary(&authn_data, &vault.secrets));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    
+    let output = match &merge_base {
+        _ if !write_config => {
+            let vault = generate_vault_with(&schema, &authn_data, &generate_options)?;
+            GeneratedOutput {
+                config: String::new(),
+                vault: vault.vault,
+                secrets: vault.secrets,
+                notes: Vec::new(),
+                warnings: vault.warnings,
+                providers: authn_data.oauth_providers.iter().map(|provider| provider.name.clone()).collect(),
+                defaults: authn_data.applied_defaults(),
+            }
+        }
+        Some(existing) => merge_with(&schema, existing, &authn_data, &generate_options).map_err(|e| match e {
+            GenError::Step(message) => GenError::Step(format!("cannot merge into '{}': {}", config_output_path, message)),
+            other => other,
+        })?,
+        None => generate_with(&schema, &template, &authn_data, &generate_options)?,
+    };
+    for warning in &output.warnings {
+        warnings.warn(warning);
+    }
+    // Unused authn values are only reported with --verbose, but --strict doesn't let them pass
+    if options.verbose || options.strict {
+        for note in &output.notes {
+            match note {
+                FillNote::Filled(message) if options.verbose => eprintln!("{}", message),
+                FillNote::Filled(_) => {}
+                FillNote::Unmatched(message) => warnings.warn(message),
+            }
+        }
+    }
+    let config = output.config;
+    let vault_base = if options.merge_vault && options.only != Some(OutputKind::Config) {
+        match options.vault_format {
+            VaultFormat::Textproto => read_merge_base("vault", vault_output_path)?,
+            VaultFormat::Binary => read_existing_vault(&schema, vault_output_path, VaultFormat::Binary, None)?,
+        }
+    } else {
+        None
+    };
+    // Merging builds the same secrets again, so its warnings were already given with the output's
+    let (vault_content, secrets) = match vault_base {
+        Some(existing) => {
+            let merged = merge_vault_with(&schema, &existing, &authn_data, &generate_options).map_err(|e| match e {
+                GenError::Step(message) => GenError::Step(format!("cannot merge into '{}': {}", vault_output_path, message)),
+                other => other,
+            })?;
+            if options.verbose {
+                let kept: Vec<&str> = merged.secrets.keys().filter(|key| !output.secrets.contains_key(*key)).map(String::as_str).collect();
+                eprintln!("kept {} existing vault secret(s): {}", kept.len(), kept.join(", "));
+            }
+            (merged.vault, merged.secrets)
+        }
+        None => (output.vault, output.secrets),
+    };
+    if options.verbose {
+        if let Some(previous) = &generate_options.previous_secrets {
+            let rotated: Vec<&str> = secrets
+                .iter()
+                .filter(|(key, value)| previous.get(*key).is_some_and(|old| old != *value))
+                .map(|(key, _)| key.as_str())
+                .collect();
+            eprintln!("rotated {} vault secret(s): {}", rotated.len(), rotated.join(", "));
+        }
+    }
+    
+    // Why the vault isn't written, if it isn't
+    let vault_skipped = if options.only == Some(OutputKind::Config) {
+        Some("only the config was requested")
+    } else if options.no_vault_if_empty && secrets.is_empty() {
+        Some("there are no secrets to write")
+    } else {
+        None
+    };
+    let write_vault = vault_skipped.is_none();
+    warnings.check()?;
+    
+    set_phase("validating outputs");
+    
+    // Enforce the security policy's list of fields that belong in the vault
+    if let Some(policy_path) = &options.redaction_policy_path {
+        let policy = fs::read_to_string(policy_path)
+            .map_err(|source| GenError::Io { context: format!("failed to read redaction policy '{}'", policy_path), source })?;
+        let violations = parse_redaction_policy(&schema.config, &policy)
+            .and_then(|patterns| find_policy_violations(&schema, &config, &patterns, &options.placeholder))
+            .map_err(GenError::Step)?;
+        if !violations.is_empty() {
+            let mut message =
+                String::from("generated config emits redaction-policy fields in plaintext (they must be routed to the vault):");
+            for path in violations {
+                message.push_str(&format!("\n  {}", path));
+            }
+            return Err(GenError::Rejected(message));
+        }
+    }
+    
+    // Catch example values and TODO markers that survived from the template
+    if let Some(forbidden) = &options.forbidden_substrings {
+        let leftovers = find_template_leftovers(&config, forbidden);
+        if !leftovers.is_empty() {
+            let mut message = String::from("generated config contains template leftovers:");
+            for (line_number, substring) in leftovers {
+                message.push_str(&format!("\n  line {}: '{}'", line_number, substring));
+            }
+            return Err(GenError::Rejected(message));
+        }
+    }
+    
+    // Compare against an existing config instead of writing anything
+    if let Some(existing_path) = &options.compare_config_path {
+        let existing = fs::read_to_string(existing_path)
+            .map_err(|source| GenError::Io { context: format!("failed to read config file '{}'", existing_path), source })?;
+        let changes = compare_messages(&schema.config, &existing, &config)
+            .map_err(|e| GenError::Step(format!("failed to compare configs: {}", e)))?;
+        for change in &changes {
+            println!("{}", change);
+        }
+        if changes.is_empty() {
+            if !options.quiet {
+                eprintln!("No differences from {}", existing_path);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        eprintln!("{} field(s) differ from {}", changes.len(), existing_path);
+        return Ok(ExitCode::from(EXIT_DIFFERENCES));
+    }
+    
+    // Print only the fields that differ from an existing config instead of writing anything
+    if let Some(existing_path) = &options.config_patch_path {
+        let existing = fs::read_to_string(existing_path)
+            .map_err(|source| GenError::Io { context: format!("failed to read config file '{}'", existing_path), source })?;
+        let (patch, removed) = compare_messages(&schema.config, &existing, &config)
+            .and_then(|changes| {
+                let removed = changes
+                    .into_iter()
+                    .filter(|change| matches!(change, FieldChange::Removed { .. }))
+                    .map(|change| change.path().to_string())
+                    .collect::<Vec<_>>();
+                Ok((config_patch(&schema.config, &existing, &config)?, removed))
+            })
+            .map_err(|e| GenError::Step(format!("failed to build config patch: {}", e)))?;
+        for path in removed {
+            warnings.warn(&format!("{} is not generated but can't be removed by a patch", path));
+        }
+        warnings.check()?;
+        if !has_fields(&patch) {
+            if !options.quiet {
+                eprintln!("No differences from {}", existing_path);
+            }
+        } else {
+            println!("{}", to_canonical_text(&patch));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    
+    // Summarize how the existing outputs would change instead of writing anything
+    if options.print_diff_summary {
+        let summary = [("config", &schema.config, config_output_path, &config), ("vault", &schema.vault, vault_output_path, &vault_content)]
+            .into_iter()
+            .filter(|(name, ..)| match options.only {
+                Some(OutputKind::Config) => *name == "config",
+                Some(OutputKind::Vault) => *name == "vault",
+                None => true,
+            })
+            .map(|(name, descriptor, path, generated)| {
+                // A missing output is compared as empty, so every generated field counts as added
+                let existing = if name == "vault" && options.vault_format == VaultFormat::Binary {
+                    read_existing_vault(&schema, path, VaultFormat::Binary, None)?.unwrap_or_default()
+                } else {
+                    match fs::read_to_string(path) {
+                        Ok(content) => content,
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                        Err(source) => return Err(GenError::Io { context: format!("failed to read existing {} '{}'", name, path), source }),
+                    }
+                };
+                compare_messages(descriptor, &existing, generated)
+                    .map(|changes| (name, changes))
+                    .map_err(|e| GenError::Step(format!("failed to compare outputs: {}", e)))
+            })
+            .collect::<Result<Vec<_>, GenError>>()?;
+        println!("{}", diff_summary_json(&summary));
+        return Ok(ExitCode::SUCCESS);
+    }
+    
+    // Verify the existing outputs instead of writing anything, e.g. for drift detection in CI
+    if options.check {
+        let mut drift = Vec::new();
+        let mut checked = Vec::new();
+        if write_config {
+            drift.extend(
+                check_config_output(&schema, config_output_path, &config)?,
+            );
+            checked.push(config_output_path.as_str());
+        }
+        if options.only != Some(OutputKind::Config) {
+            drift.extend(
+                check_vault_output(&schema, vault_output_path, options.vault_format, write_vault.then_some(&secrets))?,
+            );
+            checked.push(vault_output_path.as_str());
+        }
+        for line in &drift {
+            println!("{}", line);
+        }
+        if drift.is_empty() {
+            if !options.quiet {
+                eprintln!("{} {} up to date", checked.join(" and "), if checked.len() == 1 { "is" } else { "are" });
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        eprintln!("{} difference(s) from the outputs the template and authn file generate", drift.len());
+        return Ok(ExitCode::from(EXIT_DIFFERENCES));
+    }
+    
+    // Build the inventory up front so a failure doesn't leave outputs without it
+    let inventory = match &options.inventory_path {
+        Some(_) => Some(
+            build_inventory(&schema, &config, &secrets)
+                .map_err(|e| GenError::Step(format!("failed to build inventory: {}", e)))?,
+        ),
+        None => None,
+    };
+    // Likewise the JSON config
+    let config_json = match &options.config_json_path {
+        Some(_) if write_config => Some(config_to_json(&schema, &config)?),
+        _ => None,
+    };
+    // Likewise the binary vault, which is checked by decoding it again
+    let vault_file = match options.vault_format {
+        VaultFormat::Textproto => vault_content.clone().into_bytes(),
+        VaultFormat::Binary => encode_vault(&schema, &secrets, VaultFormat::Binary, None)?,
+    };
+    
+    let mut outputs = Vec::new();
+    if write_config {
+        outputs.push((config_output_path, &config));
+    }
+    if write_vault {
+        outputs.push((vault_output_path, &vault_content));
+    }
+    
+    // Check all outputs before writing any so a refusal leaves them untouched. A merged config
+    // keeps its hand edits, so there is nothing for the guard to protect there.
+    if options.checksum_guard && !options.force {
+        for &(output_path, _) in outputs.iter().filter(|(path, _)| merge_base.is_none() || *path != config_output_path) {
+            check_unmodified(output_path)?;
+        }
+    }
+    
+    // Show what would be written, touching nothing
+    if options.dry_run {
+        if write_config {
+            print!("{}", config);
+            eprintln!("Dry run: the config above would be written to {}", config_output_path);
+            if let Some(json_path) = &options.config_json_path {
+                eprintln!("Dry run: the config would also be written as JSON to {}", json_path);
+            }
+        }
+        match vault_skipped {
+            None => {
+                // The binary form isn't printable, so the vault is always shown as textproto
+                let binary = if options.vault_format == VaultFormat::Binary { ", in binary," } else { "" };
+                let masked = if options.show_secrets { "" } else { " (secrets masked; --show-secrets prints them)" };
+                eprintln!("Dry run: vault that would be written to {}{} shown as textproto{}:", vault_output_path, binary, masked);
+                for line in vault_content.trim_end().lines() {
+                    eprintln!("{}", if options.show_secrets { line.to_string() } else { mask_vault_line(line) });
+                }
+            }
+            Some(reason) => eprintln!("Dry run: vault file {} would be skipped: {}", vault_output_path, reason),
+        }
+        if let Some(inventory_path) = &options.inventory_path {
+            eprintln!("Dry run: an inventory would be written to {}", inventory_path);
         }
+        return Ok(ExitCode::SUCCESS);
+    }
+    
+    // Show what writing would change, and let the user back out before anything is touched
+    if options.diff {
+        let mut changed = 0;
+        for &(output_path, content) in &outputs {
+            let existing = if output_path == vault_output_path {
+                read_existing_vault(&schema, output_path, options.vault_format, generate_options.header.as_deref())?
+            } else {
+                read_existing_output(output_path)?
+            };
+            let Some(existing) = existing else { continue };
+            let show = |line: &str| if output_path == vault_output_path { mask_vault_line(line) } else { line.to_string() };
+            let hunks = unified_diff(&existing, content, &show);
+            if hunks.is_empty() {
+                eprintln!("{} is unchanged", output_path);
+                continue;
+            }
+            changed += 1;
+            println!("--- {}", output_path);
+            println!("+++ {} (generated)", output_path);
+            for line in hunks {
+                println!("{}", line);
+            }
+        }
+        if changed > 0 && !options.yes {
+            // Stdin that held an input has nothing left to answer with
+            let interactive = io::stdin().is_terminal()
+                && template_path != STDIN_PATH
+                && !options.authn_paths.iter().any(|path| path == STDIN_PATH);
+            if !interactive {
+                return Err(GenError::Rejected(format!(
+                    "--diff: {} existing output(s) would change, but stdin is not a terminal to confirm on; pass --yes to write anyway. Nothing was written",
+                    changed
+                )));
+            }
+            eprint!("Overwrite {} output(s) with these changes? [y/N] ", changed);
+            let mut answer = String::new();
+            io::stdin()
+                .read_line(&mut answer)
+                .map_err(|source| GenError::Io { context: "failed to read the answer".to_string(), source })?;
+            if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+                eprintln!("Not confirmed; nothing was written");
+                return Ok(ExitCode::from(EXIT_DIFFERENCES));
+            }
+        }
+    }
+    
+    // Past this point the watchdog stands down so outputs are never left half-written
+    set_phase(WRITING_PHASE);
+    
+    // Compare before backups move the old outputs aside
+    let mut summary = RunSummary {
+        providers: output.providers,
+        defaults: output.defaults,
+        vault_keys: secrets.keys().cloned().collect(),
+        outputs: Vec::new(),
     };
+    if write_config {
+        summary.outputs.push(OutputSummary::new("config", config_output_path, Some(config.as_bytes())));
+    }
+    if let (Some(json_path), Some(json)) = (&options.config_json_path, &config_json) {
+        summary.outputs.push(OutputSummary::new("config_json", json_path, Some(json.as_bytes())));
+    }
+    summary.outputs.push(OutputSummary::new("vault", vault_output_path, write_vault.then_some(vault_file.as_slice())));
+    if let (Some(inventory_path), Some(inventory)) = (&options.inventory_path, &inventory) {
+        summary.outputs.push(OutputSummary::new("inventory", inventory_path, Some(inventory.as_bytes())));
+    }
     
     // Ensure vault output directory exists
-    if let Some(vault_dir) = Path::new(vault_output_path).parent() {
-        if let Err(e) = fs::create_dir_all(vault_dir) {
-            eprintln!("Error creating vault directory '{}': {}", vault_dir.display(), e);
-            process::exit(1);
+    if let Some(vault_dir) = Path::new(vault_output_path).parent().filter(|_| write_vault) {
+        fs::create_dir_all(vault_dir)
+            .map_err(|source| GenError::Write { path: vault_dir.display().to_string(), source })?;
+    }
+    
+    // Back up every existing output before writing any. The backups are copies, so when one fails the
+    // run stops with every output still at its path, unchanged
+    if options.backup {
+        let mut targets: Vec<&str> = outputs.iter().map(|(path, _)| path.as_str()).collect();
+        targets.extend(options.config_json_path.as_deref().filter(|_| config_json.is_some()));
+        targets.extend(options.inventory_path.as_deref());
+        for path in targets {
+            if let Some(backup_path) = back_up_output(path)?.filter(|_| !options.quiet) {
+                eprintln!("Backed up {} to {}", path, backup_path);
+            }
         }
     }
     
-    // Write config file
-    match fs::write(config_output_path, config) {
-        Ok(_) => {
+    if write_config {
+        write_output(config_output_path, &config)?;
+        if !options.quiet {
             eprintln!("Successfully generated config file: {}", config_output_path);
         }
-        Err(e) => {
-            eprintln!("Error writing config file '{}': {}", config_output_path, e);
-            process::exit(1);
+    }
+    if let (Some(json_path), Some(json)) = (&options.config_json_path, &config_json) {
+        write_output(json_path, json)?;
+        if !options.quiet {
+            eprintln!("Successfully generated JSON config file: {}", json_path);
         }
     }
     
-    // Write vault file
-    match fs::write(vault_output_path, vault_content) {
-        Ok(_) => {
+    if let Some(reason) = vault_skipped {
+        if !options.quiet {
+            eprintln!("Skipped vault file {}: {}", vault_output_path, reason);
+        }
+    } else {
+        write_secret_output(vault_output_path, &vault_file)?;
+        if options.verbose {
+            let keys: Vec<&str> = secrets.keys().map(String::as_str).collect();
+            eprintln!("wrote {} vault secret(s): {}", keys.len(), keys.join(", "));
+        }
+        if !options.quiet {
             eprintln!("Successfully generated vault file: {}", vault_output_path);
         }
-        Err(e) => {
-            eprintln!("Error writing vault file '{}': {}", vault_output_path, e);
-            process::exit(1);
+    }
+    
+    if options.checksum_guard {
+        if write_config {
+            record_checksum(config_output_path, config.as_bytes())?;
+        }
+        if write_vault {
+            record_checksum(vault_output_path, &vault_file)?;
         }
     }
+    
+    if let (Some(inventory_path), Some(inventory)) = (&options.inventory_path, inventory) {
+        write_output(inventory_path, &inventory)?;
+        if !options.quiet {
+            eprintln!("Successfully generated inventory file: {}", inventory_path);
+        }
+    }
+    
+    if options.format == OutputFormat::Json {
+        println!("{}", summary.to_json());
+    }
+    
+    Ok(ExitCode::SUCCESS)
 }
 
-/// Structure to hold all parsed authentication and email configuration
-struct AuthnData {
-    client_id: String,
-    client_secret: String,
-    email_smtp_host: String,
-    email_smtp_port: u16,
-    email_smtp_username: String,
-    email_smtp_password: String,
-    email_sender_name: String,
-    email_sender_address: String,
+/// Warnings found during a run: printed as they come, or with `--strict` collected and turned into
+/// one error before anything is written
+struct Warnings {
+    strict: bool,
+    collected: Vec<String>,
 }
 
-/// Parse the .authn file and extract Google OAuth credentials and email configuration
-fn parse_authn_file(content: &str) -> AuthnData {
-    let mut client_id = None;
-    let mut client_secret = None;
-    let mut email_smtp_host = None;
-    let mut email_smtp_port = None;
-    let mut email_smtp_username = None;
-    let mut email_smtp_password = None;
-    let mut email_sender_name = None;
-    let mut email_sender_address = None;
+impl Warnings {
+    fn warn(&mut self, message: &str) {
+        if self.strict {
+            self.collected.push(message.to_string());
+        } else {
+            eprintln!("Warning: {}", message);
+        }
+    }
     
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
+    /// Fail if `--strict` collected any warnings so far
+    fn check(&mut self) -> Result<(), GenError> {
+        if self.collected.is_empty() {
+            return Ok(());
+        }
+        let collected = std::mem::take(&mut self.collected);
+        Err(GenError::Rejected(format!("--strict: {} warning(s) treated as errors:\n  {}", collected.len(), collected.join("\n  "))))
+    }
+}
+
+/// What a valid authn file holds, for `--validate-only`: one line per part, naming keys, hosts and
+/// addresses but never a secret value
+fn authn_summary(authn: &AuthnData, secrets: &BTreeMap<String, String>) -> String {
+    let list = |items: Vec<String>| if items.is_empty() { "none".to_string() } else { items.join(", ") };
+    let mut summary = format!("auth mode: {}\n", authn.auth_mode);
+    let providers = authn
+        .oauth_providers
+        .iter()
+        .map(|provider| if provider.client_secret.is_none() { format!("{} (PKCE)", provider.name) } else { provider.name.clone() })
+        .collect();
+    summary.push_str(&format!("oauth providers: {}\n", list(providers)));
+    let email = |settings: &EmailSettings| {
+        format!("{}:{} as {} <{}>", settings.smtp_host, settings.smtp_port, settings.sender_name, settings.sender_address)
+    };
+    summary.push_str(&format!("email: {}\n", authn.email.as_ref().map(email).unwrap_or_else(|| "none".to_string())));
+    for named in &authn.named_emails {
+        summary.push_str(&format!("{} email: {}\n", named.name, email(&named.settings)));
+    }
+    if !authn.config_values.is_empty() {
+        summary.push_str(&format!("config values: {}\n", list(authn.config_values.iter().map(|(path, _)| path.clone()).collect())));
+    }
+    summary.push_str(&format!("vault secrets: {}\n", list(secrets.keys().cloned().collect())));
+    if !authn.unknown_keys.is_empty() {
+        summary.push_str(&format!("keys only #if conditionals can use: {}\n", authn.unknown_keys.join(", ")));
+    }
+    summary
+}
+
+/// What a successful run did, for `--format json`. Holds names and paths only, never secret values.
+struct RunSummary {
+    /// Configured OAuth providers, sorted
+    providers: Vec<String>,
+    /// Authn settings that took their default, as `KEY=value`
+    defaults: Vec<String>,
+    /// Vault secret key names, sorted
+    vault_keys: Vec<String>,
+    outputs: Vec<OutputSummary>,
+}
+
+/// One output file of a run
+struct OutputSummary {
+    /// `config`, `vault` or `inventory`
+    name: &'static str,
+    path: String,
+    /// False for a vault skipped by `--no-vault-if-empty` or `--only config`
+    written: bool,
+    /// Whether the file's contents differ from before the run (a new file counts as changed)
+    changed: bool,
+}
+
+impl OutputSummary {
+    /// Summarize writing `content` to `path`, or skipping it for `None`; call before writing
+    fn new(name: &'static str, path: &str, content: Option<&[u8]>) -> Self {
+        let changed = content.is_some_and(|content| fs::read(path).ok().as_deref() != Some(content));
+        OutputSummary { name, path: path.to_string(), written: content.is_some(), changed }
+    }
+}
+
+impl RunSummary {
+    /// Render as a single-line JSON object, e.g.
+    /// `{"providers":["google"],"vault_keys":[...],"outputs":[{"name":"config","path":"...","written":true,"changed":false}]}`
+    fn to_json(&self) -> String {
+        let strings = |values: &[String]| values.iter().map(|value| json_string(value)).collect::<Vec<_>>().join(",");
+        let outputs: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|output| {
+                format!(
+                    "{{\"name\":{},\"path\":{},\"written\":{},\"changed\":{}}}",
+                    json_string(output.name),
+                    json_string(&output.path),
+                    output.written,
+                    output.changed
+                )
+            })
+            .collect();
+        format!(
+            "{{\"providers\":[{}],\"defaults\":[{}],\"vault_keys\":[{}],\"outputs\":[{}]}}",
+            strings(&self.providers),
+            strings(&self.defaults),
+            strings(&self.vault_keys),
+            outputs.join(",")
+        )
+    }
+}
+
+/// The existing config or vault for `--merge` or `--merge-vault`, or `None` if there is none yet
+fn read_merge_base(name: &str, path: &str) -> Result<Option<String>, GenError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(GenError::Io { context: format!("failed to read {} '{}' to merge into", name, path), source }),
+    }
+}
+
+/// An output's existing contents for `--check`, `--diff` and `--rotate`, or `None` if it doesn't exist
+fn read_existing_output(path: &str) -> Result<Option<String>, GenError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(GenError::Io { context: format!("failed to read '{}'", path), source }),
+    }
+}
+
+/// The existing vault for `--check`, `--diff`, `--rotate`, `--merge-vault` and
+/// `--print-diff-summary`, or `None` if it doesn't exist. A binary vault is decoded and rendered as
+/// textproto with `header`, so it compares with the generated vault as a textproto one would.
+fn read_existing_vault(schema: &Schema, path: &str, format: VaultFormat, header: Option<&str>) -> Result<Option<String>, GenError> {
+    if format == VaultFormat::Textproto {
+        return read_existing_output(path);
+    }
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(GenError::Io { context: format!("failed to read '{}'", path), source }),
+    };
+    let vault = DynamicMessage::decode(schema.vault.clone(), bytes.as_slice()).map_err(|e| {
+        GenError::Step(format!("existing vault '{}' is not a valid binary {} message: {}", path, schema.vault.full_name(), e))
+    })?;
+    let text = encode_vault(schema, &secret_entries(&vault), VaultFormat::Textproto, header)?;
+    String::from_utf8(text).map(Some).map_err(|e| GenError::Serialize(e.to_string()))
+}
+
+/// Lines of context around each change in `--diff` output
+const DIFF_CONTEXT: usize = 3;
+
+/// A line-based diff of `old` against `new` in unified format, with [`DIFF_CONTEXT`] lines of
+/// context and every line passed through `show`; empty if they are the same
+fn unified_diff(old: &str, new: &str, show: &dyn Fn(&str) -> String) -> Vec<String> {
+    let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    
+    // The edit script as (marker, old line number, new line number, line), numbers 0-based
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', i, j, old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(('-', i, j, old[i]));
+            i += 1;
+        } else {
+            edits.push(('+', i, j, new[j]));
+            j += 1;
+        }
+    }
+    
+    // Changes closer than twice the context share a hunk
+    let changes: Vec<usize> = edits.iter().enumerate().filter(|(_, edit)| edit.0 != ' ').map(|(index, _)| index).collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &index in &changes {
+        let (start, end) = (index.saturating_sub(DIFF_CONTEXT), (index + DIFF_CONTEXT + 1).min(edits.len()));
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    
+    let mut lines = Vec::new();
+    for (start, end) in hunks {
+        let hunk = &edits[start..end];
+        let old_count = hunk.iter().filter(|edit| edit.0 != '+').count();
+        let new_count = hunk.iter().filter(|edit| edit.0 != '-').count();
+        // An empty side is numbered by the line before it, as `diff -u` does
+        let (old_start, new_start) = (hunk[0].1 + usize::from(old_count > 0), hunk[0].2 + usize::from(new_count > 0));
+        lines.push(format!("@@ -{},{} +{},{} @@", old_start, old_count, new_start, new_count));
+        lines.extend(hunk.iter().map(|(marker, _, _, line)| format!("{}{}", marker, show(line))));
+    }
+    lines
+}
+
+/// A vault line with every quoted string except secret keys masked, so `--diff` and `--dry-run`
+/// show which secrets are written without showing them. Comment lines are kept as they are.
+fn mask_vault_line(line: &str) -> String {
+    if line.trim_start().starts_with('#') {
+        return line.to_string();
+    }
+    let mut masked = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' && c != '\'' {
+            masked.push(c);
             continue;
         }
-        
-        if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim();
-            let value = value.trim();
-            
-            match key {
-                "GOOGLE_OAUTH_CLIENT_ID" => {
-                    client_id = Some(value.to_string());
-                }
-                "GOOGLE_OAUTH_CLIENT_SECRET" => {
-                    client_secret = Some(value.to_string());
-                }
-                "EMAIL_SMTP_HOST" => {
-                    email_smtp_host = Some(value.to_string());
-                }
-                "EMAIL_SMTP_PORT" => {
-                    email_smtp_port = Some(value.parse::<u16>().unwrap_or_else(|_| {
-                        eprintln!("Error: EMAIL_SMTP_PORT must be a valid number");
-                        process::exit(1);
-                    }));
-                }
-                "EMAIL_SMTP_USERNAME" => {
-                    email_smtp_username = Some(value.to_string());
-                }
-                "EMAIL_SMTP_PASSWORD" => {
-                    email_smtp_password = Some(value.to_string());
+        let mut text = String::new();
+        let mut escaped = false;
+        for next in chars.by_ref() {
+            if !escaped && next == c {
+                break;
+            }
+            escaped = !escaped && next == '\\';
+            text.push(next);
+        }
+        if masked.trim_end().ends_with("key:") {
+            masked.push_str(&format!("{c}{text}{c}"));
+        } else {
+            masked.push_str(&format!("{c}{}{c}", redact(&text)));
+        }
+    }
+    masked
+}
+
+/// Differences between the existing config and the generated one, one line each, listed like
+/// `--compare-config`
+fn check_config_output(schema: &Schema, config_path: &str, config: &str) -> Result<Vec<String>, GenError> {
+    match read_existing_output(config_path)? {
+        Some(existing) => {
+            let changes = compare_messages(&schema.config, &existing, config).map_err(check_failed)?;
+            Ok(changes.iter().map(|change| format!("config: {}", change)).collect())
+        }
+        None => Ok(vec![format!("config: {} does not exist", config_path)]),
+    }
+}
+
+/// Differences between the existing vault and the generated secrets, one line each. Secrets are
+/// compared by value but only named, never shown. `secrets` is `None` when no vault would be
+/// written, so a missing vault is no drift then.
+fn check_vault_output(
+    schema: &Schema,
+    vault_path: &str,
+    format: VaultFormat,
+    secrets: Option<&BTreeMap<String, String>>,
+) -> Result<Vec<String>, GenError> {
+    let mut drift = Vec::new();
+    let empty = BTreeMap::new();
+    match (read_existing_vault(schema, vault_path, format, None)?, secrets) {
+        (Some(existing), secrets) => {
+            let existing = vault_secret_map(schema, &existing).map_err(check_failed)?;
+            let generated = secrets.unwrap_or(&empty);
+            for (key, value) in &existing {
+                match generated.get(key) {
+                    None => drift.push(format!("vault: - {} (no longer generated)", key)),
+                    Some(generated_value) if generated_value != value => drift.push(format!("vault: ~ {} differs", key)),
+                    Some(_) => {}
                 }
-                "EMAIL_SENDER_NAME" => {
-                    email_sender_name = Some(value.to_string());
+            }
+            for key in generated.keys().filter(|key| !existing.contains_key(*key)) {
+                drift.push(format!("vault: + {} (missing from the existing vault)", key));
+            }
+        }
+        (None, Some(_)) => drift.push(format!("vault: {} does not exist", vault_path)),
+        (None, None) => {}
+    }
+    Ok(drift)
+}
+
+/// An existing output that `--check` can't compare with
+fn check_failed(message: String) -> GenError {
+    GenError::Step(format!("failed to check outputs: {}", message))
+}
+
+/// A vault file's secrets by key
+fn vault_secret_map(schema: &Schema, text: &str) -> Result<BTreeMap<String, String>, String> {
+    let vault = DynamicMessage::parse_text_format(schema.vault.clone(), text)
+        .map_err(|e| format!("existing vault is not a valid {} message: {}", schema.vault.full_name(), redact_parse_error(&e)))?;
+    Ok(secret_entries(&vault))
+}
+
+/// The `secrets` map of a vault message
+fn secret_entries(vault: &DynamicMessage) -> BTreeMap<String, String> {
+    let mut secrets = BTreeMap::new();
+    if let Some(Value::Map(entries)) = vault.get_field_by_name("secrets").as_deref() {
+        for (key, value) in entries {
+            if let (MapKey::String(key), Value::String(value)) = (key, value) {
+                secrets.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    secrets
+}
+
+/// Copy an existing output to `<output>.bak`, replacing an older backup, and return the backup's
+/// path. Outputs that don't exist yet need no backup. A copy rather than a rename keeps the output
+/// in place, with its permissions, until the atomic write replaces it.
+fn back_up_output(path: &str) -> Result<Option<String>, GenError> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    let backup_path = format!("{}.bak", path);
+    fs::copy(path, &backup_path).map_err(|source| GenError::Io {
+        context: format!("failed to back up '{}' to '{}'; no output was written", path, backup_path),
+        source,
+    })?;
+    Ok(Some(backup_path))
+}
+
+/// Input path meaning "read from stdin"
+const STDIN_PATH: &str = "-";
+
+/// Read an input file, or all of stdin for `-`. An empty stdin reads as an empty file.
+fn read_input(path: &str) -> std::io::Result<String> {
+    if path == STDIN_PATH {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// The comment block under each output's first line: `--header` with `{template}` and `{timestamp}`
+/// filled in, or else where the outputs came from. The default leaves out the time so that an
+/// unchanged run writes identical bytes; an empty `--header` leaves the block out.
+fn output_header(header: Option<&str>, template_path: Option<&str>, merge: bool) -> String {
+    let template = template_path.map(|path| match Path::new(path).file_name() {
+        _ if path == STDIN_PATH => "stdin".to_string(),
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string(),
+    });
+    match (header, template) {
+        (Some(header), template) => {
+            header.replace("{template}", template.as_deref().unwrap_or("none")).replace("{timestamp}", &utc_timestamp(SystemTime::now()))
+        }
+        (None, _) if merge => "Updated by config-generator --merge".to_string(),
+        (None, Some(template)) => {
+            format!("Generated from {} by config-generator; edit the template and regenerate rather than editing this file", template)
+        }
+        (None, None) => "Generated by config-generator; regenerate rather than editing this file".to_string(),
+    }
+}
+
+/// `time` as an RFC 3339 UTC timestamp, e.g. `2026-10-14T09:30:00Z`
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+    // Civil date from the day count, after Howard Hinnant's days_from_civil inverse
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3_600,
+        time_of_day % 3_600 / 60,
+        time_of_day % 60
+    )
+}
+
+/// How an input path is named in messages
+fn input_name(path: &str) -> &str {
+    if path == STDIN_PATH {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn write_output(path: &str, content: &str) -> Result<(), GenError> {
+    write_atomically(path, content.as_bytes(), false).map_err(|source| GenError::Write { path: path.to_string(), source })
+}
+
+/// [`write_output`] for a file holding plaintext secrets, readable by its owner only
+fn write_secret_output(path: &str, content: &[u8]) -> Result<(), GenError> {
+    write_atomically(path, content, true).map_err(|source| GenError::Write { path: path.to_string(), source })
+}
+
+/// Replace `path` with `content` so readers see either the old file or the new one, never a
+/// partial write: the content goes into a sibling temp file, which is synced and renamed over
+/// `path`. With `owner_only` the file gets mode 0600 on Unix; otherwise an existing file's
+/// permissions carry over, as an in-place write would keep them.
+fn write_atomically(path: &str, content: &[u8], owner_only: bool) -> io::Result<()> {
+    let target = Path::new(path);
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if owner_only {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // A crashed run can leave its temp file behind, and a later run may get the same PID, so a
+    // taken name is skipped rather than reused: it may still be another process's
+    let mut attempt = 0;
+    let (temp_path, mut file) = loop {
+        let suffix = match attempt {
+            0 => process::id().to_string(),
+            _ => format!("{}-{}", process::id(), attempt),
+        };
+        let temp_path = target.with_file_name(format!(".{}.tmp-{}", file_name.to_string_lossy(), suffix));
+        match options.open(&temp_path) {
+            Ok(file) => break (temp_path, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    
+    let written = (|| {
+        if owner_only {
+            // The umask can only narrow the mode, but be exact
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+        } else if let Ok(existing) = fs::metadata(target) {
+            file.set_permissions(existing.permissions())?;
+        }
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written?;
+    
+    // Persist the rename itself; not every platform can open a directory, so this is best effort
+    #[cfg(unix)]
+    {
+        let dir = target.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Phase reported if the time budget runs out
+static PHASE: Mutex<&str> = Mutex::new("startup");
+
+const PRE_HOOK_PHASE: &str = "pre-hook";
+const WRITING_PHASE: &str = "writing outputs";
+
+fn set_phase(phase: &'static str) {
+    *PHASE.lock().unwrap_or_else(|e| e.into_inner()) = phase;
+}
+
+fn time_budget_message(budget: Duration, phase: &str) -> String {
+    format!("time budget of {}s exceeded during {}", budget.as_secs_f64(), phase)
+}
+
+/// Exit the process once `budget` elapses, reporting the phase that was running. The pre-hook
+/// enforces the deadline itself so its child is killed, and writing is allowed to finish.
+fn start_watchdog(budget: Duration) {
+    thread::spawn(move || {
+        thread::sleep(budget);
+        loop {
+            // The lock is held through the exit, so the main thread can't move on to writing
+            // between the check and the exit
+            let phase = PHASE.lock().unwrap_or_else(|e| e.into_inner());
+            match *phase {
+                WRITING_PHASE => return,
+                // A hook that finished in time is followed by the next phase, which is over budget
+                PRE_HOOK_PHASE => {
+                    drop(phase);
+                    thread::sleep(Duration::from_millis(10));
                 }
-                "EMAIL_SENDER_ADDRESS" => {
-                    email_sender_address = Some(value.to_string());
+                running => {
+                    eprintln!("Error: {}; aborting generation", time_budget_message(budget, running));
+                    process::exit(EXIT_ABORTED.into());
                 }
-                _ => {}
-            }
-        }
-    }
-    
-    AuthnData {
-        client_id: client_id.unwrap_or_else(|| {
-            eprintln!("Error: GOOGLE_OAUTH_CLIENT_ID not found in authn file");
-            process::exit(1);
-        }),
-        client_secret: client_secret.unwrap_or_else(|| {
-            eprintln!("Error: GOOGLE_OAUTH_CLIENT_SECRET not found in authn file");
-            process::exit(1);
-        }),
-        email_smtp_host: email_smtp_host.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SMTP_HOST not found in authn file");
-            process::exit(1);
-        }),
-        email_smtp_port: email_smtp_port.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SMTP_PORT not found in authn file");
-            process::exit(1);
-        }),
-        email_smtp_username: email_smtp_username.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SMTP_USERNAME not found in authn file");
-            process::exit(1);
-        }),
-        email_smtp_password: email_smtp_password.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SMTP_PASSWORD not found in authn file");
-            process::exit(1);
-        }),
-        email_sender_name: email_sender_name.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SENDER_NAME not found in authn file");
-            process::exit(1);
-        }),
-        email_sender_address: email_sender_address.unwrap_or_else(|| {
-            eprintln!("Error: EMAIL_SENDER_ADDRESS not found in authn file");
-            process::exit(1);
-        }),
-    }
-}
-
-/// Generate the vault textproto file with OAuth client secret and email password
-/// Note: Client ID and email non-secrets are stored in the main config file, not in the vault,
-/// because traildepot only supports loading secrets (not client IDs or email non-secrets) from vault.
-fn generate_vault_file(client_secret: &str, email_password: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Create a Vault message with the client secret and email password
-    let mut vault = Vault {
-        secrets: HashMap::new(),
-    };
-    
-    vault.secrets.insert(
-        "TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET".to_string(),
-        client_secret.to_string(),
-    );
+            }
+        }
+    });
+}
+
+/// Run the pre-hook through `sh -c`, killing it if the `(deadline, budget)` deadline passes. Its
+/// stdout and stderr both go to our stderr so hook output is visible without mixing into anything
+/// this tool prints on stdout.
+fn run_pre_hook(command: &str, deadline: Option<(Instant, Duration)>) -> Result<(), String> {
+    let mut hook = process::Command::new("sh");
+    hook.arg("-c")
+        .arg(command)
+        .stdin(process::Stdio::null())
+        .stdout(std::io::stderr())
+        .stderr(std::io::stderr());
+    // Its own process group, so a timeout also kills whatever the shell started
+    #[cfg(unix)]
+    if deadline.is_some() {
+        std::os::unix::process::CommandExt::process_group(&mut hook, 0);
+    }
+    let mut child = hook
+        .spawn()
+        .map_err(|e| format!("failed to run pre-hook '{}': {}", command, e))?;
+    let status = loop {
+        let exited = child
+            .try_wait()
+            .map_err(|e| format!("failed to wait for pre-hook '{}': {}", command, e))?;
+        if let Some(status) = exited {
+            break status;
+        }
+        if let Some((_, budget)) = deadline.filter(|(deadline, _)| Instant::now() >= *deadline) {
+            // SAFETY: killpg has no memory-safety preconditions; the group id is the child we spawned
+            #[cfg(unix)]
+            unsafe {
+                libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+            }
+            // Elsewhere only the shell itself can be killed
+            #[cfg(not(unix))]
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("{} '{}'", time_budget_message(budget, PRE_HOOK_PHASE), command));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("pre-hook '{}' failed ({})", command, status))
+    }
+}
+
+/// Substitute every `${VAR}` in an authn template with the value from `env`.
+/// A `$` not followed by `{` is kept literally, as are single-quoted values. All unset variables
+/// are reported together. Values must read back as themselves: a line break is refused, and a value
+/// the authn parser would read differently is single-quoted when it is a line's whole value.
+fn render_authn_template(template: &str, env: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut missing: Vec<&str> = Vec::new();
     
-    vault.secrets.insert(
-        "TRAIL_EMAIL_SMTP_PASSWORD".to_string(),
-        email_password.to_string(),
+    for line in template.split_inclusive('\n') {
+        let value = line.split_once('=').map(|(_, value)| value.trim());
+        if value.is_some_and(|value| value.starts_with('\'')) {
+            rendered.push_str(line);
+        } else {
+            substitute_env_vars(line, value, &env, &mut rendered, &mut missing)?;
+        }
+    }
+    
+    if missing.is_empty() {
+        Ok(rendered)
+    } else {
+        Err(format!("unset environment variables: {}", missing.join(", ")))
+    }
+}
+
+/// Substitute the `${VAR}`s in one template line; `value` is the line's trimmed value part, if it
+/// has one
+fn substitute_env_vars<'a>(
+    text: &'a str,
+    value: Option<&str>,
+    env: &impl Fn(&str) -> Option<String>,
+    rendered: &mut String,
+    missing: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated '${{' starting with '{}'", after.lines().next().unwrap_or("")))?;
+        let name = &after[..end];
+        let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("invalid variable name '${{{}}}'", name));
+        }
+        match env(name) {
+            Some(substituted) => {
+                let whole_value = value == Some(&rest[start..start + end + 3]);
+                rendered.push_str(&authn_safe_value(name, &substituted, whole_value)?);
+            }
+            None if !missing.contains(&name) => missing.push(name),
+            None => {}
+        }
+        rest = &after[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(())
+}
+
+/// An environment value as it can be written into an authn file, so the parser reads back exactly
+/// `value`. A line break would start another `KEY=value` line, so it is refused. A leading `'` or `@`,
+/// surrounding whitespace or a `#` that could start a comment would change how the value parses, so
+/// such a value is single-quoted when it is the line's whole value (`whole_value`) and refused
+/// otherwise. Errors name the variable, never its value.
+fn authn_safe_value(name: &str, value: &str, whole_value: bool) -> Result<String, String> {
+    if value.contains(['\n', '\r']) {
+        return Err(format!("${{{}}} contains a line break, which would add lines to the authn file", name));
+    }
+    let comment = value.char_indices().any(|(index, c)| c == '#' && (index == 0 || value[..index].ends_with(char::is_whitespace)));
+    let needs_quotes = comment || value.starts_with(['\'', '@']) || value.trim() != value;
+    match (needs_quotes, whole_value && !value.contains('\'')) {
+        (false, _) => Ok(value.to_string()),
+        (true, true) => Ok(format!("'{}'", value)),
+        (true, false) => Err(format!(
+            "${{{}}} has a leading quote or '@', surrounding whitespace or a '#', which the authn file would read differently; \
+             it can only be single-quoted as the whole value of a KEY=${{{}}} line, without a ' in it",
+            name, name
+        )),
+    }
+}
+
+/// Sidecar file holding the checksum an output had when it was last generated
+fn checksum_sidecar_path(output_path: &str) -> String {
+    format!("{}.checksum", output_path)
+}
+
+/// 64-bit FNV-1a; only used to detect edits, not for integrity against an attacker
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn format_checksum(content: &[u8]) -> String {
+    format!("fnv1a64:{:016x}", fnv1a64(content))
+}
+
+/// Fail if `output_path` no longer matches the checksum recorded at the last generation.
+/// Outputs without a sidecar (first guarded run) or that were deleted are fine to write.
+fn check_unmodified(output_path: &str) -> Result<(), GenError> {
+    let sidecar_path = checksum_sidecar_path(output_path);
+    let recorded = match fs::read_to_string(&sidecar_path) {
+        Ok(recorded) => recorded,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(source) => return Err(GenError::Io { context: format!("failed to read checksum file '{}'", sidecar_path), source }),
+    };
+    let current = match fs::read(output_path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(source) => {
+            return Err(GenError::Io { context: format!("failed to read '{}' to verify its checksum", output_path), source })
+        }
+    };
+    
+    if recorded.trim() != format_checksum(&current) {
+        return Err(GenError::Rejected(format!(
+            "'{}' was modified since it was last generated (checksum does not match '{}'); pass --force to overwrite",
+            output_path, sidecar_path
+        )));
+    }
+    Ok(())
+}
+
+fn record_checksum(output_path: &str, content: &[u8]) -> Result<(), GenError> {
+    write_output(&checksum_sidecar_path(output_path), &format!("{}\n", format_checksum(content)))
+}
+
+/// Secret keys whose unquoted raw value carries whitespace that `parse_authn_file` trims, with their
+/// line numbers. Whitespace around a quoted value isn't part of it.
+fn find_padded_secrets(content: &str) -> Vec<(usize, &str)> {
+    authn_lines(content)
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (key, raw_value) = line.split_once('=')?;
+            let key = key.trim();
+            let padded = raw_value != raw_value.trim() && !raw_value.trim_start().starts_with('\'');
+            (is_secret_authn_key(key) && padded).then_some((index + 1, key))
+        })
+        .collect()
+}
+
+/// `<PROVIDER>_OAUTH_CLIENT_SECRET` keys whose `<PROVIDER>_OAUTH_CLIENT_ID` is missing, as
+/// (secret key, client ID key) pairs sorted by secret key. Checks every provider prefix, not only
+/// the ones the generator fills, so a secret for an unsupported provider is flagged too.
+fn find_orphan_provider_secrets(keys: &BTreeSet<String>) -> Vec<(String, String)> {
+    keys.iter()
+        .filter_map(|key| {
+            let id_key = format!("{}_OAUTH_CLIENT_ID", key.strip_suffix("_OAUTH_CLIENT_SECRET")?);
+            (!keys.contains(&id_key)).then(|| (key.clone(), id_key))
+        })
+        .collect()
+}
+
+/// Rewrite a config or vault file in canonical form: parsed through the descriptor pool and
+/// re-serialized with FORMAT_OPTIONS, map entries sorted by key, and the generator's preface.
+/// The file type is detected by which message it parses as; values are never changed. A generated
+/// file's header comments below the preface line are kept.
+fn canonicalize_file(schema: &Schema, path: &str) -> Result<(), GenError> {
+    let content = fs::read_to_string(path).map_err(|source| GenError::Io { context: "failed to read it".to_string(), source })?;
+    
+    let message = match DynamicMessage::parse_text_format(schema.config.clone(), &content) {
+        Ok(message) => message,
+        Err(config_error) => DynamicMessage::parse_text_format(schema.vault.clone(), &content).map_err(|vault_error| {
+            GenError::Step(format!(
+                "not a valid {} ({}) or {} ({})",
+                schema.config.full_name(),
+                redact_parse_error(&config_error),
+                schema.vault.full_name(),
+                redact_parse_error(&vault_error)
+            ))
+        })?,
+    };
+    
+    let header: String = if content.starts_with("# Auto-generated ") {
+        content.lines().skip(1).take_while(|line| line.starts_with('#')).map(|line| format!("{}\n", line)).collect()
+    } else {
+        String::new()
+    };
+    let canonical = format!(
+        "# Auto-generated {} textproto\n{}{}",
+        message.descriptor().full_name(),
+        header,
+        to_canonical_text(&message)
     );
+    write_atomically(path, canonical.as_bytes(), false).map_err(|source| GenError::Write { path: path.to_string(), source })
+}
+
+/// Find forbidden substrings in the generated config, returning (1-based line number, substring)
+/// pairs in the order they appear
+fn find_template_leftovers<'a>(config: &str, forbidden: &'a [String]) -> Vec<(usize, &'a str)> {
+    let mut leftovers = Vec::new();
+    for (index, line) in config.lines().enumerate() {
+        for substring in forbidden {
+            if line.contains(substring.as_str()) {
+                leftovers.push((index + 1, substring.as_str()));
+            }
+        }
+    }
+    leftovers
+}
+
+/// A single field-level difference between two configs
+enum FieldChange {
+    Added { path: String, value: String },
+    Removed { path: String, value: String },
+    Changed { path: String, old: String, new: String },
+}
+
+impl FieldChange {
+    fn path(&self) -> &str {
+        match self {
+            FieldChange::Added { path, .. } | FieldChange::Removed { path, .. } | FieldChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldChange::Added { path, value } => write!(f, "+ {}: {}", path, redact_leaf(value)),
+            FieldChange::Removed { path, value } => write!(f, "- {}: {}", path, redact_leaf(value)),
+            FieldChange::Changed { path, old, new } => {
+                write!(f, "~ {}: {} -> {}", path, redact_leaf(old), redact_leaf(new))
+            }
+        }
+    }
+}
+
+/// Parse two texts against `descriptor` and list their field-level differences, ordered by field path
+fn compare_messages(descriptor: &MessageDescriptor, old: &str, new: &str) -> Result<Vec<FieldChange>, String> {
+    let parse = |text: &str, which: &str| {
+        DynamicMessage::parse_text_format(descriptor.clone(), text)
+            .map_err(|e| format!("{} file is not a valid {} message: {}", which, descriptor.full_name(), redact_parse_error(&e)))
+    };
+    let old_fields = flatten_fields(&parse(old, "existing")?);
+    let new_fields = flatten_fields(&parse(new, "generated")?);
     
-    // Serialize to textproto using the same approach as TrailBase
-    const PREFACE: &str = "# Auto-generated config.Vault textproto";
+    let mut changes = Vec::new();
+    for (path, old_value) in &old_fields {
+        match new_fields.get(path) {
+            None => changes.push(FieldChange::Removed { path: path.clone(), value: old_value.clone() }),
+            Some(new_value) if new_value != old_value => changes.push(FieldChange::Changed {
+                path: path.clone(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (path, new_value) in &new_fields {
+        if !old_fields.contains_key(path) {
+            changes.push(FieldChange::Added { path: path.clone(), value: new_value.clone() });
+        }
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(changes)
+}
+
+/// Split a field path into name and `[...]` segments: `a.b["k.1"].c` -> `a`, `b`, `["k.1"]`, `c`.
+/// Dots and brackets inside quoted map keys don't split.
+fn path_segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in path.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '.' if !in_quotes => {
+                if start < index {
+                    segments.push(&path[start..index]);
+                }
+                start = index + 1;
+            }
+            '[' if !in_quotes => {
+                if start < index {
+                    segments.push(&path[start..index]);
+                }
+                start = index;
+            }
+            ']' if !in_quotes => {
+                segments.push(&path[start..=index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < path.len() {
+        segments.push(&path[start..]);
+    }
+    segments
+}
+
+/// Parse a redaction policy: one field path per line (`email.smtp_password`,
+/// `auth.oauth_providers[*].client_secret`), `#` comments and blank lines ignored. Every field
+/// name is checked against `descriptor` so a typo can't silently disable enforcement.
+fn parse_redaction_policy(descriptor: &MessageDescriptor, policy: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut patterns = Vec::new();
+    for (index, line) in policy.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let segments: Vec<String> = path_segments(line).into_iter().map(String::from).collect();
+        check_policy_path(descriptor, &segments)
+            .map_err(|e| format!("redaction policy line {}: '{}' {}", index + 1, line, e))?;
+        patterns.push(segments);
+    }
+    Ok(patterns)
+}
+
+fn check_policy_path(descriptor: &MessageDescriptor, segments: &[String]) -> Result<(), String> {
+    let mut message = Some(descriptor.clone());
+    let mut rest = segments;
+    while let Some((name, tail)) = rest.split_first() {
+        let current = message.ok_or_else(|| "descends into a field that has no subfields".to_string())?;
+        if name.starts_with('[') {
+            return Err(format!("indexes '{}', which is not a map or repeated field", current.full_name()));
+        }
+        let field = current
+            .get_field_by_name(name)
+            .ok_or_else(|| format!("names unknown field '{}' in {}", name, current.full_name()))?;
+        rest = tail;
+        let kind = if field.is_map() {
+            field.kind().as_message().expect("map field has an entry message").map_entry_value_field().kind()
+        } else {
+            field.kind()
+        };
+        if (field.is_map() || field.is_list()) && rest.first().is_some_and(|s| s.starts_with('[')) {
+            rest = &rest[1..];
+        }
+        message = kind.as_message().cloned();
+    }
+    Ok(())
+}
+
+/// Whether a flattened field path falls under a policy pattern. `[*]` matches any index or map key,
+/// and a pattern naming a message covers all of its fields.
+fn policy_matches(pattern: &[String], path: &str) -> bool {
+    let segments = path_segments(path);
+    pattern.len() <= segments.len()
+        && pattern
+            .iter()
+            .zip(&segments)
+            .all(|(p, s)| p == s || (p == "[*]" && s.starts_with('[')))
+}
+
+/// Concrete config paths covered by the policy that carry a value other than the vault placeholder
+/// (or an empty string). Only paths are returned, never values.
+fn find_policy_violations(schema: &Schema, config: &str, patterns: &[Vec<String>], placeholder: &str) -> Result<Vec<String>, String> {
+    let message = DynamicMessage::parse_text_format(schema.config.clone(), config)
+        .map_err(|e| format!("generated config is not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e)))?;
+    let placeholder = format!("{:?}", placeholder);
+    Ok(flatten_fields(&message)
+        .into_iter()
+        .filter(|(path, value)| {
+            *value != placeholder && value != "\"\"" && patterns.iter().any(|p| policy_matches(p, path))
+        })
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// Parse two texts against `descriptor` and build a message holding only what `new` changes, such
+/// that merging it into `old` with protobuf merge semantics yields `new` (removals aside)
+fn config_patch(descriptor: &MessageDescriptor, old: &str, new: &str) -> Result<DynamicMessage, String> {
+    let parse = |text: &str, which: &str| {
+        DynamicMessage::parse_text_format(descriptor.clone(), text)
+            .map_err(|e| format!("{} file is not a valid {} message: {}", which, descriptor.full_name(), redact_parse_error(&e)))
+    };
+    Ok(diff_message(&parse(old, "existing")?, &parse(new, "generated")?))
+}
+
+/// Fields of `new` that differ from `old`. Singular messages and map entries are diffed recursively,
+/// since merging combines them; repeated fields are taken whole because merging appends to them.
+fn diff_message(old: &DynamicMessage, new: &DynamicMessage) -> DynamicMessage {
+    let mut patch = DynamicMessage::new(new.descriptor());
+    for (field, value) in new.fields() {
+        let old_value = old.has_field(&field).then(|| old.get_field(&field));
+        let changed = match (value, old_value.as_deref()) {
+            (Value::Message(new_sub), Some(Value::Message(old_sub))) => {
+                Some(diff_message(old_sub, new_sub)).filter(has_fields).map(Value::Message)
+            }
+            (Value::Map(new_entries), Some(Value::Map(old_entries))) => {
+                let entries: HashMap<MapKey, Value> = new_entries
+                    .iter()
+                    .filter_map(|(key, new_entry)| match (new_entry, old_entries.get(key)) {
+                        (Value::Message(new_sub), Some(Value::Message(old_sub))) => {
+                            Some(diff_message(old_sub, new_sub)).filter(has_fields).map(|sub| (key.clone(), Value::Message(sub)))
+                        }
+                        (new_entry, old_entry) if old_entry != Some(new_entry) => Some((key.clone(), new_entry.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                (!entries.is_empty()).then_some(Value::Map(entries))
+            }
+            (value, old_value) => (old_value != Some(value)).then(|| value.clone()),
+        };
+        if let Some(changed) = changed {
+            patch.set_field(&field, changed);
+        }
+    }
+    patch
+}
+
+fn has_fields(message: &DynamicMessage) -> bool {
+    message.fields().next().is_some()
+}
+
+/// Email fields that identify the deployment without being secret
+const INVENTORY_EMAIL_FIELDS: &[&str] = &["smtp_host", "smtp_port", "smtp_username", "sender_name", "sender_address"];
+
+/// Render a scalar field value as JSON; enums by name
+fn json_value(value: &Value, kind: &prost_reflect::Kind) -> String {
+    match value {
+        Value::String(text) => json_string(text),
+        Value::EnumNumber(number) => kind
+            .as_enum()
+            .and_then(|e| e.get_value(*number))
+            .map(|v| json_string(v.name()))
+            .unwrap_or_else(|| number.to_string()),
+        Value::Bool(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::U64(v) => v.to_string(),
+        other => json_string(&format!("{:?}", other)),
+    }
+}
+
+/// JSON inventory of the credentials a deployment uses: each OAuth provider's identifying fields, the
+/// SMTP identity and any named ones, and the vault secret key names. Read from the generated config so it reflects
+/// exactly what is deployed; secret values never appear.
+fn build_inventory(schema: &Schema, config: &str, secrets: &BTreeMap<String, String>) -> Result<String, String> {
+    let message = DynamicMessage::parse_text_format(schema.config.clone(), config)
+        .map_err(|e| format!("generated config is not a valid {} message: {}", schema.config.full_name(), redact_parse_error(&e)))?;
+    let present_fields = |message: &DynamicMessage, names: &[&str]| -> Vec<String> {
+        names
+            .iter()
+            .filter_map(|name| {
+                let field = message.descriptor().get_field_by_name(name)?;
+                message.has_field(&field).then(|| {
+                    format!("{}: {}", json_string(name), json_value(&message.get_field(&field), &field.kind()))
+                })
+            })
+            .collect()
+    };
     
-    let text: String = vault
-        .transcode_to_dynamic()
-        .to_text_format_with_options(&FORMAT_OPTIONS);
+    let mut providers = Vec::new();
+    if let Some(Value::Message(auth)) = message.get_field_by_name("auth").as_deref() {
+        if let Some(Value::Map(entries)) = auth.get_field_by_name("oauth_providers").as_deref() {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            for (key, provider) in entries {
+                let (MapKey::String(name), Value::Message(provider)) = (key, provider) else {
+                    continue;
+                };
+                let mut fields = vec![format!("\"name\": {}", json_string(name))];
+                fields.extend(present_fields(provider, &["provider_id", "client_id", "display_name"]));
+                providers.push(format!("    {{{}}}", fields.join(", ")));
+            }
+        }
+    }
+    let email = match message.get_field_by_name("email").as_deref() {
+        Some(Value::Message(email)) if message.has_field_by_name("email") => {
+            format!("{{{}}}", present_fields(email, INVENTORY_EMAIL_FIELDS).join(", "))
+        }
+        _ => "null".to_string(),
+    };
+    // Named identities are `<name>_email` fields of the same type as `email`, keyed by `<name>`
+    let email_type = schema.config.get_field_by_name("email").and_then(|field| field.kind().as_message().cloned());
+    let mut named_emails = Vec::new();
+    for field in schema.config.fields() {
+        let Some(name) = field.name().strip_suffix("_email") else { continue };
+        if field.kind().as_message() != email_type.as_ref() || !message.has_field(&field) {
+            continue;
+        }
+        if let Value::Message(block) = message.get_field(&field).as_ref() {
+            named_emails.push((name.to_string(), present_fields(block, INVENTORY_EMAIL_FIELDS).join(", ")));
+        }
+    }
+    named_emails.sort();
+    let named_emails: Vec<String> =
+        named_emails.iter().map(|(name, fields)| format!("    {}: {{{}}}", json_string(name), fields)).collect();
+    let mut secret_keys: Vec<String> = secrets.keys().map(|key| json_string(key)).collect();
+    secret_keys.sort();
     
-    Ok(format!("{PREFACE}\n{text}"))
+    Ok(format!(
+        "{{\n  \"oauth_providers\": [{}],\n  \"email\": {},\n  \"named_emails\": {{{}}},\n  \"vault_secrets\": [{}]\n}}\n",
+        if providers.is_empty() { String::new() } else { format!("\n{}\n  ", providers.join(",\n")) },
+        email,
+        if named_emails.is_empty() { String::new() } else { format!("\n{}\n  ", named_emails.join(",\n")) },
+        secret_keys.join(", ")
+    ))
+}
+
+/// Render per-file change counts as a single-line JSON object, e.g.
+/// `{"config":{"changed":2,"added":1,"removed":0},"vault":{...}}`. Values are never included.
+fn diff_summary_json(summary: &[(&str, Vec<FieldChange>)]) -> String {
+    let files: Vec<String> = summary
+        .iter()
+        .map(|(name, changes)| {
+            let count = |f: fn(&FieldChange) -> bool| changes.iter().filter(|c| f(c)).count();
+            format!(
+                "\"{}\":{{\"changed\":{},\"added\":{},\"removed\":{}}}",
+                name,
+                count(|c| matches!(c, FieldChange::Changed { .. })),
+                count(|c| matches!(c, FieldChange::Added { .. })),
+                count(|c| matches!(c, FieldChange::Removed { .. })),
+            )
+        })
+        .collect();
+    format!("{{{}}}", files.join(","))
+}
+
+/// Flatten a message into leaf field paths (`auth.oauth_providers["google"].client_id`,
+/// `record_apis[0].name`) mapped to their rendered values. Strings are rendered quoted and enums
+/// by name; a present but empty message is its own leaf rendered as `{}`.
+fn flatten_fields(message: &DynamicMessage) -> BTreeMap<String, String> {
+    let mut leaves = BTreeMap::new();
+    flatten_into(message, "", &mut leaves);
+    leaves
+}
+
+fn flatten_into(message: &DynamicMessage, prefix: &str, leaves: &mut BTreeMap<String, String>) {
+    let mut empty = true;
+    for (field, value) in message.fields() {
+        empty = false;
+        let path = if prefix.is_empty() {
+            field.name().to_string()
+        } else {
+            format!("{}.{}", prefix, field.name())
+        };
+        let kind = field.kind();
+        match value {
+            Value::List(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    flatten_value(item, &kind, format!("{}[{}]", path, index), leaves);
+                }
+            }
+            Value::Map(entries) => {
+                let value_kind = kind
+                    .as_message()
+                    .map(|entry| entry.map_entry_value_field().kind())
+                    .expect("map field has an entry message");
+                for (key, item) in entries {
+                    let key = match key {
+                        MapKey::String(key) => format!("{:?}", key),
+                        other => format!("{:?}", other),
+                    };
+                    flatten_value(item, &value_kind, format!("{}[{}]", path, key), leaves);
+                }
+            }
+            _ => flatten_value(value, &kind, path, leaves),
+        }
+    }
+    if empty && !prefix.is_empty() {
+        leaves.insert(prefix.to_string(), "{}".to_string());
+    }
+}
+
+fn flatten_value(value: &Value, kind: &prost_reflect::Kind, path: String, leaves: &mut BTreeMap<String, String>) {
+    let rendered = match value {
+        Value::Message(message) => return flatten_into(message, &path, leaves),
+        Value::String(text) => format!("{:?}", text),
+        Value::EnumNumber(number) => kind
+            .as_enum()
+            .and_then(|e| e.get_value(*number))
+            .map(|v| v.name().to_string())
+            .unwrap_or_else(|| number.to_string()),
+        Value::Bool(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::U64(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        Value::Bytes(bytes) => format!("<{} bytes>", bytes.len()),
+        Value::List(_) | Value::Map(_) => unreachable!("nested lists and maps are not valid protobuf"),
+    };
+    leaves.insert(path, rendered);
+}
+
+/// Redact a rendered leaf value: strings are masked, other scalars are shown as-is
+fn redact_leaf(rendered: &str) -> String {
+    match rendered.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => format!("\"{}\"", redact(inner)),
+        None => rendered.to_string(),
+    }
 }
@@ -0,0 +1,670 @@
+//! JSON and YAML authn files, read into the same keys as the `KEY=value` format
+//!
+//! Both formats describe one mapping:
+//!
+//! ```yaml
+//! auth_mode: both
+//! oauth_providers:
+//!   google:
+//!     client_id: "..."
+//!     client_secret: "..."
+//! email:
+//!   smtp_host: smtp.example.com
+//!   smtp_port: 587
+//! ```
+//!
+//! `oauth_providers.<name>.client_id` becomes `<NAME>_OAUTH_CLIENT_ID` (likewise `client_secret`,
+//! `scopes`, `redirect_url` and `pkce`), `email.<field>` becomes
+//! `EMAIL_<FIELD>`, `emails.<name>.<field>` becomes `EMAIL_<NAME>_<FIELD>`, a value nested under
+//! `config` becomes `CONFIG_<field path>` (`config.server.site_url` is `CONFIG_server.site_url`),
+//! and any other top-level value, such as `auth_mode`, becomes its upper-cased key, which template `#if`
+//! conditionals can test. Values are used exactly as written; numbers and booleans keep their text. Only the block-mapping subset of YAML is read: no sequences, flow
+//! collections, anchors, tags or block scalars.
+//!
+//! A `--providers-json` file is instead a JSON array with one object per OAuth provider, read into
+//! the same `<NAME>_OAUTH_<FIELD>` keys; see [`parse_providers_json`].
+
+use crate::{authn_lines, redact, CONFIG_KEY_PREFIX, EMAIL_KEY_FIELDS, OAUTH_KEY_FIELDS};
+use std::borrow::Borrow;
+
+/// Where in the file something is, 1-based `(line, column)`; columns count characters
+type Position = (usize, usize);
+
+/// What is wrong with a JSON or YAML authn file, and where
+pub(crate) struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    fn at((line, column): Position, message: impl Into<String>) -> SyntaxError {
+        SyntaxError { line, column, message: message.into() }
+    }
+}
+
+struct Node {
+    at: Position,
+    kind: Kind,
+}
+
+enum Kind {
+    Scalar(String),
+    /// `(key, key position, value)` in file order
+    Mapping(Vec<(String, Position, Node)>),
+    /// A JSON array; only `--providers-json` files may contain them
+    Sequence(Vec<Node>),
+}
+
+/// The `oauth_providers.<name>` fields, each read as `<NAME>_OAUTH_<FIELD>`
+fn oauth_fields() -> Vec<String> {
+    OAUTH_KEY_FIELDS.iter().map(|spec| spec.name.to_lowercase()).collect()
+}
+
+/// The `email` and `emails.<name>` fields, each read as `EMAIL_<FIELD>` or `EMAIL_<NAME>_<FIELD>`
+fn email_fields() -> Vec<String> {
+    EMAIL_KEY_FIELDS.iter().map(|spec| spec.name.to_lowercase()).collect()
+}
+
+const NULL_UNSUPPORTED: &str = "null values are not supported; leave the key out instead";
+
+const ARRAYS_UNSUPPORTED: &str = "arrays are not supported in authn files";
+
+/// The fields of each `--providers-json` entry
+const PROVIDER_ENTRY_FIELDS: [&str; 5] = ["provider", "client_id", "client_secret", "redirect_url", "scopes"];
+
+/// Read a JSON authn file into `(key, value)` entries in file order
+pub(crate) fn parse_json(content: &str) -> Result<Vec<(String, String)>, SyntaxError> {
+    flatten(json_document(content)?)
+}
+
+/// Read a `--providers-json` file, an array of `{provider, client_id, client_secret, redirect_url,
+/// scopes}` objects, into `<PROVIDER>_OAUTH_<FIELD>` entries in file order. `provider` and
+/// `client_id` are required; `scopes` is an array of strings, joined with commas as the
+/// `KEY=value` format writes them.
+pub(crate) fn parse_providers_json(content: &str) -> Result<Vec<(String, String)>, SyntaxError> {
+    let document = json_document(content)?;
+    let Kind::Sequence(items) = document.kind else {
+        return Err(SyntaxError::at(document.at, "expected an array of provider objects"));
+    };
+    let mut entries = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let at = item.at;
+        let section = format!("provider entry {}", index + 1);
+        let mut name = None;
+        let mut fields = Vec::new();
+        for (field, field_at, value) in mapping(item, &section)? {
+            match field.as_str() {
+                "provider" => name = Some((scalar(value, &format!("{}.provider", section))?, field_at)),
+                "scopes" => fields.push(("SCOPES", scopes(value, &section)?)),
+                "client_id" => fields.push(("CLIENT_ID", scalar(value, &format!("{}.client_id", section))?)),
+                "client_secret" => fields.push(("CLIENT_SECRET", scalar(value, &format!("{}.client_secret", section))?)),
+                "redirect_url" => fields.push(("REDIRECT_URL", scalar(value, &format!("{}.redirect_url", section))?)),
+                _ => return Err(unknown_field(field_at, &field, &section, &PROVIDER_ENTRY_FIELDS)),
+            }
+        }
+        let Some((name, name_at)) = name else {
+            return Err(SyntaxError::at(at, format!("{} has no 'provider' name", section)));
+        };
+        if name.is_empty() {
+            return Err(SyntaxError::at(name_at, "OAuth provider names can't be empty"));
+        }
+        if names.iter().any(|existing| existing.eq_ignore_ascii_case(&name)) {
+            return Err(SyntaxError::at(name_at, format!("provider '{}' is listed more than once", name)));
+        }
+        if !fields.iter().any(|(field, _)| *field == "CLIENT_ID") {
+            return Err(SyntaxError::at(at, format!("provider '{}' has no client_id", name)));
+        }
+        entries.extend(fields.into_iter().map(|(field, value)| (format!("{}_OAUTH_{}", name.to_uppercase(), field), value)));
+        names.push(name);
+    }
+    Ok(entries)
+}
+
+/// A provider entry's `scopes`: an array of non-empty strings, or one comma-separated string
+fn scopes(node: Node, section: &str) -> Result<String, SyntaxError> {
+    let what = format!("{}.scopes", section);
+    let Kind::Sequence(items) = node.kind else {
+        return scalar(node, &what);
+    };
+    let mut scopes = Vec::new();
+    for item in items {
+        let at = item.at;
+        let scope = scalar(item, &what)?;
+        if scope.is_empty() || scope.contains(',') {
+            return Err(SyntaxError::at(at, format!("scopes in {} must be non-empty and contain no ','", section)));
+        }
+        scopes.push(scope);
+    }
+    Ok(scopes.join(","))
+}
+
+/// A whole JSON file's value, with nothing but whitespace after it
+fn json_document(content: &str) -> Result<Node, SyntaxError> {
+    let mut cursor = Cursor::new(content.strip_prefix('\u{feff}').unwrap_or(content));
+    let document = json_value(&mut cursor)?;
+    cursor.skip_whitespace();
+    if let Some(c) = cursor.peek() {
+        return Err(SyntaxError::at(cursor.at(), format!("unexpected '{}' after the document", c)));
+    }
+    Ok(document)
+}
+
+/// Read a YAML authn file into `(key, value)` entries in file order
+pub(crate) fn parse_yaml(content: &str) -> Result<Vec<(String, String)>, SyntaxError> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = Vec::new();
+    for (index, line) in authn_lines(content).enumerate() {
+        if line.trim_end() == "---" {
+            if lines.is_empty() {
+                continue;
+            }
+            return Err(SyntaxError::at((index + 1, 1), "only one YAML document is supported"));
+        }
+        lines.extend(yaml_line(index + 1, line)?);
+    }
+    
+    let mut index = 0;
+    let members = match lines.first() {
+        Some(first) => yaml_block(&lines, &mut index, first.indent)?,
+        None => Vec::new(),
+    };
+    if let Some(line) = lines.get(index) {
+        return Err(SyntaxError::at(line.key_at, "unexpected indentation"));
+    }
+    flatten(Node { at: (1, 1), kind: Kind::Mapping(members) })
+}
+
+/// Turn the document into the keys the `KEY=value` format uses
+fn flatten(document: Node) -> Result<Vec<(String, String)>, SyntaxError> {
+    let mut entries = Vec::new();
+    for (key, _, node) in mapping(document, "the document")? {
+        match key.as_str() {
+            "oauth_providers" => {
+                for (name, name_at, provider) in mapping(node, "oauth_providers")? {
+                    if name.is_empty() {
+                        return Err(SyntaxError::at(name_at, "OAuth provider names can't be empty"));
+                    }
+                    let section = format!("oauth_providers.{}", name);
+                    for (field, field_at, value) in mapping(provider, &section)? {
+                        if !oauth_fields().contains(&field) {
+                            return Err(unknown_field(field_at, &field, &section, &oauth_fields()));
+                        }
+                        let value = scalar(value, &format!("{}.{}", section, field))?;
+                        entries.push((format!("{}_OAUTH_{}", name.to_uppercase(), field.to_uppercase()), value));
+                    }
+                }
+            }
+            "email" => {
+                for (field, field_at, value) in mapping(node, "email")? {
+                    if !email_fields().contains(&field) {
+                        return Err(unknown_field(field_at, &field, "email", &email_fields()));
+                    }
+                    let value = scalar(value, &format!("email.{}", field))?;
+                    entries.push((format!("EMAIL_{}", field.to_uppercase()), value));
+                }
+            }
+            "emails" => {
+                for (name, name_at, email) in mapping(node, "emails")? {
+                    if name.is_empty() {
+                        return Err(SyntaxError::at(name_at, "email config names can't be empty"));
+                    }
+                    let section = format!("emails.{}", name);
+                    for (field, field_at, value) in mapping(email, &section)? {
+                        if !email_fields().contains(&field) {
+                            return Err(unknown_field(field_at, &field, &section, &email_fields()));
+                        }
+                        let value = scalar(value, &format!("{}.{}", section, field))?;
+                        entries.push((format!("EMAIL_{}_{}", name.to_uppercase(), field.to_uppercase()), value));
+                    }
+                }
+            }
+            "config" => flatten_config(node, "", &mut entries)?,
+            _ => {
+                let value = scalar(node, &key)?;
+                entries.push((key.to_uppercase(), value));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Read the values nested under `config` into `CONFIG_<field path>` keys, `path` being the
+/// `.`-joined mapping keys above `node` (empty for `config` itself)
+fn flatten_config(node: Node, path: &str, entries: &mut Vec<(String, String)>) -> Result<(), SyntaxError> {
+    let section = if path.is_empty() { "config".to_string() } else { format!("config.{}", path) };
+    for (field, field_at, value) in mapping(node, &section)? {
+        if field.is_empty() || field.contains('.') {
+            return Err(SyntaxError::at(field_at, format!("config field names in {} must be non-empty and contain no '.'", section)));
+        }
+        let path = if path.is_empty() { field } else { format!("{}.{}", path, field) };
+        match value.kind {
+            Kind::Mapping(_) => flatten_config(value, &path, entries)?,
+            Kind::Scalar(value) => entries.push((format!("{}{}", CONFIG_KEY_PREFIX, path), value)),
+            Kind::Sequence(_) => return Err(SyntaxError::at(value.at, ARRAYS_UNSUPPORTED)),
+        }
+    }
+    Ok(())
+}
+
+fn mapping(node: Node, what: &str) -> Result<Vec<(String, Position, Node)>, SyntaxError> {
+    match node.kind {
+        Kind::Mapping(members) => Ok(members),
+        Kind::Scalar(_) => Err(SyntaxError::at(node.at, format!("expected a mapping for {}", what))),
+        Kind::Sequence(_) => Err(SyntaxError::at(node.at, ARRAYS_UNSUPPORTED)),
+    }
+}
+
+fn scalar(node: Node, what: &str) -> Result<String, SyntaxError> {
+    match node.kind {
+        Kind::Scalar(value) => Ok(value),
+        Kind::Mapping(_) => Err(SyntaxError::at(node.at, format!("expected a value for {}, not a mapping", what))),
+        Kind::Sequence(_) => Err(SyntaxError::at(node.at, ARRAYS_UNSUPPORTED)),
+    }
+}
+
+fn unknown_field(at: Position, field: &str, section: &str, expected: &[impl Borrow<str>]) -> SyntaxError {
+    SyntaxError::at(at, format!("unknown field '{}' in {} (expected one of {})", field, section, expected.join(", ")))
+}
+
+fn push_member(members: &mut Vec<(String, Position, Node)>, key: String, at: Position, node: Node) -> Result<(), SyntaxError> {
+    if members.iter().any(|(existing, _, _)| *existing == key) {
+        return Err(SyntaxError::at(at, format!("duplicate key '{}'", key)));
+    }
+    members.push((key, at, node));
+    Ok(())
+}
+
+/// Characters of a JSON document with the position of the next one
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(content: &'a str) -> Cursor<'a> {
+        Cursor { chars: content.chars().peekable(), line: 1, column: 1 }
+    }
+    
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+    
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    
+    fn at(&self) -> Position {
+        (self.line, self.column)
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.bump();
+        }
+    }
+    
+    fn expect(&mut self, expected: char, context: &str) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(SyntaxError::at(self.at(), format!("expected '{}' {}, found '{}'", expected, context, c))),
+            None => Err(SyntaxError::at(self.at(), format!("expected '{}' {}, found the end of the file", expected, context))),
+        }
+    }
+}
+
+fn json_value(cursor: &mut Cursor) -> Result<Node, SyntaxError> {
+    cursor.skip_whitespace();
+    let at = cursor.at();
+    let kind = match cursor.peek() {
+        Some('{') => {
+            cursor.bump();
+            Kind::Mapping(json_object(cursor)?)
+        }
+        Some('"') => Kind::Scalar(json_string(cursor)?),
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = cursor.peek().filter(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                number.push(c);
+                cursor.bump();
+            }
+            if !is_json_number(&number) {
+                return Err(SyntaxError::at(at, format!("invalid number '{}'", redact(&number))));
+            }
+            Kind::Scalar(number)
+        }
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = cursor.peek().filter(char::is_ascii_alphanumeric) {
+                word.push(c);
+                cursor.bump();
+            }
+            match word.as_str() {
+                "true" | "false" => Kind::Scalar(word),
+                "null" => return Err(SyntaxError::at(at, NULL_UNSUPPORTED)),
+                // Likely an unquoted value, which may be a secret
+                _ => return Err(SyntaxError::at(at, format!("unexpected '{}'", redact(&word)))),
+            }
+        }
+        Some('[') => {
+            cursor.bump();
+            Kind::Sequence(json_array(cursor)?)
+        }
+        Some(c) => return Err(SyntaxError::at(at, format!("unexpected '{}'", c))),
+        None => return Err(SyntaxError::at(at, "unexpected end of the file")),
+    };
+    Ok(Node { at, kind })
+}
+
+/// The members of an object whose `{` was just read
+fn json_object(cursor: &mut Cursor) -> Result<Vec<(String, Position, Node)>, SyntaxError> {
+    let mut members = Vec::new();
+    cursor.skip_whitespace();
+    if cursor.peek() == Some('}') {
+        cursor.bump();
+        return Ok(members);
+    }
+    loop {
+        cursor.skip_whitespace();
+        let at = cursor.at();
+        if cursor.peek() != Some('"') {
+            return Err(SyntaxError::at(at, "expected a quoted key"));
+        }
+        let key = json_string(cursor)?;
+        cursor.skip_whitespace();
+        cursor.expect(':', "after the key")?;
+        let value = json_value(cursor)?;
+        push_member(&mut members, key, at, value)?;
+        
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            Some(',') => {
+                cursor.bump();
+            }
+            Some('}') => {
+                cursor.bump();
+                return Ok(members);
+            }
+            _ => cursor.expect('}', "or ',' after a value")?,
+        }
+    }
+}
+
+/// The elements of an array whose `[` was just read
+fn json_array(cursor: &mut Cursor) -> Result<Vec<Node>, SyntaxError> {
+    let mut items = Vec::new();
+    cursor.skip_whitespace();
+    if cursor.peek() == Some(']') {
+        cursor.bump();
+        return Ok(items);
+    }
+    loop {
+        items.push(json_value(cursor)?);
+        
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            Some(',') => {
+                cursor.bump();
+            }
+            Some(']') => {
+                cursor.bump();
+                return Ok(items);
+            }
+            _ => cursor.expect(']', "or ',' after an array element")?,
+        }
+    }
+}
+
+/// A string starting at the cursor's `"`
+fn json_string(cursor: &mut Cursor) -> Result<String, SyntaxError> {
+    let start = cursor.at();
+    cursor.bump();
+    let mut value = String::new();
+    loop {
+        let at = cursor.at();
+        match cursor.bump() {
+            None => return Err(SyntaxError::at(start, "unterminated string")),
+            Some('"') => return Ok(value),
+            Some('\\') => {
+                let escaped = match cursor.bump() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => {
+                        let mut code = json_hex4(cursor, at)?;
+                        if (0xD800..0xDC00).contains(&code) {
+                            // A high surrogate must be followed by the low one
+                            if cursor.bump() != Some('\\') || cursor.bump() != Some('u') {
+                                return Err(SyntaxError::at(at, "unpaired surrogate in \\u escape"));
+                            }
+                            let low = json_hex4(cursor, at)?;
+                            if !(0xDC00..0xE000).contains(&low) {
+                                return Err(SyntaxError::at(at, "unpaired surrogate in \\u escape"));
+                            }
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                        }
+                        char::from_u32(code).ok_or_else(|| SyntaxError::at(at, "unpaired surrogate in \\u escape"))?
+                    }
+                    _ => return Err(SyntaxError::at(at, "invalid escape sequence")),
+                };
+                value.push(escaped);
+            }
+            Some(c) if c < ' ' => return Err(SyntaxError::at(at, "control characters must be escaped in strings")),
+            Some(c) => value.push(c),
+        }
+    }
+}
+
+/// The four hex digits of a `\u` escape starting at `at`
+fn json_hex4(cursor: &mut Cursor, at: Position) -> Result<u32, SyntaxError> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = cursor.bump().and_then(|c| c.to_digit(16));
+        code = code * 16 + digit.ok_or_else(|| SyntaxError::at(at, "expected four hex digits after \\u"))?;
+    }
+    Ok(code)
+}
+
+/// Whether `text` follows JSON's number grammar, e.g. no leading zeros or `+`
+fn is_json_number(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let mut index = usize::from(bytes.first() == Some(&b'-'));
+    let digits = |index: &mut usize| {
+        let start = *index;
+        while bytes.get(*index).is_some_and(u8::is_ascii_digit) {
+            *index += 1;
+        }
+        *index - start
+    };
+    
+    let integer_start = index;
+    match digits(&mut index) {
+        0 => return false,
+        length => {
+            if length > 1 && bytes[integer_start] == b'0' {
+                return false;
+            }
+        }
+    }
+    if bytes.get(index) == Some(&b'.') {
+        index += 1;
+        if digits(&mut index) == 0 {
+            return false;
+        }
+    }
+    if matches!(bytes.get(index), Some(b'e' | b'E')) {
+        index += 1;
+        if matches!(bytes.get(index), Some(b'+' | b'-')) {
+            index += 1;
+        }
+        if digits(&mut index) == 0 {
+            return false;
+        }
+    }
+    index == bytes.len()
+}
+
+/// A `key: value` or `key:` line of a YAML file
+struct YamlLine {
+    indent: usize,
+    key: String,
+    key_at: Position,
+    /// `None` for `key:`, whose value is the more-indented block below it
+    value: Option<(Position, String)>,
+}
+
+/// Read line `number`, or `None` for blank and comment lines
+fn yaml_line(number: usize, line: &str) -> Result<Option<YamlLine>, SyntaxError> {
+    let at = |byte: usize| (number, line[..byte].chars().count() + 1);
+    let content = line.trim_start_matches(' ');
+    let indent = line.len() - content.len();
+    if content.starts_with('\t') {
+        return Err(SyntaxError::at(at(indent), "tabs can't be used for indentation"));
+    }
+    let content = content.trim_end();
+    if content.is_empty() || content.starts_with('#') {
+        return Ok(None);
+    }
+    if content == "-" || content.starts_with("- ") {
+        return Err(SyntaxError::at(at(indent), "sequences are not supported in authn files"));
+    }
+    
+    // The key, and the byte offset in `content` of the `:` after it
+    let (key, colon) = if content.starts_with(['"', '\'']) {
+        let (key, length) = yaml_quoted(content).map_err(|(offset, message)| SyntaxError::at(at(indent + offset), message))?;
+        let spaces = content[length..].len() - content[length..].trim_start().len();
+        (key, length + spaces)
+    } else {
+        let colon = content
+            .match_indices(':')
+            .map(|(index, _)| index)
+            .find(|&index| content[index + 1..].is_empty() || content[index + 1..].starts_with([' ', '\t']));
+        let Some(colon) = colon else {
+            return Err(SyntaxError::at(at(indent), "expected 'key: value' or 'key:'"));
+        };
+        (content[..colon].trim_end().to_string(), colon)
+    };
+    if !content[colon..].starts_with(':') {
+        return Err(SyntaxError::at(at(indent + colon), "expected ':' after the key"));
+    }
+    
+    let rest = &content[colon + 1..];
+    let offset = indent + colon + 1 + (rest.len() - rest.trim_start().len());
+    let value = yaml_scalar(rest.trim_start()).map_err(|(relative, message)| SyntaxError::at(at(offset + relative), message))?;
+    Ok(Some(YamlLine { indent, key, key_at: at(indent), value: value.map(|value| (at(offset), value)) }))
+}
+
+/// The value after a key's `:`, or `None` if there is none; errors carry a byte offset into `text`
+fn yaml_scalar(text: &str) -> Result<Option<String>, (usize, &'static str)> {
+    if text.is_empty() || text.starts_with('#') {
+        return Ok(None);
+    }
+    if text.starts_with(['"', '\'']) {
+        let (value, length) = yaml_quoted(text)?;
+        let rest = text[length..].trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err((text.len() - rest.len(), "unexpected text after the quoted value"));
+        }
+        return Ok(Some(value));
+    }
+    if text.starts_with(['[', '{', '&', '*', '!', '|', '>']) {
+        return Err((0, "flow collections, anchors, tags and block scalars are not supported in authn files"));
+    }
+    
+    let value = match text.find(" #").or_else(|| text.find("\t#")) {
+        Some(comment) => text[..comment].trim_end(),
+        None => text,
+    };
+    if let Some(colon) = value.find(": ") {
+        return Err((colon, "unquoted values can't contain ': '; quote the value"));
+    }
+    if matches!(value, "~" | "null" | "Null" | "NULL") {
+        return Err((0, NULL_UNSUPPORTED));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// A single- or double-quoted scalar at the start of `text`, with the bytes it takes up
+fn yaml_quoted(text: &str) -> Result<(String, usize), (usize, &'static str)> {
+    let quote = if text.starts_with('"') { '"' } else { '\'' };
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            // `''` is a literal quote in single-quoted values
+            if quote == '\'' && chars.peek().map(|&(_, next)| next) == Some('\'') {
+                chars.next();
+                value.push('\'');
+                continue;
+            }
+            return Ok((value, index + 1));
+        }
+        if c == '\\' && quote == '"' {
+            let escaped = match chars.next().map(|(_, escaped)| escaped) {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('/') => '/',
+                Some('0') => '\0',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('u') => {
+                    let hex: String = (0..4).filter_map(|_| chars.next().map(|(_, digit)| digit)).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 4)
+                        .and_then(char::from_u32)
+                        .ok_or((index, "expected four hex digits of a character after \\u"))?
+                }
+                _ => return Err((index, "invalid escape sequence")),
+            };
+            value.push(escaped);
+            continue;
+        }
+        value.push(c);
+    }
+    Err((0, "unterminated quoted value"))
+}
+
+/// The members of the block at `indent` starting at `lines[*index]`, leaving `index` after it
+fn yaml_block(lines: &[YamlLine], index: &mut usize, indent: usize) -> Result<Vec<(String, Position, Node)>, SyntaxError> {
+    let mut members = Vec::new();
+    while let Some(line) = lines.get(*index) {
+        if line.indent < indent {
+            break;
+        }
+        if line.indent > indent {
+            return Err(SyntaxError::at(line.key_at, "unexpected indentation"));
+        }
+        *index += 1;
+        
+        let node = match &line.value {
+            Some((at, value)) => Node { at: *at, kind: Kind::Scalar(value.clone()) },
+            None => match lines.get(*index) {
+                Some(next) if next.indent > indent => {
+                    Node { at: next.key_at, kind: Kind::Mapping(yaml_block(lines, index, next.indent)?) }
+                }
+                _ => return Err(SyntaxError::at(line.key_at, format!("'{}' has no value", line.key))),
+            },
+        };
+        push_member(&mut members, line.key.clone(), line.key_at, node)?;
+    }
+    Ok(members)
+}
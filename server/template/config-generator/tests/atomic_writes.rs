@@ -0,0 +1,108 @@
+//! Tests that outputs are replaced atomically through a renamed temp file.
+
+mod common;
+
+use common::{stderr, Workspace};
+use std::fs;
+use std::io::Read;
+
+/// Names of the files in `dir` within the workspace
+fn entries(workspace: &Workspace, dir: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(workspace.path(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn no_temp_files_are_left_behind() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(entries(&workspace, "secrets"), ["secrets.textproto"]);
+    assert!(!entries(&workspace, ".").iter().any(|name| name.contains(".tmp-")), "{:?}", entries(&workspace, "."));
+}
+
+#[test]
+fn a_reader_of_the_old_file_never_sees_the_new_content() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# previous config\n");
+    let mut reader = fs::File::open(workspace.path("config.textproto")).unwrap();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mut seen = String::new();
+    reader.read_to_string(&mut seen).unwrap();
+    assert_eq!(seen, "# previous config\n");
+    assert!(workspace.read("config.textproto").contains("smtp_host"));
+}
+
+#[test]
+fn failed_replace_cleans_up_its_temp_file() {
+    let workspace = Workspace::new();
+    // A non-empty directory can't be renamed over
+    workspace.write("config.textproto/keep", "untouched");
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("config.textproto"), "{}", stderr(&output));
+    assert_eq!(workspace.read("config.textproto/keep"), "untouched");
+    assert!(!entries(&workspace, ".").iter().any(|name| name.contains(".tmp-")), "{:?}", entries(&workspace, "."));
+}
+
+#[cfg(unix)]
+#[test]
+fn existing_config_permissions_are_kept() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "");
+    fs::set_permissions(workspace.path("config.textproto"), fs::Permissions::from_mode(0o640)).unwrap();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mode = fs::metadata(workspace.path("config.textproto")).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode, 0o640);
+}
+
+#[cfg(unix)]
+#[test]
+fn backed_up_outputs_keep_their_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# previous config\n");
+    fs::set_permissions(workspace.path("config.textproto"), fs::Permissions::from_mode(0o640)).unwrap();
+
+    let output = workspace.generate(&["--backup"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mode = |name: &str| fs::metadata(workspace.path(name)).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode("config.textproto"), 0o640);
+    assert_eq!(mode("config.textproto.bak"), 0o640);
+    assert_eq!(workspace.read("config.textproto.bak"), "# previous config\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn stale_temp_file_from_a_reused_pid_is_skipped() {
+    let workspace = Workspace::new();
+    // The pre-hook's parent is the generator, so this is the temp name it would pick first
+    let stale = workspace.path(".config.textproto.tmp-");
+    let hook = format!("echo stale > '{}'$PPID", stale.to_string_lossy());
+
+    let output = workspace.generate(&["--pre-hook", &hook]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("smtp_host"));
+    let temp_files: Vec<String> = entries(&workspace, ".").into_iter().filter(|name| name.contains(".tmp-")).collect();
+    assert_eq!(temp_files.len(), 1, "{:?}", temp_files);
+    assert_eq!(workspace.read(&temp_files[0]), "stale\n");
+}
@@ -0,0 +1,78 @@
+//! Tests for the authn file's `AUTH_MODE` key selecting which auth blocks are emitted.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+/// The default authn file without the lines for the given key prefix
+fn authn_without(prefix: &str) -> String {
+    AUTHN.lines().filter(|line| !line.starts_with(prefix)).map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn both_is_the_default() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+}
+
+#[test]
+fn email_mode_omits_oauth_and_needs_no_google_keys() {
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=email\n{}", authn_without("GOOGLE_")));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(!config.contains("oauth_providers"), "{}", config);
+    assert!(config.contains("auth_token_ttl_sec: 3600"), "{}", config);
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("TRAIL_EMAIL_SMTP_PASSWORD"), "{}", vault);
+    assert!(!vault.contains("GOOGLE"), "{}", vault);
+}
+
+#[test]
+fn oauth_mode_omits_email_and_needs_no_email_keys() {
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=oauth\n{}", authn_without("EMAIL_")));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(!config.contains("email"), "{}", config);
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET"), "{}", vault);
+    assert!(!vault.contains("TRAIL_EMAIL_SMTP_PASSWORD"), "{}", vault);
+}
+
+#[test]
+fn missing_credentials_for_mode_fail() {
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=oauth\n{}", authn_without("GOOGLE_OAUTH_CLIENT_SECRET")));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("missing: GOOGLE_OAUTH_CLIENT_SECRET (required for AUTH_MODE=oauth"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn unknown_mode_is_rejected() {
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=sso\n{}", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("invalid: AUTH_MODE='sso' (must be one of email, oauth, both)"), "{}", stderr(&output));
+}
@@ -0,0 +1,117 @@
+//! Tests for `--authn-template`, which renders `${VAR}` references from the environment.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+const TEMPLATE: &str = "\
+# rendered for ${DEPLOY_ENV}
+GOOGLE_OAUTH_CLIENT_SECRET=${TEST_GOOGLE_SECRET}
+EMAIL_SMTP_PASSWORD=${TEST_SMTP_PASSWORD}
+PRICE=$5
+";
+
+#[test]
+fn renders_all_variables() {
+    let workspace = Workspace::new();
+    workspace.write(".authn.template", TEMPLATE);
+    let env = [
+        ("DEPLOY_ENV", "staging".to_string()),
+        ("TEST_GOOGLE_SECRET", "GOCSPX-from-env".to_string()),
+        ("TEST_SMTP_PASSWORD", "p@ss".to_string()),
+    ];
+
+    let output = workspace.run_with_env(&["--authn-template", ".authn.template", "rendered.authn"], &env);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        workspace.read("rendered.authn"),
+        "# rendered for staging\nGOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-from-env\nEMAIL_SMTP_PASSWORD=p@ss\nPRICE=$5\n"
+    );
+}
+
+#[test]
+fn missing_variable_fails_without_writing() {
+    let workspace = Workspace::new();
+    workspace.write(".authn.template", TEMPLATE);
+    let env = [
+        ("DEPLOY_ENV", "staging".to_string()),
+        ("TEST_GOOGLE_SECRET", "GOCSPX-from-env".to_string()),
+    ];
+
+    let output = workspace.run_with_env(&["--authn-template", ".authn.template", "rendered.authn"], &env);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("unset environment variables: TEST_SMTP_PASSWORD"), "{}", message);
+    assert!(!message.contains("GOCSPX-from-env"), "{}", message);
+    assert!(!workspace.exists("rendered.authn"));
+}
+
+#[test]
+fn output_defaults_to_authn_path() {
+    let workspace = Workspace::new();
+    workspace.write(".authn.template", "KEY=${TEST_VALUE}\n");
+    let env = [
+        ("TEST_VALUE", "v".to_string()),
+        ("TRAIL_GEN_AUTHN", workspace.path("from-env.authn").to_string_lossy().into_owned()),
+    ];
+
+    let output = workspace.run_with_env(&["--authn-template", ".authn.template"], &env);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(workspace.read("from-env.authn"), "KEY=v\n");
+}
+
+#[test]
+fn value_with_a_line_break_is_refused() {
+    let workspace = Workspace::new();
+    workspace.write(".authn.template", "EMAIL_SENDER_NAME=${TEST_SENDER}\n");
+
+    for injected in ["Trail\nAUTH_MODE=email", "Trail\rAUTH_MODE=email"] {
+        let output = workspace.run_with_env(&["--authn-template", ".authn.template", "rendered.authn"], &[("TEST_SENDER", injected.to_string())]);
+
+        assert!(!output.status.success());
+        let message = stderr(&output);
+        assert!(message.contains("${TEST_SENDER} contains a line break, which would add lines to the authn file"), "{}", message);
+        assert!(!message.contains("AUTH_MODE=email"), "{}", message);
+        assert!(!workspace.exists("rendered.authn"));
+    }
+}
+
+#[test]
+fn values_the_parser_would_change_are_quoted() {
+    let workspace = Workspace::new();
+    workspace.write(".authn.template", "GOOGLE_OAUTH_CLIENT_SECRET=${TEST_SECRET}\nEMAIL_SMTP_PASSWORD=${TEST_PASSWORD}\nEMAIL_SENDER_NAME=${TEST_NAME}\n");
+    let env = [
+        ("TEST_SECRET", "abc #def".to_string()),
+        ("TEST_PASSWORD", "@/etc/passwd".to_string()),
+        ("TEST_NAME", " TrailBase ".to_string()),
+    ];
+
+    let output = workspace.run_with_env(&["--authn-template", ".authn.template", "rendered.authn"], &env);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        workspace.read("rendered.authn"),
+        "GOOGLE_OAUTH_CLIENT_SECRET='abc #def'\nEMAIL_SMTP_PASSWORD='@/etc/passwd'\nEMAIL_SENDER_NAME=' TrailBase '\n"
+    );
+}
+
+#[test]
+fn values_that_cannot_be_quoted_are_refused() {
+    let workspace = Workspace::new();
+    for (template, value) in [
+        // Part of a larger value, so quoting would change the rest of it
+        ("EMAIL_SENDER_NAME=Trail ${TEST_VALUE}\n", "#1"),
+        // A single quote can't appear inside single quotes
+        ("EMAIL_SENDER_NAME=${TEST_VALUE}\n", "'it's'"),
+    ] {
+        workspace.write(".authn.template", template);
+        let output = workspace.run_with_env(&["--authn-template", ".authn.template", "rendered.authn"], &[("TEST_VALUE", value.to_string())]);
+
+        assert!(!output.status.success(), "{}", template);
+        assert!(stderr(&output).contains("${TEST_VALUE} has a leading quote or '@'"), "{}", stderr(&output));
+        assert!(!workspace.exists("rendered.authn"));
+    }
+}
@@ -0,0 +1,74 @@
+//! Tests for `--backup`, which keeps the previous outputs as `<output>.bak`.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+#[test]
+fn existing_outputs_are_backed_up() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# hand-edited config\n");
+    workspace.write("secrets/secrets.textproto", "# old vault\n");
+
+    let output = workspace.generate(&["--backup"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(workspace.read("config.textproto.bak"), "# hand-edited config\n");
+    assert_eq!(workspace.read("secrets/secrets.textproto.bak"), "# old vault\n");
+    assert!(workspace.read("config.textproto").starts_with("# Auto-generated config.Config textproto"));
+    assert!(stderr(&output).contains("config.textproto.bak"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_outputs_need_no_backup() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--backup"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto.bak"));
+    assert!(!workspace.exists("secrets/secrets.textproto.bak"));
+}
+
+#[test]
+fn failed_backup_aborts_before_writing() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# hand-edited config\n");
+    // A non-empty directory where the backup should go makes the rename fail
+    workspace.write("config.textproto.bak/keep", "");
+
+    let output = workspace.generate(&["--backup"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("failed to back up"), "{}", stderr(&output));
+    assert_eq!(workspace.read("config.textproto"), "# hand-edited config\n");
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn failed_vault_backup_leaves_the_config_in_place() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# hand-edited config\n");
+    workspace.write("secrets/secrets.textproto", "# old vault\n");
+    // The config is backed up first; the vault's backup then fails
+    workspace.write("secrets/secrets.textproto.bak/keep", "");
+
+    let output = workspace.generate(&["--backup"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("failed to back up"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("no output was written"), "{}", stderr(&output));
+    assert_eq!(workspace.read("config.textproto"), "# hand-edited config\n");
+    assert_eq!(workspace.read("secrets/secrets.textproto"), "# old vault\n");
+}
+
+#[test]
+fn without_backup_outputs_are_overwritten() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "# hand-edited config\n");
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto.bak"));
+}
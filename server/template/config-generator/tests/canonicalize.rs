@@ -0,0 +1,112 @@
+//! Tests for `--canonicalize`, which reformats an existing config or vault in place.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+const MESSY_CONFIG: &str = r#"auth { oauth_providers: [{key: "google" value { provider_id: GOOGLE client_id: "g" }},
+  {key: "discord" value {client_id: "d"}}] auth_token_ttl_sec: 60 }
+server {site_url: "http://localhost:7000"    application_name: "TrailBase"}
+# a hand-written comment
+email {}
+record_apis { name: "counters" acl_authenticated: [READ, CREATE] }
+"#;
+
+const CANONICAL_CONFIG: &str = r#"# Auto-generated config.Config textproto
+email {}
+server {
+  application_name: "TrailBase"
+  site_url: "http://localhost:7000"
+}
+auth {
+  auth_token_ttl_sec: 60
+  oauth_providers: [{
+    key: "discord"
+    value {
+      client_id: "d"
+    }
+  }, {
+    key: "google"
+    value {
+      client_id: "g"
+      provider_id: GOOGLE
+    }
+  }]
+}
+record_apis: [{
+  name: "counters"
+  acl_authenticated: [READ, CREATE]
+}]"#;
+
+fn canonicalize(workspace: &Workspace, name: &str) -> std::process::Output {
+    workspace.run(&["--canonicalize", name])
+}
+
+#[test]
+fn messy_config_is_reformatted() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", MESSY_CONFIG);
+
+    let output = canonicalize(&workspace, "config.textproto");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(workspace.read("config.textproto"), CANONICAL_CONFIG);
+}
+
+#[test]
+fn canonical_form_is_stable() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", CANONICAL_CONFIG);
+
+    assert!(canonicalize(&workspace, "config.textproto").status.success());
+    assert_eq!(workspace.read("config.textproto"), CANONICAL_CONFIG);
+}
+
+#[test]
+fn vault_secrets_are_sorted_by_key() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "secrets.textproto",
+        r#"secrets: [{key: "ZETA" value: "z"}, {key: "ALPHA" value: "a\"quoted"}] secrets {key: "MID" value: "m"}"#,
+    );
+
+    let output = canonicalize(&workspace, "secrets.textproto");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        workspace.read("secrets.textproto"),
+        r#"# Auto-generated config.Vault textproto
+secrets: [{
+  key: "ALPHA"
+  value: "a\"quoted"
+}, {
+  key: "MID"
+  value: "m"
+}, {
+  key: "ZETA"
+  value: "z"
+}]"#
+    );
+}
+
+#[test]
+fn generated_config_is_already_canonical() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    let generated = workspace.read("config.textproto");
+
+    assert!(canonicalize(&workspace, "config.textproto").status.success());
+    assert_eq!(workspace.read("config.textproto"), generated.trim_end());
+}
+
+#[test]
+fn invalid_file_is_left_untouched() {
+    let workspace = Workspace::new();
+    workspace.write("broken.textproto", "server { site_url: }");
+
+    let output = canonicalize(&workspace, "broken.textproto");
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("config.Config"), "{}", stderr(&output));
+    assert_eq!(workspace.read("broken.textproto"), "server { site_url: }");
+}
@@ -0,0 +1,76 @@
+//! Tests for `--check`, which verifies existing outputs against the template and authn file.
+
+mod common;
+
+use common::{stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn freshly_generated_outputs_pass() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+
+    let output = workspace.generate(&["--check"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("are up to date"), "{}", stderr(&output));
+}
+
+#[test]
+fn changed_secret_is_named_but_not_shown() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    let vault_before = workspace.read("secrets/secrets.textproto");
+
+    workspace.write(
+        ".authn",
+        &AUTHN.replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD=rotated-smtp-password"),
+    );
+    let output = workspace.generate(&["--check"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert_eq!(report, "vault: ~ TRAIL_EMAIL_SMTP_PASSWORD differs\n");
+    let everything = format!("{}{}", report, stderr(&output));
+    assert!(!everything.contains("smtp-test-password"), "{}", everything);
+    assert!(!everything.contains("rotated"), "{}", everything);
+
+    // Check mode never writes
+    assert_eq!(workspace.read("secrets/secrets.textproto"), vault_before);
+}
+
+#[test]
+fn hand_edited_config_and_extra_secret_are_reported() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(
+        "config.textproto",
+        &workspace.read("config.textproto").replace("application_name: \"TrailBase\"", "application_name: \"Edited\""),
+    );
+    workspace.write(
+        "secrets/secrets.textproto",
+        &format!("{}\nsecrets: [{{ key: \"STALE_SECRET\" value: \"stale-value\" }}]\n", workspace.read("secrets/secrets.textproto")),
+    );
+
+    let output = workspace.generate(&["--check"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(report.contains("config: ~ server.application_name: "), "{}", report);
+    assert!(report.contains("vault: - STALE_SECRET (no longer generated)\n"), "{}", report);
+    assert!(!report.contains("stale-value"), "{}", report);
+    assert!(stderr(&output).contains("2 difference(s)"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_outputs_are_drift() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--check"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(report.contains("config: ") && report.contains("config.textproto does not exist"), "{}", report);
+    assert!(report.contains("secrets.textproto does not exist"), "{}", report);
+    assert!(!workspace.exists("config.textproto"));
+}
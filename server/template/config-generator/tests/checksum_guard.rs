@@ -0,0 +1,52 @@
+//! Tests for `--checksum-guard`, which refuses to overwrite hand-edited outputs.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+#[test]
+fn records_checksums_and_regenerates_unmodified_outputs() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto.checksum").starts_with("fnv1a64:"));
+    assert!(workspace.exists("secrets/secrets.textproto.checksum"));
+
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn refuses_to_overwrite_modified_output() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let edited = format!("{}# hand edit\n", workspace.read("config.textproto"));
+    workspace.write("config.textproto", &edited);
+    workspace.write(".authn", &common::AUTHN.replace("smtp.mail.test", "smtp.other.test"));
+
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("config.textproto' was modified since it was last generated"), "{}", message);
+    assert!(message.contains("--force"), "{}", message);
+    assert_eq!(workspace.read("config.textproto"), edited);
+}
+
+#[test]
+fn force_overwrites_modified_output() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    workspace.write("secrets/secrets.textproto", "# hand edit\n");
+
+    let output = workspace.generate(&["--checksum-guard", "--force"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("GOCSPX-test-client-secret"));
+
+    // The new checksum is recorded, so the next guarded run succeeds again
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,125 @@
+//! Shared helpers for the config-generator integration tests: a scratch
+//! directory pre-populated with the real template and a complete authn file,
+//! plus a runner for the compiled binary.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// The template shipped next to the generator
+pub const TEMPLATE: &str = include_str!("../../../config.textproto.template");
+
+/// An authn file with every required key present
+pub const AUTHN: &str = "\
+GOOGLE_OAUTH_CLIENT_ID=test-client-id.apps.googleusercontent.com
+GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret
+EMAIL_SMTP_HOST=smtp.mail.test
+EMAIL_SMTP_PORT=587
+EMAIL_SMTP_USERNAME=mailer@mail.test
+EMAIL_SMTP_PASSWORD=smtp-test-password
+EMAIL_SENDER_NAME=TrailBase Test
+EMAIL_SENDER_ADDRESS=noreply@mail.test
+";
+
+/// A scratch directory holding the generator's inputs and outputs
+pub struct Workspace {
+    pub dir: TempDir,
+}
+
+impl Workspace {
+    /// Create a workspace with the default template and authn file
+    pub fn new() -> Self {
+        Self::with_authn(AUTHN)
+    }
+
+    /// Create a workspace with the default template and the given authn content
+    pub fn with_authn(authn: &str) -> Self {
+        let workspace = Workspace {
+            dir: TempDir::new().expect("create temp dir"),
+        };
+        workspace.write("config.textproto.template", TEMPLATE);
+        workspace.write(".authn", authn);
+        workspace
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn write(&self, name: &str, content: &str) {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("create parent dir");
+        }
+        fs::write(path, content).expect("write workspace file");
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).expect("read workspace file")
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    /// The four positional arguments for a default run
+    pub fn default_args(&self) -> Vec<String> {
+        ["config.textproto.template", ".authn", "config.textproto", "secrets/secrets.textproto"]
+            .iter()
+            .map(|name| path_arg(&self.path(name)))
+            .collect()
+    }
+
+    /// Run the generator with the default positional arguments plus `extra`
+    pub fn generate(&self, extra: &[&str]) -> Output {
+        let mut args: Vec<String> = extra.iter().map(|arg| arg.to_string()).collect();
+        args.extend(self.default_args());
+        self.run(&args)
+    }
+
+    /// Run the generator with exactly `args`, from inside the workspace
+    pub fn run<S: AsRef<str>>(&self, args: &[S]) -> Output {
+        self.run_with_env(args, &[])
+    }
+
+    /// Run the generator with exactly `args` and extra environment variables
+    pub fn run_with_env<S: AsRef<str>>(&self, args: &[S], env: &[(&str, String)]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_config-generator"))
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .envs(env.iter().map(|(name, value)| (name, value)))
+            .current_dir(self.dir.path())
+            .output()
+            .expect("run config-generator")
+    }
+
+    /// Run the generator with exactly `args`, feeding `input` on stdin
+    pub fn run_with_stdin<S: AsRef<str>>(&self, args: &[S], input: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_config-generator"))
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .current_dir(self.dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn config-generator");
+        // The generator may exit without reading stdin (e.g. on a usage error), closing the pipe
+        let _ = child.stdin.take().expect("piped stdin").write_all(input.as_bytes());
+        child.wait_with_output().expect("run config-generator")
+    }
+}
+
+pub fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
@@ -0,0 +1,60 @@
+//! Tests for `--compare-config`, the descriptor-based field diff against an existing config.
+
+mod common;
+
+use common::{path_arg, stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn identical_config_reports_no_differences() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+
+    let existing = path_arg(&workspace.path("config.textproto"));
+    let output = workspace.generate(&["--compare-config", &existing]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn changed_field_is_reported_redacted() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    let before = workspace.read("config.textproto");
+
+    workspace.write(
+        ".authn",
+        &AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test", "EMAIL_SMTP_HOST=relay.mail.test"),
+    );
+    let existing = path_arg(&workspace.path("config.textproto"));
+    let output = workspace.generate(&["--compare-config", &existing]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert_eq!(report.lines().count(), 1, "{}", report);
+    assert!(report.starts_with("~ email.smtp_host: "), "{}", report);
+    assert!(!report.contains("relay.mail.test"), "{}", report);
+
+    // Compare mode never writes
+    assert_eq!(workspace.read("config.textproto"), before);
+}
+
+#[test]
+fn reformatted_config_has_no_differences() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+
+    // Field order and whitespace don't matter, only field values
+    let reformatted = workspace
+        .read("config.textproto")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+    workspace.write("existing.textproto", &reformatted);
+    let existing = path_arg(&workspace.path("existing.textproto"));
+    let output = workspace.generate(&["--compare-config", &existing]);
+
+    assert_eq!(output.status.code(), Some(0), "{}\n{}", stdout(&output), stderr(&output));
+}
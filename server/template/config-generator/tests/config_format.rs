@@ -0,0 +1,80 @@
+//! Tests for `--config-format`, which writes the config in several formats next to each other.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+use config_generator::{config_to_json, Schema};
+
+/// Run with `--config-format formats` and the config at `<workspace>/config` (no extension)
+fn generate_formats(workspace: &Workspace, formats: &str) -> std::process::Output {
+    let mut args = vec!["--config-format".to_string(), formats.to_string()];
+    args.extend(workspace.default_args());
+    args[4] = path_arg(&workspace.path("config"));
+    workspace.run(&args)
+}
+
+#[test]
+fn textproto_and_json_are_written_and_consistent() {
+    let workspace = Workspace::new();
+
+    let output = generate_formats(&workspace, "textproto,json");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.exists("config"));
+    let textproto = workspace.read("config.textproto");
+    let json = workspace.read("config.json");
+    let schema = Schema::load(None).expect("embedded schema");
+    assert_eq!(json, config_to_json(&schema, &textproto).expect("config converts"));
+    // Field names, integer and enum encodings follow the proto3 JSON mapping
+    for expected in [
+        "    \"smtpHost\": \"smtp.mail.test\",\n",
+        "    \"smtpPort\": 587,\n",
+        "    \"smtpPassword\": \"<REDACTED>\",\n",
+        "    \"logsRetentionSec\": \"604800\"\n",
+        "      \"google\": {\n        \"clientId\": \"test-client-id.apps.googleusercontent.com\",\n",
+        "        \"providerId\": \"GOOGLE\"\n",
+        "    \"aclAuthenticated\": [\"READ\", \"UPDATE\", \"CREATE\"],\n",
+    ] {
+        assert!(json.contains(expected), "{:?} missing from:\n{}", expected, json);
+    }
+    // Every string value of the textproto is in the JSON
+    for value in textproto.lines().filter_map(|line| line.split_once(": \"")).map(|(_, value)| value.trim_end_matches('"')) {
+        assert!(json.contains(&format!("\"{}\"", value)), "{} missing from:\n{}", value, json);
+    }
+    assert!(!workspace.read("secrets/secrets.textproto").is_empty());
+}
+
+#[test]
+fn json_escapes_string_values() {
+    let schema = Schema::load(None).expect("embedded schema");
+
+    let json = config_to_json(&schema, "server { application_name: \"Trail \\\"Base\\\"\\n\" }").expect("config converts");
+
+    assert_eq!(json, "{\n  \"server\": {\n    \"applicationName\": \"Trail \\\"Base\\\"\\n\"\n  }\n}\n");
+}
+
+#[test]
+fn textproto_alone_keeps_the_extension_rule() {
+    let workspace = Workspace::new();
+
+    let output = generate_formats(&workspace, "textproto");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+    assert!(!workspace.exists("config.json"));
+}
+
+#[test]
+fn unknown_or_textproto_less_lists_are_rejected() {
+    let workspace = Workspace::new();
+
+    for (formats, expected) in [
+        ("textproto,yaml", "--config-format entries must be 'textproto' or 'json', got 'yaml'"),
+        ("json", "--config-format must include textproto"),
+    ] {
+        let output = generate_formats(&workspace, formats);
+        assert_eq!(output.status.code(), Some(2), "{}", formats);
+        assert!(stderr(&output).contains(expected), "{}", stderr(&output));
+    }
+    assert!(!workspace.exists("config.textproto") && !workspace.exists("config.json"));
+}
@@ -0,0 +1,49 @@
+//! Tests for `--config-patch`, which prints only the changed config fields as textproto.
+
+mod common;
+
+use common::{stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn patch_contains_single_changed_field() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    workspace.write("existing.textproto", &workspace.read("config.textproto"));
+    workspace.write(".authn", &AUTHN.replace("smtp.mail.test", "smtp.other.test"));
+
+    let output = workspace.generate(&["--config-patch", "existing.textproto"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "email {\n  smtp_host: \"smtp.other.test\"\n}\n");
+}
+
+#[test]
+fn map_entries_are_patched_by_key() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    workspace.write("existing.textproto", &workspace.read("config.textproto"));
+    workspace.write(".authn", &AUTHN.replace("test-client-id", "rotated-client-id"));
+
+    let output = workspace.generate(&["--config-patch", "existing.textproto"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let patch = stdout(&output);
+    assert!(patch.contains("key: \"google\""), "{}", patch);
+    assert!(patch.contains("client_id: \"rotated-client-id.apps.googleusercontent.com\""), "{}", patch);
+    assert!(!patch.contains("client_secret") && !patch.contains("email"), "{}", patch);
+}
+
+#[test]
+fn unchanged_config_gives_empty_patch() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    workspace.write("existing.textproto", &workspace.read("config.textproto"));
+
+    let output = workspace.generate(&["--config-patch", "existing.textproto"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("No differences"), "{}", stderr(&output));
+}
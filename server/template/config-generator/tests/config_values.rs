@@ -0,0 +1,106 @@
+//! Tests for `CONFIG_<field path>` authn keys, which set other config fields through the schema.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_as, AuthnFormat};
+
+#[test]
+fn values_are_set_by_path_as_their_field_types() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}CONFIG_server.site_url=https://app.example.com\nCONFIG_server.backup_interval_sec=86400\nCONFIG_auth.disable_password_auth=true\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&["--verbose"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("site_url: \"https://app.example.com\""), "{}", config);
+    assert!(!config.contains("http://localhost:7000"), "{}", config);
+    assert!(config.contains("backup_interval_sec: 86400"), "{}", config);
+    assert!(config.contains("disable_password_auth: true"), "{}", config);
+    assert!(stderr(&output).contains("set server.site_url from CONFIG_server.site_url\n"), "{}", stderr(&output));
+    // Not an unknown key, so nothing to warn about
+    assert!(!stderr(&output).contains("Warning:"), "{}", stderr(&output));
+}
+
+#[test]
+fn messages_along_the_path_are_created() {
+    let workspace = Workspace::with_authn(&format!("{}CONFIG_server.s3_storage_config.bucket_name=uploads\n", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("s3_storage_config {\n    bucket_name: \"uploads\"\n  }"), "{}", config);
+}
+
+#[test]
+fn unknown_paths_and_bad_values_are_all_reported() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}CONFIG_server.site_ur=https://app.example.com\nCONFIG_server.logs_retention_sec=week\nCONFIG_auth.oauth_providers=github\nCONFIG_server=x\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    for expected in [
+        "CONFIG_server.site_ur='https://app.example.com' (config.ServerConfig has no field 'site_ur')",
+        "CONFIG_server.logs_retention_sec='week' (not a valid value for config.ServerConfig.logs_retention_sec, which is int64)",
+        "CONFIG_auth.oauth_providers='github' (config.AuthConfig.oauth_providers is map<string, config.OAuthProviderConfig>, which a CONFIG_ key can't set)",
+        "CONFIG_server='x' (config.Config.server is a config.ServerConfig message; name one of its fields)",
+    ] {
+        assert!(message.contains(expected), "{}\n{}", expected, message);
+    }
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn secrets_are_refused_without_showing_them() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}CONFIG_email.smtp_password=leaked-password\nCONFIG_server.s3_storage_config.secret_access_key=leaked-key\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("CONFIG_email.smtp_password (config.EmailConfig.smtp_password is a secret, which must not be written to the config)"),
+        "{}",
+        message
+    );
+    assert!(message.contains("CONFIG_server.s3_storage_config.secret_access_key (config.S3StorageConfig.secret_access_key is a secret"), "{}", message);
+    assert!(!message.contains("leaked"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn structured_files_nest_values_under_config() {
+    let yaml = "\
+oauth_providers:
+  google:
+    client_id: test-client-id
+    client_secret: test-client-secret
+auth_mode: oauth
+config:
+  server:
+    site_url: https://app.example.com
+    s3_storage_config:
+      bucket_name: uploads
+";
+
+    let authn = parse_authn_as(yaml, AuthnFormat::Yaml, std::iter::empty()).expect("authn file parses");
+
+    assert_eq!(
+        authn.config_values,
+        [
+            ("server.site_url".to_string(), "https://app.example.com".to_string()),
+            ("server.s3_storage_config.bucket_name".to_string(), "uploads".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,82 @@
+//! Tests for `--decode-base64` and `--decode-hex`, which decode encoded secrets before vaulting them.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{generate_vault_with, parse_authn_file, GenerateOptions, Schema, SecretEncoding};
+use std::collections::BTreeMap;
+
+#[test]
+fn listed_secrets_are_decoded_into_the_vault() {
+    let workspace = Workspace::with_authn(
+        &AUTHN
+            .replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD=cm90YXRlZC1zbXRwLXBhc3N3b3Jk")
+            .replace("GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret", "GOOGLE_OAUTH_CLIENT_SECRET=67682d736563726574"),
+    );
+
+    let output = workspace.generate(&["--decode-base64", "EMAIL_SMTP_PASSWORD", "--decode-hex", "GOOGLE_OAUTH_CLIENT_SECRET"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("value: \"rotated-smtp-password\""), "{}", vault);
+    assert!(vault.contains("value: \"gh-secret\""), "{}", vault);
+    assert!(!vault.contains("cm90YXRl"), "{}", vault);
+}
+
+#[test]
+fn values_are_used_as_written_by_default() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("smtp-test-password", "YWI="));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"YWI=\""));
+}
+
+#[test]
+fn undecodable_value_names_the_key_but_not_the_value() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("smtp-test-password", "not*base64"));
+
+    let output = workspace.generate(&["--decode-base64", "EMAIL_SMTP_PASSWORD"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("cannot decode EMAIL_SMTP_PASSWORD as base64: invalid base64 character at position 4"), "{}", message);
+    assert!(!message.contains("not*base64"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn non_secret_and_unset_keys_are_rejected() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--decode-hex", "EMAIL_SMTP_HOST"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--decode-hex: 'EMAIL_SMTP_HOST' is not a secret authn key"), "{}", stderr(&output));
+
+    let output = workspace.generate(&["--decode-base64", "GITHUB_OAUTH_CLIENT_SECRET"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("GITHUB_OAUTH_CLIENT_SECRET is listed for base64 decoding, but the authn file has no such secret"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn library_decodes_hex_and_rejects_non_utf8() {
+    let decode = |value: &str| {
+        let authn = parse_authn_file(&AUTHN.replace("smtp-test-password", value)).expect("authn file parses");
+        let options = GenerateOptions {
+            secret_encodings: BTreeMap::from([("EMAIL_SMTP_PASSWORD".to_string(), SecretEncoding::Hex)]),
+            ..GenerateOptions::default()
+        };
+        generate_vault_with(&Schema::load(None).expect("embedded schema"), &authn, &options)
+            .map(|vault| vault.secrets["TRAIL_EMAIL_SMTP_PASSWORD"].clone())
+            .map_err(|e| e.to_string())
+    };
+
+    assert_eq!(decode("48692D5061737321"), Ok("Hi-Pass!".to_string()));
+    assert!(decode("abc").unwrap_err().ends_with("odd number of hex digits"));
+    assert!(decode("ff00").unwrap_err().ends_with("the decoded bytes are not UTF-8 text"));
+}
@@ -0,0 +1,185 @@
+//! Tests for `--descriptor-set` and the errors reported for unusable descriptor sets.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+use config_generator::Schema;
+use prost::Message;
+use prost_reflect::prost_types::field_descriptor_proto::Type;
+use prost_reflect::prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+/// The descriptor set embedded in the binary
+const EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+/// The embedded descriptor set with `change` applied to `EmailConfig`'s `field`
+fn with_email_field(field: &str, change: impl FnOnce(&mut FieldDescriptorProto)) -> Vec<u8> {
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let email = set
+        .file
+        .iter_mut()
+        .flat_map(|file| file.message_type.iter_mut())
+        .find(|message| message.name() == "EmailConfig")
+        .expect("EmailConfig in embedded descriptor set");
+    change(email.field.iter_mut().find(|candidate| candidate.name() == field).expect("field in EmailConfig"));
+    set.encode_to_vec()
+}
+
+fn write_descriptor_set(workspace: &Workspace, bytes: &[u8]) -> String {
+    let path = workspace.path("descriptors.bin");
+    std::fs::write(&path, bytes).unwrap();
+    path_arg(&path)
+}
+
+#[test]
+fn runtime_descriptor_set_is_used() {
+    let workspace = Workspace::new();
+    let path = write_descriptor_set(&workspace, EMBEDDED);
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn truncated_descriptor_set_is_a_clean_error() {
+    let workspace = Workspace::new();
+    let path = write_descriptor_set(&workspace, &EMBEDDED[..EMBEDDED.len() / 2]);
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(message.contains("corrupt or for an incompatible proto version"), "{}", message);
+    assert!(!message.contains("panicked"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn missing_message_is_a_clean_error() {
+    let workspace = Workspace::new();
+    let set = FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("vault.proto".to_string()),
+            package: Some("config".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Vault".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    };
+    let path = write_descriptor_set(&workspace, &set.encode_to_vec());
+
+    let output = workspace.run(&["--descriptor-set", &path, "--canonicalize", "config.textproto.template"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(message.contains("message 'config.Config' not found"), "{}", message);
+    assert!(!message.contains("panicked"), "{}", message);
+}
+
+#[test]
+fn mismatched_vault_secrets_type_is_a_clean_error() {
+    let workspace = Workspace::new();
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let entry = set
+        .file
+        .iter_mut()
+        .flat_map(|file| file.message_type.iter_mut())
+        .find(|message| message.name() == "Vault")
+        .and_then(|vault| vault.nested_type.iter_mut().find(|nested| nested.name() == "SecretsEntry"))
+        .expect("Vault.SecretsEntry in embedded descriptor set");
+    let value = entry.field.iter_mut().find(|field| field.name() == "value").unwrap();
+    value.set_type(Type::Int32);
+    let path = write_descriptor_set(&workspace, &set.encode_to_vec());
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(
+        message.contains("field 'config.Vault.secrets' in descriptor set"),
+        "{}",
+        message
+    );
+    assert!(message.contains("is map<string, int32>, expected map<string, string>"), "{}", message);
+    assert!(!message.contains("panicked"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn renamed_email_field_is_a_clean_error() {
+    let workspace = Workspace::new();
+    let path = write_descriptor_set(&workspace, &with_email_field("smtp_host", |field| field.name = Some("smtp_server".to_string())));
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(
+        message.contains("message 'config.EmailConfig' in descriptor set") && message.contains("has no 'smtp_host' field for EMAIL_SMTP_HOST"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn email_port_follows_the_schema_type() {
+    let workspace = Workspace::new();
+    let path = write_descriptor_set(&workspace, &with_email_field("smtp_port", |field| field.set_type(Type::Int64)));
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("smtp_port: 587"));
+
+    let path = write_descriptor_set(&workspace, &with_email_field("smtp_port", |field| field.set_type(Type::String)));
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(message.contains("field 'config.EmailConfig.smtp_port' in descriptor set"), "{}", message);
+    assert!(message.contains("is string, expected an integer for EMAIL_SMTP_PORT"), "{}", message);
+}
+
+#[test]
+fn renamed_vault_message_is_found_with_vault_message() {
+    let workspace = Workspace::new();
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let vault = set
+        .file
+        .iter_mut()
+        .flat_map(|file| file.message_type.iter_mut())
+        .find(|message| message.name() == "Vault")
+        .expect("Vault in embedded descriptor set");
+    vault.name = Some("SecretStore".to_string());
+    for field in &mut vault.field {
+        field.type_name = field.type_name.as_ref().map(|name| name.replace(".config.Vault.", ".config.SecretStore."));
+    }
+    let path = write_descriptor_set(&workspace, &set.encode_to_vec());
+
+    let output = workspace.generate(&["--descriptor-set", &path]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let message = stderr(&output);
+    assert!(message.contains("message 'config.Vault' not found in descriptor set"), "{}", message);
+    assert!(message.contains("messages with a map<string, string> secrets field: config.SecretStore"), "{}", message);
+    assert!(!message.contains("panicked"), "{}", message);
+
+    let output = workspace.generate(&["--descriptor-set", &path, "--vault-message", "config.SecretStore"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.starts_with("# Auto-generated config.SecretStore textproto\n"), "{}", vault);
+    assert!(vault.contains("key: \"TRAIL_EMAIL_SMTP_PASSWORD\""), "{}", vault);
+}
+
+#[test]
+fn embedded_schema_is_a_result() {
+    let schema = Schema::embedded().expect("embedded descriptor set decodes");
+
+    assert_eq!(schema.config.full_name(), "config.Config");
+    assert_eq!(schema.vault.full_name(), "config.Vault");
+}
@@ -0,0 +1,77 @@
+//! Tests for `--diff`, which previews changes to existing outputs and confirms before writing.
+
+mod common;
+
+use common::{stderr, stdout, Workspace, AUTHN};
+
+/// The default authn file with a rotated password and a renamed sender
+fn changed_authn() -> String {
+    AUTHN
+        .replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD=rotated-smtp-password")
+        .replace("EMAIL_SENDER_NAME=TrailBase Test", "EMAIL_SENDER_NAME=Ops Team")
+}
+
+#[test]
+fn yes_prints_the_masked_diff_and_writes() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(".authn", &changed_authn());
+
+    let output = workspace.generate(&["--diff", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let diff = stdout(&output);
+    assert!(diff.contains("config.textproto (generated)\n@@ -5,7 +5,7 @@\n"), "{}", diff);
+    assert!(diff.contains("\n-  sender_name: \"TrailBase Test\"\n+  sender_name: \"Ops Team\"\n"), "{}", diff);
+    assert!(diff.contains("secrets.textproto (generated)\n"), "{}", diff);
+    assert!(diff.contains("   key: \"TRAIL_EMAIL_SMTP_PASSWORD\"\n-  value: \"smtp…\"\n+  value: \"rota…\"\n"), "{}", diff);
+    assert!(!diff.contains("smtp-test-password") && !diff.contains("rotated-smtp-password"), "{}", diff);
+    assert!(workspace.read("secrets/secrets.textproto").contains("rotated-smtp-password"));
+}
+
+#[test]
+fn changes_without_a_terminal_need_yes() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    let config_before = workspace.read("config.textproto");
+    workspace.write(".authn", &changed_authn());
+
+    let output = workspace.generate(&["--diff"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("--diff: 2 existing output(s) would change, but stdin is not a terminal to confirm on; pass --yes to write anyway"),
+        "{}",
+        message
+    );
+    assert!(stdout(&output).contains("+  sender_name: \"Ops Team\""), "{}", stdout(&output));
+    assert_eq!(workspace.read("config.textproto"), config_before);
+    assert!(workspace.read("secrets/secrets.textproto").contains("smtp-test-password"));
+}
+
+#[test]
+fn new_and_unchanged_outputs_are_written_without_asking() {
+    let workspace = Workspace::new();
+
+    let first = workspace.generate(&["--diff"]);
+    assert!(first.status.success(), "{}", stderr(&first));
+    assert!(stdout(&first).is_empty(), "{}", stdout(&first));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+
+    let second = workspace.generate(&["--diff"]);
+    assert!(second.status.success(), "{}", stderr(&second));
+    assert!(stdout(&second).is_empty(), "{}", stdout(&second));
+    assert!(stderr(&second).contains("config.textproto is unchanged"), "{}", stderr(&second));
+}
+
+#[test]
+fn yes_requires_diff() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--yes"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--yes only applies with --diff"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
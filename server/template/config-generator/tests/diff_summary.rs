@@ -0,0 +1,48 @@
+//! Tests for `--print-diff-summary`, which prints JSON change counts against existing outputs.
+
+mod common;
+
+use common::{stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn counts_known_delta_without_values() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Dropping a field from the existing config makes it "added" by the next generation
+    let existing_config = workspace.read("config.textproto").replace("  smtp_port: 587\n", "");
+    workspace.write("config.textproto", &existing_config);
+    let existing_vault = workspace.read("secrets/secrets.textproto");
+    workspace.write(
+        ".authn",
+        &AUTHN
+            .replace("smtp.mail.test", "smtp.other.test")
+            .replace("TrailBase Test", "TrailBase Staging")
+            .replace("GOCSPX-test-client-secret", "GOCSPX-rotated-secret"),
+    );
+
+    let output = workspace.generate(&["--print-diff-summary"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary = stdout(&output);
+    assert_eq!(
+        summary.trim(),
+        r#"{"config":{"changed":2,"added":1,"removed":0},"vault":{"changed":1,"added":0,"removed":0}}"#
+    );
+    assert!(!summary.contains("smtp.other.test") && !summary.contains("GOCSPX"), "{}", summary);
+    assert_eq!(workspace.read("config.textproto"), existing_config);
+    assert_eq!(workspace.read("secrets/secrets.textproto"), existing_vault);
+}
+
+#[test]
+fn missing_outputs_count_as_added() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--print-diff-summary"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary = stdout(&output);
+    assert!(summary.contains(r#""vault":{"changed":0,"added":2,"removed":0}"#), "{}", summary);
+    assert!(!workspace.exists("config.textproto"));
+}
@@ -0,0 +1,81 @@
+//! Tests for `--dry-run`, which prints the outputs instead of writing them.
+
+mod common;
+
+use common::{stderr, stdout, Workspace};
+
+#[test]
+fn dry_run_prints_outputs_and_writes_nothing() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--dry-run"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets"));
+    let config = stdout(&output);
+    assert!(config.starts_with("# Auto-generated config.Config textproto\n"), "{}", config);
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+    let message = stderr(&output);
+    assert!(message.contains("vault that would be written to"), "{}", message);
+    assert!(message.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\""), "{}", message);
+    assert!(!message.contains("GOCSPX-test-client-secret"), "{}", message);
+    assert!(!message.contains("smtp-test-password"), "{}", message);
+}
+
+#[test]
+fn show_secrets_prints_the_vault_in_plaintext() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--dry-run", "--show-secrets"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(message.contains("value: \"GOCSPX-test-client-secret\""), "{}", message);
+    assert!(!message.contains("secrets masked"), "{}", message);
+
+    // Only a dry run prints the vault
+    let output = workspace.generate(&["--show-secrets"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--show-secrets only applies with --dry-run"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn binary_vault_is_labelled_as_shown_in_textproto() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--dry-run", "--vault-format", "binary"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(message.contains(", in binary, shown as textproto (secrets masked"), "{}", message);
+    assert!(message.contains("key: \"TRAIL_EMAIL_SMTP_PASSWORD\""), "{}", message);
+}
+
+#[test]
+fn dry_run_output_matches_a_real_run() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let written = workspace.read("config.textproto");
+
+    // The flag is accepted after the positional arguments too
+    let mut args = workspace.default_args();
+    args.push("--dry-run".to_string());
+    let output = workspace.run(&args);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), written);
+}
+
+#[test]
+fn dry_run_still_applies_checks() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--dry-run", "--forbidden-substrings", "googleusercontent"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("template leftovers"), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+}
@@ -0,0 +1,63 @@
+//! Tests for rejecting secrets that are set but blank, which would only fail at login time.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_file, GenError};
+
+#[test]
+fn empty_client_secret_is_rejected_before_writing() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret", "GOOGLE_OAUTH_CLIENT_SECRET="));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("invalid: GOOGLE_OAUTH_CLIENT_SECRET (must not be empty)"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn whitespace_only_password_counts_as_empty() {
+    let authn = AUTHN.replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD='   '");
+
+    match parse_authn_file(&authn) {
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert!(missing.is_empty(), "{:?}", missing);
+            let invalid: Vec<String> = invalid.iter().map(ToString::to_string).collect();
+            assert_eq!(invalid, ["EMAIL_SMTP_PASSWORD (must not be empty)"]);
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a blank password was accepted"),
+    }
+}
+
+#[test]
+fn named_identity_password_is_checked_too() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}EMAIL_MARKETING_SMTP_HOST=smtp.mail.test\nEMAIL_MARKETING_SMTP_PORT=587\nEMAIL_MARKETING_SMTP_USERNAME=news@mail.test\n\
+         EMAIL_MARKETING_SMTP_PASSWORD=\nEMAIL_MARKETING_SENDER_NAME=News\nEMAIL_MARKETING_SENDER_ADDRESS=news@mail.test\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("EMAIL_MARKETING_SMTP_PASSWORD (must not be empty)"), "{}", stderr(&output));
+}
+
+#[test]
+fn empty_non_secret_values_are_still_allowed() {
+    let authn = parse_authn_file(&AUTHN.replace("EMAIL_SENDER_NAME=TrailBase Test", "EMAIL_SENDER_NAME="))
+        .expect("an empty sender name is accepted");
+
+    assert_eq!(authn.email.expect("email settings").sender_name, "");
+}
+
+#[test]
+fn unused_secrets_are_not_checked() {
+    let authn = AUTHN.replace("GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret", "GOOGLE_OAUTH_CLIENT_SECRET=");
+
+    assert!(parse_authn_file(&format!("AUTH_MODE=email\n{}", authn)).is_ok());
+}
@@ -0,0 +1,61 @@
+//! Tests for `TRAIL_GEN_*` environment-variable defaults of the CLI arguments and options.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN};
+
+#[test]
+fn full_run_from_environment_only() {
+    let workspace = Workspace::new();
+    let env = [
+        ("TRAIL_GEN_TEMPLATE", path_arg(&workspace.path("config.textproto.template"))),
+        ("TRAIL_GEN_AUTHN", path_arg(&workspace.path(".authn"))),
+        ("TRAIL_GEN_CONFIG_OUTPUT", path_arg(&workspace.path("env/config.textproto"))),
+        ("TRAIL_GEN_VAULT_OUTPUT", path_arg(&workspace.path("env/secrets/secrets.textproto"))),
+    ];
+    std::fs::create_dir_all(workspace.path("env")).unwrap();
+
+    let output = workspace.run_with_env::<&str>(&[], &env);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("env/config.textproto").contains("test-client-id"));
+    assert!(workspace.read("env/secrets/secrets.textproto").contains("GOCSPX-test-client-secret"));
+}
+
+#[test]
+fn explicit_arguments_take_precedence() {
+    let workspace = Workspace::new();
+    let env = [("TRAIL_GEN_CONFIG_OUTPUT", path_arg(&workspace.path("from-env.textproto")))];
+
+    let output = workspace.run_with_env(&workspace.default_args(), &env);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+    assert!(!workspace.exists("from-env.textproto"));
+}
+
+#[test]
+fn switch_from_environment() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("noreply@mail.test", "noreply@example.com"));
+
+    let output = workspace.run(&workspace.default_args());
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let env = [("TRAIL_GEN_VERIFY_NO_TEMPLATE_LEFTOVERS", "1".to_string())];
+    let output = workspace.run_with_env(&workspace.default_args(), &env);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("template leftovers"), "{}", stderr(&output));
+
+    let output = workspace.run_with_env(&workspace.default_args(), &[("TRAIL_GEN_NO_VALIDATE", "maybe".to_string())]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("TRAIL_GEN_NO_VALIDATE"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_argument_names_its_variable() {
+    let workspace = Workspace::new();
+    let output = workspace.run(&["config.textproto.template", ".authn"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("TRAIL_GEN_CONFIG_OUTPUT"), "{}", stderr(&output));
+}
@@ -0,0 +1,60 @@
+//! Tests for taking authn keys the file doesn't set from the environment.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{generate, parse_authn_file_with_env};
+
+/// The default authn file without the lines for `key`
+fn authn_without(key: &str) -> String {
+    AUTHN.lines().filter(|line| !line.starts_with(&format!("{}=", key))).map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn process_environment_fills_a_missing_secret() {
+    // The only test in this binary touching the process environment, so nothing races with it
+    std::env::set_var("EMAIL_SMTP_PASSWORD", "smtp-password-from-env");
+
+    let authn = parse_authn_file_with_env(&authn_without("EMAIL_SMTP_PASSWORD"), std::env::vars()).expect("env supplies the password");
+    let output = generate(common::TEMPLATE, &authn).expect("generation succeeds");
+
+    std::env::remove_var("EMAIL_SMTP_PASSWORD");
+    assert!(output.vault.contains("value: \"smtp-password-from-env\""), "{}", output.vault);
+    assert!(!output.config.contains("smtp-password-from-env"), "{}", output.config);
+}
+
+#[test]
+fn environment_fallback_reaches_the_vault_file() {
+    let workspace = Workspace::with_authn(&authn_without("GOOGLE_OAUTH_CLIENT_SECRET"));
+
+    let output = workspace.run_with_env(&workspace.default_args(), &[("GOOGLE_OAUTH_CLIENT_SECRET", "GOCSPX-from-env".to_string())]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"GOCSPX-from-env\""));
+}
+
+#[test]
+fn file_takes_precedence_over_environment() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run_with_env(&workspace.default_args(), &[("EMAIL_SMTP_HOST", "smtp.from-env.test".to_string())]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(!config.contains("from-env"), "{}", config);
+}
+
+#[test]
+fn unrelated_variables_are_ignored() {
+    let env = [("GOOGLE_OAUTH_CLIENT_SECRET", "GOCSPX-irrelevant".to_string()), ("EMAIL_EXTRA", "x".to_string())];
+
+    let authn = parse_authn_file_with_env(
+        &format!("AUTH_MODE=email\n{}", authn_without("GOOGLE_OAUTH_CLIENT_ID")),
+        env.iter().map(|(key, value)| (key.to_string(), value.clone())),
+    )
+    .expect("email-only authn file parses");
+
+    assert!(authn.oauth_providers.is_empty());
+    assert!(!authn.keys.contains("EMAIL_EXTRA"));
+}
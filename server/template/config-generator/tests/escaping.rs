@@ -0,0 +1,61 @@
+//! Tests for escaping authn values that contain textproto special characters.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{escape_textproto_string, to_canonical_text, Schema};
+use prost_reflect::{DynamicMessage, Value};
+
+#[test]
+fn quoted_sender_name_is_escaped() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SENDER_NAME=TrailBase Test", "EMAIL_SENDER_NAME=O'Brien \"The Great\""));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains(r#"sender_name: "O\'Brien \"The Great\"""#), "{}", config);
+    assert!(config.contains("sender_address: \"noreply@mail.test\""), "{}", config);
+}
+
+#[test]
+fn password_with_backslash_is_escaped() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("smtp-test-password", r"smtp\test\password"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains(r#"value: "smtp\\test\\password""#), "{}", vault);
+    assert!(workspace.read("config.textproto").contains("smtp_password: \"<REDACTED>\""));
+}
+
+#[test]
+fn escapes_follow_the_text_format() {
+    assert_eq!(escape_textproto_string("plain value"), "plain value");
+    assert_eq!(escape_textproto_string("a\"b'c\\d"), r#"a\"b\'c\\d"#);
+    assert_eq!(escape_textproto_string("line\nbreak\ttab\r"), r"line\nbreak\ttab\r");
+    assert_eq!(escape_textproto_string("bell\u{7}"), r"bell\007");
+    assert_eq!(escape_textproto_string("Café"), "Café");
+}
+
+/// The helper's escapes agree with the formatter that writes the config, and both parse back to the
+/// original value. The formatter also writes non-ASCII as octal UTF-8 bytes, which the helper keeps.
+#[test]
+fn escapes_match_the_config_formatter() {
+    let schema = Schema::load(None).expect("embedded schema");
+    for value in ["plain value", "a\"b'c\\d", "line\nbreak\ttab\r", "bell\u{7}", "Café"] {
+        let mut email = DynamicMessage::new(schema.email.clone());
+        email.set_field_by_name("sender_name", Value::String(value.to_string()));
+        let formatted = to_canonical_text(&email);
+        let escaped = format!("sender_name: \"{}\"", escape_textproto_string(value));
+
+        if value.is_ascii() {
+            assert_eq!(formatted.trim_end(), escaped, "{:?}", value);
+        }
+        for text in [formatted.as_str(), escaped.as_str()] {
+            let parsed = DynamicMessage::parse_text_format(schema.email.clone(), text).expect("escaped text parses");
+            assert_eq!(parsed.get_field_by_name("sender_name").as_deref(), Some(&Value::String(value.to_string())), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Tests for the exit codes that tell failures apart, and for `--quiet`.
+
+mod common;
+
+use common::{path_arg, stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn success_is_zero_and_quiet_prints_nothing() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--quiet"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).is_empty(), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    assert!(workspace.exists("config.textproto"));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn quiet_still_prints_errors_and_warnings() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=5870"));
+
+    let output = workspace.generate(&["--quiet"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Warning: EMAIL_SMTP_PORT=5870 is not a common SMTP port"), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Successfully"), "{}", stderr(&output));
+
+    workspace.write(".authn", &AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test\n", ""));
+    let output = workspace.generate(&["--quiet"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("missing: EMAIL_SMTP_HOST"), "{}", stderr(&output));
+}
+
+#[test]
+fn differences_found_are_one() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--check"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+}
+
+#[test]
+fn bad_arguments_are_two() {
+    let workspace = Workspace::new();
+
+    for args in [&["--no-such-option"][..], &["--quiet", "--verbose"], &["--yes"]] {
+        let output = workspace.generate(args);
+
+        assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr(&output));
+    }
+    let output = workspace.generate(&["--quiet", "--verbose"]);
+    assert!(stderr(&output).contains("--quiet cannot be combined with --verbose"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn invalid_input_and_failed_checks_are_three() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=port"));
+    assert_eq!(workspace.generate(&[]).status.code(), Some(3));
+
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.template", "server { no_such_field: 1 }\n");
+    assert_eq!(workspace.generate(&[]).status.code(), Some(3));
+
+    let workspace = Workspace::new();
+    let output = workspace.generate(&["--forbidden-substrings", "TrailBase"]);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("template leftovers"), "{}", stderr(&output));
+}
+
+#[test]
+fn unreadable_inputs_and_unwritable_outputs_are_four() {
+    let workspace = Workspace::new();
+    let missing = path_arg(&workspace.path("missing.template"));
+    let output = workspace.run(&[
+        &missing,
+        &path_arg(&workspace.path(".authn")),
+        &path_arg(&workspace.path("config.textproto")),
+        &path_arg(&workspace.path("secrets/secrets.textproto")),
+    ]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stderr(&output).contains("failed to read template file"), "{}", stderr(&output));
+
+    let output = workspace.generate(&["--vault-key-map", &path_arg(&workspace.path("missing.map"))]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stderr(&output).contains("failed to read vault key map"), "{}", stderr(&output));
+
+    for flag in ["--compare-config", "--config-patch"] {
+        let output = workspace.generate(&[flag, &path_arg(&workspace.path("missing.textproto"))]);
+        assert_eq!(output.status.code(), Some(4), "{}: {}", flag, stderr(&output));
+        assert!(stderr(&output).contains("failed to read config file"), "{}", stderr(&output));
+    }
+    
+    // A sidecar that exists but can't be read as a file
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.checksum/placeholder", "");
+    let output = workspace.generate(&["--checksum-guard"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stderr(&output).contains("failed to read checksum file"), "{}", stderr(&output));
+    
+    // A directory where the config should go can't be replaced by a file
+    let workspace = Workspace::new();
+    workspace.write("config.textproto/placeholder", "");
+    let output = workspace.generate(&[]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[test]
+fn failed_pre_hook_is_five() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--pre-hook", "exit 7"]);
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(stderr(&output).contains("aborting generation"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
@@ -0,0 +1,67 @@
+//! Tests for `--generate-template`, which prints a commented authn file with every recognized key.
+
+mod common;
+
+use common::{stderr, stdout, Workspace};
+use config_generator::{authn_file_template, parse_authn_file, GenError, NO_PROVIDER_KEY};
+
+#[test]
+fn prints_the_template_to_stdout() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&["--generate-template"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), authn_file_template());
+    for key in ["# AUTH_MODE=both", "GOOGLE_OAUTH_CLIENT_ID=", "EMAIL_SMTP_HOST=", "# EMAIL_SMTP_SECURITY=starttls"] {
+        assert!(stdout(&output).contains(key), "{} missing from:\n{}", key, stdout(&output));
+    }
+}
+
+#[test]
+fn template_is_a_complete_authn_file() {
+    let workspace = Workspace::with_authn(&authn_file_template());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("smtp_host: \"smtp.example.com\""));
+}
+
+#[test]
+fn every_listed_key_is_required_by_the_parser() {
+    let template = authn_file_template();
+    let keys: Vec<&str> = template
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('=').map(|(key, _)| key))
+        .collect();
+    assert!(!keys.is_empty());
+
+    for key in keys {
+        let prefix = format!("{}=", key);
+        let without: Vec<&str> = template.lines().filter(|line| !line.starts_with(&prefix)).collect();
+        match parse_authn_file(&without.join("\n")) {
+            Err(GenError::Authn { missing, .. }) => {
+                // Without its client ID a provider is ignored, so the stand-in key is reported instead
+                assert!(
+                    missing.iter().any(|missing| missing == key || (key.ends_with("_OAUTH_CLIENT_ID") && missing == NO_PROVIDER_KEY)),
+                    "{}: {:?}",
+                    key,
+                    missing
+                );
+            }
+            other => panic!("{}: expected missing keys, got {:?}", key, other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn other_arguments_are_rejected() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--generate-template"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--generate-template takes no other arguments"), "{}", stderr(&output));
+}
@@ -0,0 +1,55 @@
+//! Tests for `#` in authn values: inline comments, base64 padding and quoted values.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::parse_authn_file;
+
+/// The default authn file with `key`'s line replaced by `line`
+fn authn_with_line(key: &str, line: &str) -> String {
+    AUTHN
+        .lines()
+        .map(|existing| if existing.starts_with(&format!("{}=", key)) { line } else { existing })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn password(line: &str) -> String {
+    let authn = parse_authn_file(&authn_with_line("EMAIL_SMTP_PASSWORD", line)).expect("authn file parses");
+    authn.email.expect("email settings").smtp_password
+}
+
+#[test]
+fn base64_padding_is_kept() {
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=c2VjcmV0LXZhbHVl=="), "c2VjcmV0LXZhbHVl==");
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=c2VjcmV0=  # padded"), "c2VjcmV0=");
+}
+
+#[test]
+fn hash_inside_a_value_is_kept() {
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=pa#ss#word"), "pa#ss#word");
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=#leading"), "#leading");
+}
+
+#[test]
+fn hash_after_whitespace_starts_a_comment() {
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=smtp-test-password # rotated monthly"), "smtp-test-password");
+    assert_eq!(password("EMAIL_SMTP_PASSWORD=smtp-test-password\t#tab"), "smtp-test-password");
+}
+
+#[test]
+fn single_quotes_keep_hash_and_spaces_exactly() {
+    assert_eq!(password("EMAIL_SMTP_PASSWORD='  p # w  ' # a comment after the quote"), "  p # w  ");
+}
+
+#[test]
+fn text_after_the_closing_quote_is_an_error() {
+    let workspace = Workspace::with_authn(&authn_with_line("EMAIL_SMTP_PASSWORD", "EMAIL_SMTP_PASSWORD='quoted' trailing"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("EMAIL_SMTP_PASSWORD (unexpected text after the closing quote)"), "{}", message);
+    assert!(!message.contains("trailing"), "{}", message);
+}
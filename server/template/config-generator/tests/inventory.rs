@@ -0,0 +1,98 @@
+//! Tests for `--inventory`, which writes a JSON credentials inventory without secret values.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+use prost::Message;
+use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+use prost_reflect::prost_types::{FieldDescriptorProto, FileDescriptorSet};
+
+/// The descriptor set embedded in the binary
+const EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+/// Write the embedded descriptor set with an `EmailConfig marketing_email = 90` field added to
+/// `Config`, returning its path
+fn write_schema_with_marketing_email(workspace: &Workspace) -> String {
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let file = set.file.iter_mut().find(|file| file.message_type.iter().any(|message| message.name() == "Config")).unwrap();
+    let config = file.message_type.iter_mut().find(|message| message.name() == "Config").unwrap();
+    let mut field = FieldDescriptorProto {
+        name: Some("marketing_email".to_string()),
+        number: Some(90),
+        type_name: Some(".config.EmailConfig".to_string()),
+        ..Default::default()
+    };
+    field.set_label(Label::Optional);
+    field.set_type(Type::Message);
+    config.field.push(field);
+
+    let path = workspace.path("descriptors.bin");
+    std::fs::write(&path, set.encode_to_vec()).unwrap();
+    path_arg(&path)
+}
+
+#[test]
+fn inventory_lists_client_id_and_masks_secrets() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--inventory", "inventory.json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let inventory = workspace.read("inventory.json");
+    assert_eq!(
+        inventory,
+        r#"{
+  "oauth_providers": [
+    {"name": "google", "provider_id": "GOOGLE", "client_id": "test-client-id.apps.googleusercontent.com"}
+  ],
+  "email": {"smtp_host": "smtp.mail.test", "smtp_port": 587, "smtp_username": "mailer@mail.test", "sender_name": "TrailBase Test", "sender_address": "noreply@mail.test"},
+  "named_emails": {},
+  "vault_secrets": ["TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET", "TRAIL_EMAIL_SMTP_PASSWORD"]
+}
+"#
+    );
+    assert!(!inventory.contains("GOCSPX-test-client-secret"));
+    assert!(!inventory.contains("smtp-test-password"));
+    assert!(!inventory.contains("<REDACTED>"));
+}
+
+#[test]
+fn inventory_follows_auth_mode() {
+    let authn: String = AUTHN.lines().filter(|line| !line.starts_with("EMAIL_")).map(|line| format!("{}\n", line)).collect();
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=oauth\n{}", authn));
+
+    let output = workspace.generate(&["--inventory", "inventory.json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let inventory = workspace.read("inventory.json");
+    assert!(inventory.contains("\"email\": null"), "{}", inventory);
+    assert!(inventory.contains("\"vault_secrets\": [\"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\"]"), "{}", inventory);
+}
+
+#[test]
+fn inventory_lists_named_email_identities() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}EMAIL_MARKETING_SMTP_HOST=smtp.news.test\nEMAIL_MARKETING_SMTP_PORT=2525\nEMAIL_MARKETING_SMTP_USERNAME=news@news.test\n\
+         EMAIL_MARKETING_SMTP_PASSWORD=marketing-test-password\nEMAIL_MARKETING_SENDER_NAME=TrailBase News\n\
+         EMAIL_MARKETING_SENDER_ADDRESS=news@news.test\n",
+        AUTHN
+    ));
+    workspace.write("config.textproto.template", &format!("{}marketing_email {{}}\n", TEMPLATE));
+    let schema = write_schema_with_marketing_email(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema, "--inventory", "inventory.json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let inventory = workspace.read("inventory.json");
+    assert!(inventory.contains("\"email\": {\"smtp_host\": \"smtp.mail.test\""), "{}", inventory);
+    assert!(
+        inventory.contains(
+            "\"named_emails\": {\n    \"marketing\": {\"smtp_host\": \"smtp.news.test\", \"smtp_port\": 2525, \"smtp_username\": \"news@news.test\", \
+             \"sender_name\": \"TrailBase News\", \"sender_address\": \"news@news.test\"}\n  },\n"
+        ),
+        "{}",
+        inventory
+    );
+    assert!(inventory.contains("\"TRAIL_EMAIL_MARKETING_SMTP_PASSWORD\""), "{}", inventory);
+    assert!(!inventory.contains("marketing-test-password"), "{}", inventory);
+}
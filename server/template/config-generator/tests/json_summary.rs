@@ -0,0 +1,80 @@
+//! Tests for `--format json`, which prints a summary of a successful run to stdout.
+
+mod common;
+
+use common::{path_arg, stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn summary_lists_providers_vault_keys_and_outputs() {
+    let workspace = Workspace::new();
+    let config_path = path_arg(&workspace.path("config.textproto"));
+    let vault_path = path_arg(&workspace.path("secrets/secrets.textproto"));
+
+    let output = workspace.generate(&["--format", "json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{{\"providers\":[\"google\"],\"defaults\":[\"AUTH_MODE=both\",\"EMAIL_SMTP_SECURITY=starttls\"],\"vault_keys\":[\"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\",\"TRAIL_EMAIL_SMTP_PASSWORD\"],\"outputs\":[{{\"name\":\"config\",\"path\":{:?},\"written\":true,\"changed\":true}},{{\"name\":\"vault\",\"path\":{:?},\"written\":true,\"changed\":true}}]}}\n",
+            config_path, vault_path
+        )
+    );
+    // The human-readable messages stay on stderr
+    assert!(stderr(&output).contains("Successfully generated config file"), "{}", stderr(&output));
+}
+
+#[test]
+fn unchanged_and_changed_outputs_are_told_apart() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(".authn", &AUTHN.replace("smtp-test-password", "rotated-smtp-password"));
+
+    let output = workspace.generate(&["--format", "json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary = stdout(&output);
+    assert!(summary.contains("{\"name\":\"config\",") && summary.contains("\"written\":true,\"changed\":false}"), "{}", summary);
+    assert!(summary.contains("secrets.textproto\",\"written\":true,\"changed\":true}"), "{}", summary);
+}
+
+#[test]
+fn secret_values_never_appear() {
+    let workspace = Workspace::new();
+    let inventory = path_arg(&workspace.path("inventory.json"));
+
+    let output = workspace.generate(&["--format", "json", "--inventory", &inventory]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary = stdout(&output);
+    assert!(summary.contains("{\"name\":\"inventory\","), "{}", summary);
+    for secret in ["GOCSPX-test-client-secret", "smtp-test-password"] {
+        assert!(!summary.contains(secret), "{}", summary);
+    }
+}
+
+#[test]
+fn modes_that_print_to_stdout_are_rejected() {
+    let workspace = Workspace::new();
+
+    for extra in [&["--dry-run"][..], &["--check"][..]] {
+        let mut args = vec!["--format", "json"];
+        args.extend(extra);
+        let output = workspace.generate(&args);
+
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains(&format!("--format json cannot be combined with {}", extra[0])),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn unknown_format_is_rejected() {
+    let output = Workspace::new().generate(&["--format", "yaml"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--format must be 'human' or 'json', got 'yaml'"), "{}", stderr(&output));
+}
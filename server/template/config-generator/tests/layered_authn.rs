@@ -0,0 +1,105 @@
+//! Tests for layering several authn files, given as a comma-separated `<authn-file>` list.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_layers, parse_authn_layers_with, AuthnFormat, MergeStrategy};
+
+const OVERRIDES: &str = "\
+EMAIL_SENDER_NAME=Production
+GOOGLE_OAUTH_CLIENT_SECRET=prod-client-secret
+";
+
+/// Run with the default template and outputs and `authn` as the authn-file argument
+fn generate_with_authn(workspace: &Workspace, authn: &str) -> std::process::Output {
+    workspace.run(&["config.textproto.template", authn, "config.textproto", "secrets/secrets.textproto"])
+}
+
+#[test]
+fn later_files_override_earlier_ones_key_by_key() {
+    let workspace = Workspace::new();
+    workspace.write("prod.authn", OVERRIDES);
+
+    let output = generate_with_authn(&workspace, ".authn,prod.authn");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("sender_name: \"Production\""), "{}", config);
+    // Keys only the base file sets are kept
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("value: \"prod-client-secret\""), "{}", vault);
+    assert!(!vault.contains("GOCSPX-test-client-secret"), "{}", vault);
+    assert!(vault.contains("value: \"smtp-test-password\""), "{}", vault);
+}
+
+#[test]
+fn required_key_may_come_from_the_override() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PASSWORD=smtp-test-password\n", ""));
+    let alone = generate_with_authn(&workspace, ".authn");
+    assert!(!alone.status.success());
+    assert!(stderr(&alone).contains("EMAIL_SMTP_PASSWORD"), "{}", stderr(&alone));
+
+    workspace.write("secrets.yaml", "email:\n  smtp_password: 'layered-password'\n");
+    let output = generate_with_authn(&workspace, ".authn,secrets.yaml");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"layered-password\""));
+}
+
+#[test]
+fn override_replaces_an_invalid_earlier_value() {
+    let authn = parse_authn_layers(
+        &[("base", &format!("{}AUTH_MODE=sometimes\n", AUTHN), AuthnFormat::KeyValue), ("override", "AUTH_MODE=email\n", AuthnFormat::KeyValue)],
+        std::iter::empty(),
+    )
+    .expect("the override's AUTH_MODE is valid");
+
+    assert!(authn.oauth_providers.is_empty());
+    assert_eq!(authn.email.expect("email settings").sender_name, "TrailBase Test");
+}
+
+#[test]
+fn syntax_error_names_the_layer() {
+    let workspace = Workspace::new();
+    workspace.write("prod.json", "{\"auth_mode\": }");
+
+    let output = generate_with_authn(&workspace, ".authn,prod.json");
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("invalid JSON authn file 'prod.json' at line 1, column"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn merge_strategy_decides_whether_scopes_replace_or_extend() {
+    let base = format!("{}GOOGLE_OAUTH_SCOPES=openid,email\n", AUTHN);
+    let layers = [
+        ("base", base.as_str(), AuthnFormat::KeyValue),
+        ("override", "GOOGLE_OAUTH_SCOPES=email, profile\nEMAIL_SENDER_NAME=Production\n", AuthnFormat::KeyValue),
+    ];
+    let scopes = |strategy| {
+        let authn = parse_authn_layers_with(&layers, strategy, std::iter::empty()).expect("layers parse");
+        // Scalars override under either strategy
+        assert_eq!(authn.email.expect("email settings").sender_name, "Production");
+        authn.oauth_providers[0].scopes.clone().expect("scopes are set")
+    };
+
+    assert_eq!(scopes(MergeStrategy::Override), ["email", "profile"]);
+    assert_eq!(scopes(MergeStrategy::Append), ["openid", "email", "profile"]);
+}
+
+#[test]
+fn merge_strategy_flag_selects_append() {
+    let workspace = Workspace::with_authn(&format!("{}GOOGLE_OAUTH_SCOPES=openid\n", AUTHN));
+    workspace.write("prod.yaml", "oauth_providers:\n  google:\n    scopes: email\n");
+    let authn = ".authn,prod.yaml";
+
+    let output = workspace.run(&["--validate-only", "--merge-strategy", "append", authn]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    let output = workspace.run(&["--validate-only", "--merge-strategy", "union", authn]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--merge-strategy must be 'override' or 'append', got 'union'"), "{}", stderr(&output));
+}
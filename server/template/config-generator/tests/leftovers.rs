@@ -0,0 +1,38 @@
+//! Tests for `--verify-no-template-leftovers` and `--forbidden-substrings`.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+fn example_host_authn() -> String {
+    AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test", "EMAIL_SMTP_HOST=smtp.example.com")
+}
+
+#[test]
+fn leftover_example_value_fails_generation() {
+    let workspace = Workspace::with_authn(&example_host_authn());
+    let output = workspace.generate(&["--verify-no-template-leftovers"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("'example.com'"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn check_is_off_by_default() {
+    let workspace = Workspace::with_authn(&example_host_authn());
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn substring_list_can_be_overridden() {
+    let workspace = Workspace::with_authn(&example_host_authn());
+
+    let output = workspace.generate(&["--forbidden-substrings", "TODO,FIXME"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = workspace.generate(&["--forbidden-substrings", "mail.test"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("'mail.test'"), "{}", stderr(&output));
+}
@@ -0,0 +1,144 @@
+//! Tests for calling the generator as a library, without the binary.
+
+mod common;
+
+use common::{AUTHN, TEMPLATE};
+use config_generator::{generate, parse_authn_file, parse_vault, GenError};
+
+#[test]
+fn generate_returns_both_outputs() {
+    let authn = parse_authn_file(AUTHN).expect("complete authn file parses");
+
+    let output = generate(TEMPLATE, &authn).expect("generation succeeds");
+
+    assert!(output.config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", output.config);
+    assert!(output.config.contains("smtp_password: \"<REDACTED>\""), "{}", output.config);
+    assert!(output.vault.starts_with("# Auto-generated config.Vault textproto\n"), "{}", output.vault);
+    assert_eq!(
+        output.secrets.get("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET").map(String::as_str),
+        Some("GOCSPX-test-client-secret")
+    );
+    assert_eq!(output.secrets.get("TRAIL_EMAIL_SMTP_PASSWORD").map(String::as_str), Some("smtp-test-password"));
+}
+
+#[test]
+fn missing_key_is_an_error_not_an_exit() {
+    let authn: String = AUTHN.lines().filter(|line| !line.starts_with("EMAIL_SENDER_NAME")).map(|line| format!("{}\n", line)).collect();
+
+    match parse_authn_file(&authn) {
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert_eq!(missing, ["EMAIL_SENDER_NAME"]);
+            assert!(invalid.is_empty());
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("incomplete authn file parsed"),
+    }
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let authn: String = AUTHN
+        .replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=abc")
+        .lines()
+        .filter(|line| !line.starts_with("GOOGLE_OAUTH_CLIENT_SECRET") && !line.starts_with("EMAIL_SENDER_NAME"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    let error = parse_authn_file(&authn).err().expect("partially-filled authn file is rejected");
+
+    assert_eq!(
+        error.to_string(),
+        "invalid authn file: missing: GOOGLE_OAUTH_CLIENT_SECRET, EMAIL_SENDER_NAME (required for AUTH_MODE=both; \
+         OAuth provider 'google' is incomplete); invalid: EMAIL_SMTP_PORT='abc' (not a port number)"
+    );
+}
+
+#[test]
+fn malformed_secret_is_reported_without_its_value() {
+    let authn = format!("AUTH_MODE=sso\n{}", AUTHN.replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD='smtp-test-password"));
+
+    match parse_authn_file(&authn) {
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert!(missing.is_empty(), "{:?}", missing);
+            let invalid: Vec<String> = invalid.iter().map(ToString::to_string).collect();
+            assert_eq!(
+                invalid,
+                ["AUTH_MODE='sso' (must be one of email, oauth, both)", "EMAIL_SMTP_PASSWORD (unterminated single quote)"]
+            );
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("malformed authn file parsed"),
+    }
+}
+
+#[test]
+fn invalid_template_is_a_template_error() {
+    let authn = parse_authn_file(AUTHN).expect("complete authn file parses");
+
+    match generate("auth { no_such_field: 1 }", &authn) {
+        Err(GenError::Template(message)) => assert!(message.contains("no_such_field"), "{}", message),
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("invalid template generated"),
+    }
+}
+
+#[test]
+fn generated_vault_parses_back_to_its_secrets() {
+    let secret = r#"GOCSPX-"quoted"\back\slash'"#;
+    let authn = parse_authn_file(&AUTHN.replace("GOCSPX-test-client-secret", secret)).expect("authn file parses");
+    let output = generate(TEMPLATE, &authn).expect("generation succeeds");
+
+    // The preface comment line is skipped
+    assert!(output.vault.starts_with("# Auto-generated config.Vault textproto\n"), "{}", output.vault);
+    let vault = parse_vault(&output.vault).expect("generated vault parses");
+
+    assert_eq!(vault.secrets.into_iter().collect::<std::collections::BTreeMap<_, _>>(), output.secrets);
+    assert_eq!(output.secrets.get("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET").map(String::as_str), Some(secret));
+}
+
+#[test]
+fn malformed_vault_is_a_vault_error() {
+    match parse_vault("secrets { key: \"TRAIL_EMAIL_SMTP_PASSWORD\" value: unquoted-secret-value }") {
+        Err(GenError::Vault(message)) => {
+            assert!(message.contains("not a valid config.Vault message"), "{}", message);
+            assert!(!message.contains("unquoted-secret-value"), "{}", message);
+        }
+        other => panic!("expected a vault error, got {:?}", other.map(|vault| vault.secrets)),
+    }
+}
+
+#[test]
+fn result_describes_a_two_provider_run() {
+    let block = "{\n    key: \"github\"\n    value {\n      client_id: \"<GITHUB_OAUTH_CLIENT_ID>\"\n      client_secret: \"<REDACTED>\"\n      provider_id: GITHUB\n    }\n  }";
+    let template = TEMPLATE.replacen("  }]\n}", &format!("  }}, {}]\n}}", block), 1);
+    let authn = parse_authn_file(&format!(
+        "{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-client-secret\nEMAIL_SMTP_SECURITY=starttls\n",
+        AUTHN
+    ))
+    .expect("authn file parses");
+
+    let output = generate(&template, &authn).expect("generation succeeds");
+
+    assert_eq!(output.providers, ["github", "google"]);
+    // The security mode is set explicitly, so only AUTH_MODE took its default
+    assert_eq!(output.defaults, ["AUTH_MODE=both"]);
+    assert_eq!(
+        output.vault_keys(),
+        [
+            "TRAIL_AUTH_OAUTH_PROVIDERS_GITHUB_CLIENT_SECRET",
+            "TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET",
+            "TRAIL_EMAIL_SMTP_PASSWORD"
+        ]
+    );
+    assert_eq!(output.secrets.get("TRAIL_AUTH_OAUTH_PROVIDERS_GITHUB_CLIENT_SECRET").map(String::as_str), Some("gh-client-secret"));
+    assert!(output.warnings.is_empty(), "{:?}", output.warnings);
+}
+
+#[test]
+fn defaults_cover_the_port_based_smtp_security() {
+    let authn = parse_authn_file(&format!("AUTH_MODE=both\n{}", AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=465"))).expect("authn file parses");
+
+    let output = generate(TEMPLATE, &authn).expect("generation succeeds");
+
+    assert_eq!(output.defaults, ["EMAIL_SMTP_SECURITY=tls"]);
+}
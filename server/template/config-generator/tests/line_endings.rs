@@ -0,0 +1,58 @@
+//! Tests that authn files saved with Windows or classic Mac OS line endings leave no `\r` in values.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_as, parse_authn_file, AuthnFormat};
+
+#[test]
+fn crlf_authn_file_generates_clean_values() {
+    let workspace = Workspace::with_authn(&AUTHN.replace('\n', "\r\n"));
+
+    let output = workspace.generate(&["--normalize-secrets"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\"\n"), "{}", config);
+    assert!(config.contains("sender_name: \"TrailBase Test\"\n"), "{}", config);
+    assert!(vault.contains("value: \"GOCSPX-test-client-secret\"\n"), "{}", vault);
+    assert!(!config.contains("\\r") && !vault.contains("\\r"), "{}{}", config, vault);
+    // A line ending is not whitespace copied into a secret
+    assert!(!stderr(&output).contains("Warning:"), "{}", stderr(&output));
+}
+
+#[test]
+fn lone_carriage_returns_end_lines() {
+    let authn = parse_authn_file(&AUTHN.replace('\n', "\r")).expect("authn file with CR line endings parses");
+
+    assert_eq!(authn.oauth_providers[0].client_id, "test-client-id.apps.googleusercontent.com");
+    assert_eq!(authn.oauth_providers[0].client_secret.as_deref(), Some("GOCSPX-test-client-secret"));
+    let email = authn.email.expect("email settings");
+    assert_eq!(email.smtp_host, "smtp.mail.test");
+    assert_eq!(email.sender_address, "noreply@mail.test");
+}
+
+#[test]
+fn value_file_line_endings_are_normalized() {
+    let workspace = Workspace::new();
+    workspace.write("run/smtp-password", "first line\r\nsecond line\r\n");
+    let reference = format!("@{}", path_arg(&workspace.path("run/smtp-password")));
+    workspace.write(".authn", &AUTHN.replace("smtp-test-password", &reference).replace('\n', "\r\n"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("value: \"first line\\nsecond line\"\n"), "{}", vault);
+}
+
+#[test]
+fn crlf_yaml_file_parses() {
+    let yaml = "oauth_providers:\r\n  google:\r\n    client_id: test-client-id\r\n    client_secret: \"test-client-secret\"\r\nauth_mode: oauth\r\n";
+
+    let authn = parse_authn_as(yaml, AuthnFormat::Yaml, std::iter::empty()).expect("authn file parses");
+
+    assert_eq!(authn.oauth_providers[0].client_id, "test-client-id");
+    assert_eq!(authn.oauth_providers[0].client_secret.as_deref(), Some("test-client-secret"));
+}
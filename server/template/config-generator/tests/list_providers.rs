@@ -0,0 +1,54 @@
+//! Tests for `--list-providers`, which prints the schema's OAuth providers and their authn keys.
+
+mod common;
+
+use common::{stderr, stdout, Workspace};
+use config_generator::{provider_listing, Schema, DEFAULT_VAULT_KEY_TEMPLATE};
+
+#[test]
+fn google_and_github_appear_with_their_keys() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&["--list-providers"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let listing = stdout(&output);
+    for provider in ["GOOGLE", "GITHUB"] {
+        assert!(listing.contains(&format!("\n{}\n", provider)), "{} missing from:\n{}", provider, listing);
+        for expected in [
+            format!("  {}_OAUTH_CLIENT_ID (required)\n", provider),
+            format!(
+                "  {}_OAUTH_CLIENT_SECRET (required unless PKCE); written to the vault as TRAIL_AUTH_OAUTH_PROVIDERS_{}_CLIENT_SECRET\n",
+                provider, provider
+            ),
+            format!("  {}_OAUTH_SCOPES (optional)\n", provider),
+            format!("  {}_OAUTH_REDIRECT_URL (optional)\n", provider),
+        ] {
+            assert!(listing.contains(&expected), "{:?} missing from:\n{}", expected, listing);
+        }
+    }
+    // The enum's unset value is not a provider
+    assert!(!listing.contains("UNDEFINED"), "{}", listing);
+    let schema = Schema::load(None).expect("embedded schema");
+    assert_eq!(listing, provider_listing(&schema, DEFAULT_VAULT_KEY_TEMPLATE).unwrap());
+}
+
+#[test]
+fn vault_key_template_names_the_secret() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&["--list-providers", "--vault-key-template", "OAUTH_{PROVIDER}_SECRET"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GITHUB_OAUTH_CLIENT_SECRET (required unless PKCE); written to the vault as OAUTH_GITHUB_SECRET\n"));
+}
+
+#[test]
+fn takes_no_other_arguments() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--list-providers"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--list-providers takes no other arguments"), "{}", stderr(&output));
+}
@@ -0,0 +1,106 @@
+//! Tests for `--merge`, which updates an existing config instead of regenerating it from the template.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate, merge_with, parse_authn_file, GenerateOptions, Schema};
+
+/// A generated workspace whose config was then tuned by hand
+fn hand_tuned_workspace() -> Workspace {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(
+        "config.textproto",
+        &workspace
+            .read("config.textproto")
+            .replace("application_name: \"TrailBase\"", "application_name: \"Tuned\"\n    backup_interval_sec: 86400"),
+    );
+    workspace
+}
+
+#[test]
+fn unrelated_fields_survive_and_authn_fields_are_updated() {
+    let workspace = hand_tuned_workspace();
+    workspace.write(
+        ".authn",
+        &AUTHN
+            .replace("test-client-id.apps", "rotated-client-id.apps")
+            .replace("EMAIL_SMTP_HOST=smtp.mail.test", "EMAIL_SMTP_HOST=relay.mail.test")
+            .replace("GOCSPX-test-client-secret", "GOCSPX-rotated-secret"),
+    );
+
+    let output = workspace.generate(&["--merge"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("backup_interval_sec: 86400"), "{}", config);
+    assert!(config.contains("application_name: \"Tuned\""), "{}", config);
+    assert!(config.contains("client_id: \"rotated-client-id.apps.googleusercontent.com\""), "{}", config);
+    assert!(config.contains("smtp_host: \"relay.mail.test\""), "{}", config);
+    assert!(!config.contains("GOCSPX"), "{}", config);
+    assert!(workspace.read("secrets/secrets.textproto").contains("GOCSPX-rotated-secret"));
+}
+
+#[test]
+fn template_is_used_when_there_is_no_config_yet() {
+    let merged = Workspace::new();
+    let generated = Workspace::new();
+
+    let output = merged.generate(&["--merge"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(generated.generate(&[]).status.success());
+    assert_eq!(merged.read("config.textproto"), generated.read("config.textproto"));
+}
+
+#[test]
+fn merging_twice_changes_nothing() {
+    let workspace = hand_tuned_workspace();
+    assert!(workspace.generate(&["--merge"]).status.success());
+    let once = workspace.read("config.textproto");
+
+    assert!(workspace.generate(&["--merge"]).status.success());
+
+    assert_eq!(workspace.read("config.textproto"), once);
+}
+
+#[test]
+fn checksum_guard_allows_merging_into_an_edited_config() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&["--checksum-guard"]).status.success());
+    workspace.write(
+        "config.textproto",
+        &workspace.read("config.textproto").replace("application_name: \"TrailBase\"", "application_name: \"Tuned\""),
+    );
+
+    let output = workspace.generate(&["--merge", "--checksum-guard"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("application_name: \"Tuned\""));
+}
+
+#[test]
+fn invalid_existing_config_is_reported() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "server { no_such_field: 1 }\n");
+
+    let output = workspace.generate(&["--merge"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("cannot merge into '"), "{}", message);
+    assert!(message.contains("existing config is not a valid config.Config message"), "{}", message);
+    assert_eq!(workspace.read("config.textproto"), "server { no_such_field: 1 }\n");
+}
+
+#[test]
+fn library_merge_keeps_blocks_auth_mode_does_not_use() {
+    let existing = generate(TEMPLATE, &parse_authn_file(AUTHN).expect("authn file parses")).expect("generation succeeds").config;
+    let oauth_only = parse_authn_file(&format!("AUTH_MODE=oauth\n{}", AUTHN)).expect("authn file parses");
+
+    let output = merge_with(&Schema::load(None).expect("embedded schema"), &existing, &oauth_only, &GenerateOptions::default())
+        .expect("merge succeeds");
+
+    assert_eq!(output.config, existing);
+    assert!(!output.secrets.contains_key("TRAIL_EMAIL_SMTP_PASSWORD"), "{:?}", output.secrets.keys());
+}
@@ -0,0 +1,88 @@
+//! Tests for `--merge-vault`, which keeps secrets in the existing vault that the generator doesn't manage.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{merge_vault_with, parse_authn_file, GenerateOptions, Schema};
+
+const SEEDED_VAULT: &str = "\
+secrets: [
+  { key: \"TRAIL_EMAIL_SMTP_PASSWORD\" value: \"old-smtp-password\" },
+  { key: \"S3_SECRET_ACCESS_KEY\" value: \"hand-added-s3-secret\" }
+]
+";
+
+#[test]
+fn unmanaged_secret_survives_and_managed_ones_are_updated() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", SEEDED_VAULT);
+
+    let output = workspace.generate(&["--merge-vault"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("key: \"S3_SECRET_ACCESS_KEY\"\n  value: \"hand-added-s3-secret\""), "{}", vault);
+    assert!(vault.contains("key: \"TRAIL_EMAIL_SMTP_PASSWORD\"\n  value: \"smtp-test-password\""), "{}", vault);
+    assert!(!vault.contains("old-smtp-password"), "{}", vault);
+    assert!(vault.contains("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET"), "{}", vault);
+}
+
+#[test]
+fn without_the_flag_the_vault_is_replaced() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", SEEDED_VAULT);
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.read("secrets/secrets.textproto").contains("S3_SECRET_ACCESS_KEY"));
+}
+
+#[test]
+fn missing_vault_is_generated_normally() {
+    let merged = Workspace::new();
+    let generated = Workspace::new();
+
+    let output = merged.generate(&["--merge-vault"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(generated.generate(&[]).status.success());
+    assert_eq!(merged.read("secrets/secrets.textproto"), generated.read("secrets/secrets.textproto"));
+}
+
+#[test]
+fn check_accepts_a_merged_vault() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", SEEDED_VAULT);
+    assert!(workspace.generate(&["--merge-vault"]).status.success());
+
+    let output = workspace.generate(&["--merge-vault", "--check"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}
+
+#[test]
+fn invalid_existing_vault_is_reported_without_its_contents() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", "secrets: [{ key: \"A\" value: unquoted-secret-value }]\n");
+
+    let output = workspace.generate(&["--merge-vault"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("cannot merge into '") && message.contains("existing vault: not a valid config.Vault message"), "{}", message);
+    assert!(!message.contains("unquoted-secret-value"), "{}", message);
+}
+
+#[test]
+fn library_merge_keeps_unmanaged_secrets() {
+    let authn = parse_authn_file(AUTHN).expect("authn file parses");
+
+    let output = merge_vault_with(&Schema::load(None).expect("embedded schema"), SEEDED_VAULT, &authn, &GenerateOptions::default())
+        .expect("merge succeeds");
+
+    assert_eq!(
+        output.secrets.keys().map(String::as_str).collect::<Vec<_>>(),
+        ["S3_SECRET_ACCESS_KEY", "TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET", "TRAIL_EMAIL_SMTP_PASSWORD"]
+    );
+}
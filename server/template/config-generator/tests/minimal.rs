@@ -0,0 +1,64 @@
+//! Tests for `--minimal`, which writes only the config fields the generator sets.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate_with, parse_authn_file, GenerateOptions, Schema};
+
+#[test]
+fn defaulted_fields_are_absent() {
+    let workspace = Workspace::with_authn(&format!("{}CONFIG_server.site_url=https://app.example.test\n", AUTHN));
+
+    let output = workspace.generate(&["--minimal"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\"\n"), "{}", config);
+    assert!(config.contains("smtp_host: \"smtp.mail.test\"\n"), "{}", config);
+    assert!(config.contains("smtp_password: \"<REDACTED>\"\n"), "{}", config);
+    assert!(config.contains("site_url: \"https://app.example.test\"\n"), "{}", config);
+    // Values only the template supplies, and blocks nothing was set in, are left out
+    for defaulted in ["application_name", "logs_retention_sec", "auth_token_ttl_sec", "provider_id", "client_secret", "jobs", "record_apis"] {
+        assert!(!config.contains(defaulted), "{} in {}", defaulted, config);
+    }
+    // The vault is the same either way
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"GOCSPX-test-client-secret\"\n"));
+}
+
+#[test]
+fn full_template_output_stays_the_default() {
+    let schema = Schema::load(None).expect("embedded schema");
+    let authn = parse_authn_file(AUTHN).expect("authn file parses");
+
+    let full = generate_with(&schema, TEMPLATE, &authn, &GenerateOptions::default()).expect("generation succeeds");
+    let minimal = generate_with(&schema, TEMPLATE, &authn, &GenerateOptions { minimal: true, ..GenerateOptions::default() })
+        .expect("generation succeeds");
+
+    assert!(full.config.contains("application_name: \"TrailBase\"\n"), "{}", full.config);
+    assert!(!minimal.config.contains("application_name"), "{}", minimal.config);
+    // Notes and the vault come from the same fill
+    assert_eq!(minimal.notes, full.notes);
+    assert_eq!(minimal.vault, full.vault);
+}
+
+#[test]
+fn auth_mode_still_drops_unused_blocks() {
+    let workspace = Workspace::with_authn(&format!("{}AUTH_MODE=email\n", AUTHN));
+
+    let output = workspace.generate(&["--minimal"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("email {\n"), "{}", config);
+    assert!(!config.contains("auth {") && !config.contains("oauth_providers"), "{}", config);
+}
+
+#[test]
+fn merge_has_nothing_to_leave_out() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--minimal", "--merge"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--minimal cannot be combined with --merge"), "{}", stderr(&output));
+}
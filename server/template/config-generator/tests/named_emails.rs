@@ -0,0 +1,109 @@
+//! Tests for named email identities (`EMAIL_<NAME>_*` keys) and their `<name>_email` blocks.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::parse_authn_file;
+use prost::Message;
+use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+use prost_reflect::prost_types::{FieldDescriptorProto, FileDescriptorSet};
+
+/// The descriptor set embedded in the binary
+const EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+const MARKETING: &str = "\
+EMAIL_MARKETING_SMTP_HOST=smtp.news.test
+EMAIL_MARKETING_SMTP_PORT=2525
+EMAIL_MARKETING_SMTP_USERNAME=news@news.test
+EMAIL_MARKETING_SMTP_PASSWORD=marketing-test-password
+EMAIL_MARKETING_SENDER_NAME=TrailBase News
+EMAIL_MARKETING_SENDER_ADDRESS=news@news.test
+";
+
+/// Write the embedded descriptor set with an `EmailConfig marketing_email = 90` field added to
+/// `Config`, returning its path
+fn write_schema_with_marketing_email(workspace: &Workspace) -> String {
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let file = set.file.iter_mut().find(|file| file.message_type.iter().any(|message| message.name() == "Config")).unwrap();
+    let config = file.message_type.iter_mut().find(|message| message.name() == "Config").unwrap();
+    let mut field = FieldDescriptorProto {
+        name: Some("marketing_email".to_string()),
+        number: Some(90),
+        type_name: Some(".config.EmailConfig".to_string()),
+        ..Default::default()
+    };
+    field.set_label(Label::Optional);
+    field.set_type(Type::Message);
+    config.field.push(field);
+
+    let path = workspace.path("descriptors.bin");
+    std::fs::write(&path, set.encode_to_vec()).unwrap();
+    path_arg(&path)
+}
+
+/// A workspace whose authn file has a marketing identity and whose template has a block for it
+fn marketing_workspace() -> Workspace {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, MARKETING));
+    workspace.write("config.textproto.template", &format!("{}marketing_email {{}}\n", TEMPLATE));
+    workspace
+}
+
+#[test]
+fn named_identity_fills_its_own_block() {
+    let workspace = marketing_workspace();
+    let schema = write_schema_with_marketing_email(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(config.contains("smtp_host: \"smtp.news.test\""), "{}", config);
+    assert!(config.contains("sender_name: \"TrailBase News\""), "{}", config);
+    assert!(!config.contains("marketing-test-password"), "{}", config);
+}
+
+#[test]
+fn named_password_gets_its_own_vault_key() {
+    let workspace = marketing_workspace();
+    let schema = write_schema_with_marketing_email(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(
+        vault.contains("key: \"TRAIL_EMAIL_MARKETING_SMTP_PASSWORD\"\n  value: \"marketing-test-password\""),
+        "{}",
+        vault
+    );
+    assert!(vault.contains("key: \"TRAIL_EMAIL_SMTP_PASSWORD\"\n  value: \"smtp-test-password\""), "{}", vault);
+}
+
+#[test]
+fn template_without_the_block_is_an_error() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, MARKETING));
+    let schema = write_schema_with_marketing_email(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("email config 'marketing' (EMAIL_MARKETING_* keys) has no marketing_email block in the template"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn incomplete_identity_reports_its_missing_keys() {
+    let partial = MARKETING.replace("EMAIL_MARKETING_SENDER_NAME=TrailBase News\n", "");
+
+    let error = parse_authn_file(&format!("{}{}", AUTHN, partial)).err().expect("missing key is an error");
+
+    let message = error.to_string();
+    assert!(message.contains("EMAIL_MARKETING_SENDER_NAME"), "{}", message);
+    assert!(!message.contains("EMAIL_SENDER_NAME"), "{}", message);
+}
@@ -0,0 +1,16 @@
+//! Tests for `--no-vault-if-empty`.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+#[test]
+fn vault_with_secrets_is_still_written() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--no-vault-if-empty"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+    assert!(!stderr(&output).contains("Skipped vault file"), "{}", stderr(&output));
+}
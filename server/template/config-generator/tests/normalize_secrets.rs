@@ -0,0 +1,143 @@
+//! Tests for `--normalize-secrets`, which trims secrets and reports each one that needed it.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN};
+use config_generator::{generate_with, parse_authn_file, GenerateOptions, Schema};
+
+fn padded_authn() -> String {
+    AUTHN.replace("GOCSPX-test-client-secret", "GOCSPX-test-client-secret \t")
+}
+
+#[test]
+fn padded_secret_is_trimmed_with_warning() {
+    let workspace = Workspace::with_authn(&padded_authn());
+
+    let output = workspace.generate(&["--normalize-secrets"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(
+        message.contains("Warning: trimmed surrounding whitespace from GOOGLE_OAUTH_CLIENT_SECRET (line 2)"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("EMAIL_SMTP_PASSWORD"), "{}", message);
+    assert!(workspace
+        .read("secrets/secrets.textproto")
+        .contains("value: \"GOCSPX-test-client-secret\""));
+}
+
+#[test]
+fn clean_secrets_produce_no_warning() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--normalize-secrets"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Warning"), "{}", stderr(&output));
+}
+
+/// Generate with `--normalize-secrets` plus `extra` and check the Google client secret was vaulted
+/// trimmed with a warning naming its key
+fn assert_trimmed_with_warning(workspace: &Workspace, extra: &[&str]) {
+    let mut args = vec!["--normalize-secrets"];
+    args.extend(extra);
+
+    let output = workspace.generate(&args);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Warning: trimmed surrounding whitespace from GOOGLE_OAUTH_CLIENT_SECRET;"),
+        "{}",
+        stderr(&output)
+    );
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("value: \"GOCSPX-test-client-secret\"\n"), "{}", vault);
+}
+
+#[test]
+fn quoted_padding_is_trimmed() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("GOCSPX-test-client-secret", "'  GOCSPX-test-client-secret  '"));
+
+    assert_trimmed_with_warning(&workspace, &[]);
+}
+
+#[test]
+fn value_file_padding_is_trimmed() {
+    let workspace = Workspace::new();
+    workspace.write("run/google", "GOCSPX-test-client-secret \n\n");
+    let reference = format!("@{}", path_arg(&workspace.path("run/google")));
+    workspace.write(".authn", &AUTHN.replace("GOCSPX-test-client-secret", &reference));
+
+    assert_trimmed_with_warning(&workspace, &[]);
+}
+
+#[test]
+fn environment_padding_is_trimmed() {
+    let authn: String = AUTHN.lines().filter(|line| !line.starts_with("GOOGLE_OAUTH_CLIENT_SECRET=")).map(|line| format!("{}\n", line)).collect();
+    let workspace = Workspace::with_authn(&authn);
+    let mut args = vec!["--normalize-secrets".to_string()];
+    args.extend(workspace.default_args());
+
+    let output = workspace.run_with_env(&args, &[("GOOGLE_OAUTH_CLIENT_SECRET", "\tGOCSPX-test-client-secret ".to_string())]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("trimmed surrounding whitespace from GOOGLE_OAUTH_CLIENT_SECRET;"), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"GOCSPX-test-client-secret\"\n"));
+}
+
+#[test]
+fn json_padding_is_trimmed() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "authn.json",
+        r#"{
+  "oauth_providers": {"google": {"client_id": "test-client-id.apps.googleusercontent.com", "client_secret": " GOCSPX-test-client-secret\n"}},
+  "auth_mode": "oauth"
+}
+"#,
+    );
+    let mut args = vec!["--normalize-secrets".to_string()];
+    args.extend(workspace.default_args());
+    args[2] = path_arg(&workspace.path("authn.json"));
+
+    let output = workspace.run(&args);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("trimmed surrounding whitespace from GOOGLE_OAUTH_CLIENT_SECRET;"), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"GOCSPX-test-client-secret\"\n"));
+}
+
+#[test]
+fn padding_is_an_error_under_strict() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("GOCSPX-test-client-secret", "'GOCSPX-test-client-secret '"));
+
+    let output = workspace.generate(&["--normalize-secrets", "--strict"]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("--strict: 1 warning(s) treated as errors"), "{}", stderr(&output));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn without_the_option_quoted_padding_is_kept() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("GOCSPX-test-client-secret", "' GOCSPX-test-client-secret'"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \" GOCSPX-test-client-secret\""));
+}
+
+#[test]
+fn library_reports_trimmed_secrets_as_warnings() {
+    let schema = Schema::load(None).expect("embedded schema");
+    let authn = parse_authn_file(&AUTHN.replace("smtp-test-password", "'smtp-test-password\t'")).expect("authn file parses");
+    let options = GenerateOptions { normalize_secrets: true, ..GenerateOptions::default() };
+
+    let output = generate_with(&schema, common::TEMPLATE, &authn, &options).expect("generation succeeds");
+
+    assert_eq!(output.secrets.get("TRAIL_EMAIL_SMTP_PASSWORD").map(String::as_str), Some("smtp-test-password"));
+    assert_eq!(output.warnings, ["trimmed surrounding whitespace from EMAIL_SMTP_PASSWORD; check the source it was copied from"]);
+}
@@ -0,0 +1,130 @@
+//! Tests for the optional `<PROVIDER>_OAUTH_SCOPES` and `<PROVIDER>_OAUTH_REDIRECT_URL` keys.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate, parse_authn_file};
+use prost::Message;
+use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+use prost_reflect::prost_types::{FieldDescriptorProto, FileDescriptorSet};
+
+/// The descriptor set embedded in the binary
+const EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+const OPTIONS: &str = "\
+GOOGLE_OAUTH_SCOPES=openid, email,profile
+GOOGLE_OAUTH_REDIRECT_URL=https://app.test/api/auth/v1/oauth/google/callback
+";
+
+/// Write the embedded descriptor set with `repeated string scopes = 15` and
+/// `optional string redirect_url = 16` added to `OAuthProviderConfig`, returning its path
+fn write_schema_with_options(workspace: &Workspace) -> String {
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let provider = set
+        .file
+        .iter_mut()
+        .flat_map(|file| file.message_type.iter_mut())
+        .find(|message| message.name() == "OAuthProviderConfig")
+        .unwrap();
+    for (name, number, label) in [("scopes", 15, Label::Repeated), ("redirect_url", 16, Label::Optional)] {
+        let mut field = FieldDescriptorProto { name: Some(name.to_string()), number: Some(number), ..Default::default() };
+        field.set_label(label);
+        field.set_type(Type::String);
+        provider.field.push(field);
+    }
+
+    let path = workspace.path("descriptors.bin");
+    std::fs::write(&path, set.encode_to_vec()).unwrap();
+    path_arg(&path)
+}
+
+#[test]
+fn scopes_and_redirect_url_are_parsed() {
+    let authn = parse_authn_file(&format!("{}{}", AUTHN, OPTIONS)).expect("authn file parses");
+
+    let google = &authn.oauth_providers[0];
+    assert_eq!(google.scopes.as_deref(), Some(&["openid".to_string(), "email".to_string(), "profile".to_string()][..]));
+    assert_eq!(google.redirect_url.as_deref(), Some("https://app.test/api/auth/v1/oauth/google/callback"));
+}
+
+#[test]
+fn options_are_emitted_when_the_schema_defines_them() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, OPTIONS));
+    let schema = write_schema_with_options(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("scopes: [\"openid\", \"email\", \"profile\"]"), "{}", config);
+    assert!(config.contains("redirect_url: \"https://app.test/api/auth/v1/oauth/google/callback\""), "{}", config);
+    // Non-secret values stay out of the vault
+    assert!(!workspace.read("secrets/secrets.textproto").contains("app.test"));
+}
+
+#[test]
+fn omitted_options_keep_the_template_values() {
+    let workspace = Workspace::new();
+    let schema = write_schema_with_options(&workspace);
+    workspace.write(
+        "config.textproto.template",
+        &TEMPLATE.replace("provider_id: GOOGLE", "provider_id: GOOGLE\n      scopes: [\"openid\"]\n      redirect_url: \"https://template.test/cb\""),
+    );
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("scopes: [\"openid\"]"), "{}", config);
+    assert!(config.contains("redirect_url: \"https://template.test/cb\""), "{}", config);
+}
+
+#[test]
+fn bundled_schema_warns_that_options_are_unused() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, OPTIONS));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    for key in ["SCOPES", "REDIRECT_URL"] {
+        let field = key.to_lowercase();
+        let warning = format!(
+            "Warning: GOOGLE_OAUTH_{} is set, but config.OAuthProviderConfig has no {} field, so it is not written to the config",
+            key, field
+        );
+        assert!(stderr(&output).contains(&warning), "{}", stderr(&output));
+    }
+    assert!(!workspace.read("config.textproto").contains("app.test"));
+
+    // The library reports them in the result, and --strict refuses them
+    let authn = parse_authn_file(&format!("{}{}", AUTHN, OPTIONS)).expect("authn file parses");
+    let generated = generate(TEMPLATE, &authn).expect("generation succeeds");
+    assert_eq!(generated.warnings.iter().filter(|warning| warning.contains("not written to the config")).count(), 2);
+    let output = workspace.generate(&["--strict"]);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+}
+
+#[test]
+fn schema_with_the_fields_has_no_warning() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, OPTIONS));
+    let schema = write_schema_with_options(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Warning:"), "{}", stderr(&output));
+}
+
+#[test]
+fn malformed_options_are_rejected() {
+    let authn = format!("{}GOOGLE_OAUTH_SCOPES=openid email\nGOOGLE_OAUTH_REDIRECT_URL=app.test/callback\n", AUTHN);
+
+    let message = parse_authn_file(&authn).err().expect("malformed options are rejected").to_string();
+
+    assert!(
+        message.contains("GOOGLE_OAUTH_SCOPES='openid email' (scopes are comma-separated, but 'openid email' contains whitespace)"),
+        "{}",
+        message
+    );
+    assert!(message.contains("GOOGLE_OAUTH_REDIRECT_URL='app.test/callback' (must be an http:// or https:// URL)"), "{}", message);
+}
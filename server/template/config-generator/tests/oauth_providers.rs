@@ -0,0 +1,98 @@
+//! Tests for configuring several OAuth providers from `<PROVIDER>_OAUTH_CLIENT_ID` /
+//! `<PROVIDER>_OAUTH_CLIENT_SECRET` pairs in one run.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+
+/// A provider block with the per-provider client ID placeholder
+fn provider_block(name: &str, provider_id: &str) -> String {
+    format!(
+        "{{\n    key: \"{}\"\n    value {{\n      client_id: \"<{}_OAUTH_CLIENT_ID>\"\n      client_secret: \"<REDACTED>\"\n      provider_id: {}\n    }}\n  }}",
+        name,
+        name.to_uppercase(),
+        provider_id
+    )
+}
+
+/// The default template with GitHub, Discord and Microsoft blocks added after Google's
+fn multi_provider_template() -> String {
+    let extra: Vec<String> = [("github", "GITHUB"), ("discord", "DISCORD"), ("microsoft", "MICROSOFT")]
+        .iter()
+        .map(|(name, provider_id)| provider_block(name, provider_id))
+        .collect();
+    let template = TEMPLATE.replacen("  }]\n}", &format!("  }}, {}]\n}}", extra.join(", ")), 1);
+    assert_ne!(template, TEMPLATE, "template anchor not found");
+    template
+}
+
+const EXTRA_PROVIDERS: &str = "\
+GITHUB_OAUTH_CLIENT_ID=gh-client-id
+GITHUB_OAUTH_CLIENT_SECRET=gh-client-secret
+DISCORD_OAUTH_CLIENT_ID=discord-client-id
+DISCORD_OAUTH_CLIENT_SECRET=discord-client-secret
+MICROSOFT_OAUTH_CLIENT_ID=ms-client-id
+MICROSOFT_OAUTH_CLIENT_SECRET=ms-client-secret
+";
+
+#[test]
+fn each_provider_fills_its_own_block_and_vault_secret() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, EXTRA_PROVIDERS));
+    workspace.write("config.textproto.template", &multi_provider_template());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    for client_id in ["test-client-id.apps.googleusercontent.com", "gh-client-id", "discord-client-id", "ms-client-id"] {
+        assert!(config.contains(&format!("client_id: \"{}\"", client_id)), "{}", config);
+    }
+    assert!(!config.contains("_OAUTH_CLIENT_ID>"), "{}", config);
+    assert!(
+        config.contains("key: \"github\"\n    value {\n      client_id: \"gh-client-id\""),
+        "{}",
+        config
+    );
+
+    let vault = workspace.read("secrets/secrets.textproto");
+    for (provider, secret) in [
+        ("GOOGLE", "GOCSPX-test-client-secret"),
+        ("GITHUB", "gh-client-secret"),
+        ("DISCORD", "discord-client-secret"),
+        ("MICROSOFT", "ms-client-secret"),
+    ] {
+        assert!(vault.contains(&format!("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_{}_CLIENT_SECRET\"", provider)), "{}", vault);
+        assert!(vault.contains(&format!("value: \"{}\"", secret)), "{}", vault);
+    }
+}
+
+#[test]
+fn missing_secret_names_the_incomplete_provider() {
+    let workspace = Workspace::with_authn(&format!("{}DISCORD_OAUTH_CLIENT_ID=discord-client-id\n", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("missing: DISCORD_OAUTH_CLIENT_SECRET (required for AUTH_MODE=both; OAuth provider 'discord' is incomplete)"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn legacy_redacted_client_id_is_filled_from_google() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "config.textproto.template",
+        &TEMPLATE.replace("client_id: \"<GOOGLE_OAUTH_CLIENT_ID>\"", "client_id: \"<REDACTED>\""),
+    );
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+}
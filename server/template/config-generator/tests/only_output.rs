@@ -0,0 +1,92 @@
+//! Tests for `--only config` and `--only vault`, which regenerate one output and leave the other alone.
+
+mod common;
+
+use common::{path_arg, stderr, stdout, Workspace, AUTHN};
+
+#[test]
+fn only_config_does_not_create_the_vault_or_its_directory() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--only", "config"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets"), "the vault directory was created");
+    assert!(stderr(&output).contains("Skipped vault file"), "{}", stderr(&output));
+}
+
+#[test]
+fn only_config_leaves_an_existing_vault_unmodified() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write("secrets/secrets.textproto", "# hand-kept vault\n");
+    workspace.write(".authn", &AUTHN.replace("test-client-id", "rotated-client-id"));
+
+    let output = workspace.generate(&["--only", "config"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("rotated-client-id"));
+    assert_eq!(workspace.read("secrets/secrets.textproto"), "# hand-kept vault\n");
+}
+
+#[test]
+fn only_vault_needs_no_template_and_leaves_the_config_alone() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    let config_before = workspace.read("config.textproto");
+    workspace.write(".authn", &AUTHN.replace("smtp-test-password", "rotated-smtp-password"));
+    workspace.write("config.textproto.template", "not { a valid template");
+
+    let output = workspace.run(&[
+        "--only".to_string(),
+        "vault".to_string(),
+        path_arg(&workspace.path(".authn")),
+        path_arg(&workspace.path("secrets/secrets.textproto")),
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("rotated-smtp-password"));
+    assert_eq!(workspace.read("config.textproto"), config_before);
+}
+
+#[test]
+fn only_vault_with_all_four_arguments_writes_no_config() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--only", "vault"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!stderr(&output).contains("config file"), "{}", stderr(&output));
+}
+
+#[test]
+fn check_only_looks_at_the_selected_output() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&["--only", "vault"]).status.success());
+
+    let output = workspace.generate(&["--only", "vault", "--check"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(stderr(&output).contains("secrets.textproto is up to date"), "{}", stderr(&output));
+}
+
+#[test]
+fn config_options_are_rejected_with_only_vault() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--only", "vault", "--merge"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--merge cannot be combined with --only vault"), "{}", stderr(&output));
+}
+
+#[test]
+fn unknown_output_is_rejected() {
+    let output = Workspace::new().generate(&["--only", "inventory"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--only must be 'config' or 'vault', got 'inventory'"), "{}", stderr(&output));
+}
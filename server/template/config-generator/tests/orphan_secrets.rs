@@ -0,0 +1,43 @@
+//! Tests for the check rejecting OAuth client secrets that have no client ID.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+#[test]
+fn orphaned_github_secret_fails_generation() {
+    let workspace = Workspace::with_authn(&format!("{}GITHUB_OAUTH_CLIENT_SECRET=gh-secret-value\n", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("GITHUB_OAUTH_CLIENT_SECRET is set but GITHUB_OAUTH_CLIENT_ID is not"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("gh-secret-value"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn secret_with_client_id_is_accepted() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-secret-value\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn flag_bypasses_the_check() {
+    let workspace = Workspace::with_authn(&format!("{}GITHUB_OAUTH_CLIENT_SECRET=gh-secret-value\n", AUTHN));
+
+    let output = workspace.generate(&["--allow-orphan-secrets"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,78 @@
+//! Tests for `--output-dir`, which derives both output paths from one directory.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+
+/// `--output-dir <workspace>/out <template> <authn>` plus `extra`
+fn output_dir_args(workspace: &Workspace, extra: &[&str]) -> Vec<String> {
+    let mut args: Vec<String> = extra.iter().map(|arg| arg.to_string()).collect();
+    args.extend([
+        "--output-dir".to_string(),
+        path_arg(&workspace.path("out")),
+        path_arg(&workspace.path("config.textproto.template")),
+        path_arg(&workspace.path(".authn")),
+    ]);
+    args
+}
+
+#[test]
+fn outputs_use_the_conventional_names() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&output_dir_args(&workspace, &[]));
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("out/config.textproto").contains("test-client-id"));
+    assert!(workspace.read("out/secrets/secrets.textproto").contains("smtp-test-password"));
+    // Same bytes as the four-argument form
+    assert!(workspace.generate(&[]).status.success());
+    assert_eq!(workspace.read("out/config.textproto"), workspace.read("config.textproto"));
+    assert_eq!(workspace.read("out/secrets/secrets.textproto"), workspace.read("secrets/secrets.textproto"));
+}
+
+#[test]
+fn output_paths_cannot_be_given_as_well() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--output-dir", "out"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--output-dir takes only <template-file> <authn-file>, got 4 arguments"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!workspace.exists("out"));
+}
+
+#[test]
+fn only_vault_needs_just_the_authn_file() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&[
+        "--only".to_string(),
+        "vault".to_string(),
+        "--output-dir".to_string(),
+        path_arg(&workspace.path("out")),
+        path_arg(&workspace.path(".authn")),
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("out/secrets/secrets.textproto"));
+    assert!(!workspace.exists("out/config.textproto"));
+}
+
+#[test]
+fn output_dir_can_come_from_the_environment() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run_with_env(
+        &[path_arg(&workspace.path("config.textproto.template")), path_arg(&workspace.path(".authn"))],
+        &[("TRAIL_GEN_OUTPUT_DIR", path_arg(&workspace.path("from-env")))],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("from-env/config.textproto"));
+    assert!(workspace.exists("from-env/secrets/secrets.textproto"));
+}
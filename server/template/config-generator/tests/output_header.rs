@@ -0,0 +1,74 @@
+//! Tests for the comment header at the top of both outputs, and `--header` to customize it.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+
+#[test]
+fn default_header_names_the_template_and_is_stable() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    let vault = workspace.read("secrets/secrets.textproto");
+    let header = "# Generated from config.textproto.template by config-generator; edit the template and regenerate rather than editing this file\n";
+    assert!(config.starts_with(&format!("# Auto-generated config.Config textproto\n{}", header)), "{}", config);
+    assert!(vault.starts_with(&format!("# Auto-generated config.Vault textproto\n{}", header)), "{}", vault);
+
+    // No timestamp by default, so a rerun writes the same bytes
+    assert!(workspace.generate(&[]).status.success());
+    assert_eq!(workspace.read("config.textproto"), config);
+    assert_eq!(workspace.read("secrets/secrets.textproto"), vault);
+}
+
+#[test]
+fn custom_header_fills_in_the_template_and_timestamp() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--header", "Owned by the platform team\n\nFrom {template} at {timestamp}"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    for name in ["config.textproto", "secrets/secrets.textproto"] {
+        let content = workspace.read(name);
+        let lines: Vec<&str> = content.lines().take(4).collect();
+        assert!(lines[0].starts_with("# Auto-generated config."), "{}", content);
+        assert_eq!(lines[1..3], ["# Owned by the platform team", "#"], "{}", content);
+        let timestamp = lines[3].strip_prefix("# From config.textproto.template at ").expect("timestamp line");
+        assert_eq!(timestamp.len(), "2026-10-14T09:30:00Z".len(), "{}", timestamp);
+        assert!(timestamp.starts_with("20") && timestamp.ends_with('Z') && timestamp.as_bytes()[10] == b'T', "{}", timestamp);
+    }
+}
+
+#[test]
+fn empty_header_leaves_only_the_preface() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--header", ""]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.starts_with("# Auto-generated config.Config textproto\n") && !config.contains("\n#"), "{}", config);
+}
+
+#[test]
+fn vault_only_header_names_no_template() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run(&[
+        "--only".to_string(),
+        "vault".to_string(),
+        path_arg(&workspace.path(".authn")),
+        path_arg(&workspace.path("secrets/secrets.textproto")),
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        workspace
+            .read("secrets/secrets.textproto")
+            .starts_with("# Auto-generated config.Vault textproto\n# Generated by config-generator; regenerate rather than editing this file\n"),
+        "{}",
+        workspace.read("secrets/secrets.textproto")
+    );
+}
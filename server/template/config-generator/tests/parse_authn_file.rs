@@ -0,0 +1,92 @@
+//! Tests for `parse_authn_file` against the fixture files in `tests/fixtures/authn`.
+
+use config_generator::{parse_authn_file, AuthMode, GenError, SmtpSecurity};
+
+/// A fixture's contents, read at compile time so a missing fixture fails the build
+macro_rules! fixture {
+    ($name:literal) => {
+        include_str!(concat!("fixtures/authn/", $name))
+    };
+}
+
+#[test]
+fn complete_file_parses_every_block() {
+    let authn = parse_authn_file(fixture!("complete.authn")).expect("complete fixture parses");
+
+    assert_eq!(authn.auth_mode, AuthMode::Both);
+    let providers: Vec<(&str, &str, Option<&str>)> = authn
+        .oauth_providers
+        .iter()
+        .map(|provider| (provider.name.as_str(), provider.client_id.as_str(), provider.client_secret.as_deref()))
+        .collect();
+    assert_eq!(
+        providers,
+        [
+            ("github", "fixture-github-id", Some("fixture-github-secret")),
+            ("google", "fixture-google-id.apps.googleusercontent.com", Some("GOCSPX-fixture # not a comment")),
+        ]
+    );
+    assert_eq!(authn.oauth_providers[1].scopes, Some(vec!["openid".to_string(), "email".to_string()]));
+    assert_eq!(authn.oauth_providers[0].scopes, None);
+
+    let email = authn.email.as_ref().expect("email settings");
+    assert_eq!(email.smtp_host, "smtp.fixture.test");
+    assert_eq!(email.smtp_port, 465);
+    assert_eq!(email.smtp_security, Some(SmtpSecurity::Tls));
+    assert_eq!(email.smtp_username, "mailer@fixture.test");
+    assert_eq!(email.smtp_password, "fixture=password");
+    assert_eq!(email.sender_name, "Fixture Sender");
+    assert_eq!(email.sender_address, "noreply@fixture.test");
+    assert!(authn.named_emails.is_empty());
+
+    assert!(authn.keys.contains("SEND_WELCOME_MAIL"));
+    assert_eq!(authn.unknown_keys, ["SEND_WELCOME_MAIL"]);
+    assert!(authn.warnings.is_empty(), "{:?}", authn.warnings);
+}
+
+#[test]
+fn missing_keys_are_all_listed() {
+    let Err(GenError::Authn { missing, invalid, auth_mode }) = parse_authn_file(fixture!("missing-key.authn")) else {
+        panic!("missing-key fixture should fail with GenError::Authn");
+    };
+
+    assert_eq!(missing, ["GITHUB_OAUTH_CLIENT_SECRET", "EMAIL_SMTP_HOST"]);
+    assert!(invalid.is_empty(), "{:?}", invalid.iter().map(ToString::to_string).collect::<Vec<_>>());
+    assert_eq!(auth_mode, AuthMode::Both);
+}
+
+#[test]
+fn bad_port_is_invalid_with_its_value() {
+    let Err(GenError::Authn { missing, invalid, auth_mode }) = parse_authn_file(fixture!("bad-port.authn")) else {
+        panic!("bad-port fixture should fail with GenError::Authn");
+    };
+
+    assert!(missing.is_empty(), "{:?}", missing);
+    assert_eq!(auth_mode, AuthMode::Email);
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].key, "EMAIL_SMTP_PORT");
+    assert_eq!(invalid[0].value.as_deref(), Some("submission"));
+}
+
+#[test]
+fn empty_file_is_missing_a_provider_and_the_email_block() {
+    let error = parse_authn_file(fixture!("empty.authn")).err().expect("empty fixture should fail");
+    let GenError::Authn { missing, invalid, .. } = &error else {
+        panic!("expected GenError::Authn, got {}", error);
+    };
+
+    assert_eq!(
+        missing,
+        &[
+            "<PROVIDER>_OAUTH_CLIENT_ID",
+            "EMAIL_SMTP_HOST",
+            "EMAIL_SMTP_PORT",
+            "EMAIL_SMTP_USERNAME",
+            "EMAIL_SMTP_PASSWORD",
+            "EMAIL_SENDER_NAME",
+            "EMAIL_SENDER_ADDRESS",
+        ]
+    );
+    assert!(invalid.is_empty());
+    assert!(error.to_string().contains("no OAuth provider found"), "{}", error);
+}
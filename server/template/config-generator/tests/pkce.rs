@@ -0,0 +1,77 @@
+//! Tests for `<PROVIDER>_OAUTH_PKCE`, which marks a public client that has no client secret.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate, parse_authn_file};
+
+const SECRET_LINE: &str = "GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret\n";
+
+/// The default authn file with Google as a PKCE client
+fn pkce_authn() -> String {
+    let authn = AUTHN.replace(SECRET_LINE, "GOOGLE_OAUTH_PKCE=true\n");
+    assert_ne!(authn, AUTHN, "authn anchor not found");
+    authn
+}
+
+#[test]
+fn pkce_provider_has_no_vault_secret() {
+    let workspace = Workspace::with_authn(&pkce_authn());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(!vault.contains("GOOGLE"), "{}", vault);
+    assert!(vault.contains("key: \"TRAIL_EMAIL_SMTP_PASSWORD\""), "{}", vault);
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+    // The template's placeholder would point TrailBase at a secret that doesn't exist
+    assert!(!config.contains("client_secret"), "{}", config);
+}
+
+#[test]
+fn library_skips_only_the_pkce_provider() {
+    let authn = parse_authn_file(&format!("{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_PKCE=true\n", AUTHN))
+        .expect("authn file parses");
+
+    let output = generate(TEMPLATE, &authn).expect("generation succeeds");
+
+    let github = authn.oauth_providers.iter().find(|provider| provider.name == "github").expect("github provider");
+    assert_eq!(github.client_id, "gh-client-id");
+    assert!(github.client_secret.is_none());
+    assert_eq!(
+        output.secrets.keys().map(String::as_str).collect::<Vec<_>>(),
+        ["TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET", "TRAIL_EMAIL_SMTP_PASSWORD"]
+    );
+}
+
+#[test]
+fn secret_for_a_pkce_provider_is_rejected() {
+    let workspace = Workspace::with_authn(&format!("{}GOOGLE_OAUTH_PKCE=true\n", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("GOOGLE_OAUTH_CLIENT_SECRET (must not be set when GOOGLE_OAUTH_PKCE=true, since a PKCE client has no secret)"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("GOCSPX-test-client-secret"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn pkce_must_be_true_or_false() {
+    let workspace = Workspace::with_authn(&pkce_authn().replace("GOOGLE_OAUTH_PKCE=true", "GOOGLE_OAUTH_PKCE=yes"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("GOOGLE_OAUTH_PKCE='yes' (must be true or false)"), "{}", message);
+    // Not a PKCE client, so its secret is still required
+    assert!(message.contains("GOOGLE_OAUTH_CLIENT_SECRET"), "{}", message);
+}
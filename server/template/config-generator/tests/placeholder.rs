@@ -0,0 +1,73 @@
+//! Tests for `--placeholder`, the sentinel left in the config for secrets that live in the vault.
+
+mod common;
+
+use common::{stderr, Workspace, TEMPLATE};
+
+const PLACEHOLDER: &str = "${SECRET}";
+
+/// A workspace whose template marks secrets with `${SECRET}` instead of `<REDACTED>`
+fn workspace_with_custom_marker() -> Workspace {
+    let workspace = Workspace::new();
+    workspace.write(
+        "config.textproto.template",
+        &TEMPLATE.replace("client_secret: \"<REDACTED>\"", &format!("client_secret: \"{}\"", PLACEHOLDER)),
+    );
+    workspace
+}
+
+#[test]
+fn custom_placeholder_is_accepted_and_written() {
+    let workspace = workspace_with_custom_marker();
+
+    let output = workspace.generate(&["--placeholder", PLACEHOLDER]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("client_secret: \"${SECRET}\""), "{}", config);
+    assert!(config.contains("smtp_password: \"${SECRET}\""), "{}", config);
+    assert!(!config.contains("<REDACTED>"), "{}", config);
+}
+
+#[test]
+fn default_placeholder_is_unfilled_when_another_is_configured() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--placeholder", PLACEHOLDER]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains(r#"template placeholders were not filled: auth.oauth_providers["google"].client_secret = "<REDACTED>""#),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn custom_placeholder_outside_secret_fields_is_rejected() {
+    let workspace = workspace_with_custom_marker();
+    workspace.write(
+        "config.textproto.template",
+        &workspace
+            .read("config.textproto.template")
+            .replace("site_url: \"http://localhost:7000\"", &format!("site_url: \"{}\"", PLACEHOLDER)),
+    );
+
+    let output = workspace.generate(&["--placeholder", PLACEHOLDER]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("server.site_url = \"${SECRET}\""), "{}", message);
+}
+
+#[test]
+fn empty_placeholder_is_a_usage_error() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--placeholder", ""]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--placeholder must not be empty"), "{}", stderr(&output));
+}
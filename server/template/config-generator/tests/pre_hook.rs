@@ -0,0 +1,32 @@
+//! Tests for `--pre-hook`, the shell command run before inputs are read.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+#[test]
+fn failing_pre_hook_aborts_generation() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&["--pre-hook", "echo 'secret fetch failed' >&2; exit 3"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("secret fetch failed"), "{}", message);
+    assert!(message.contains("aborting generation"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn pre_hook_runs_before_inputs_are_read() {
+    let workspace = Workspace::with_authn("");
+    workspace.write(
+        "fetched.authn",
+        &AUTHN.replace("GOCSPX-test-client-secret", "GOCSPX-refreshed-secret"),
+    );
+
+    let output = workspace.generate(&["--pre-hook", "cp fetched.authn .authn"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("GOCSPX-refreshed-secret"));
+}
@@ -0,0 +1,93 @@
+//! Tests for cross-checking the template's OAuth providers against the authn file's, and
+//! `--strict-providers`, which turns a mismatch into an error.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate_with, parse_authn_file, GenError, GenerateOptions, Schema};
+
+/// The default template with a Discord block, whose client ID is hardcoded rather than a placeholder
+fn template_with_discord() -> String {
+    let discord = "{\n    key: \"discord\"\n    value {\n      client_id: \"discord-client-id\"\n      client_secret: \"<REDACTED>\"\n      provider_id: DISCORD\n    }\n  }";
+    let template = TEMPLATE.replacen("  }]\n}", &format!("  }}, {}]\n}}", discord), 1);
+    assert_ne!(template, TEMPLATE, "template anchor not found");
+    template
+}
+
+const GITHUB: &str = "GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-client-secret\n";
+
+const TEMPLATE_ONLY: &str =
+    "the authn file has no DISCORD_OAUTH_CLIENT_ID, so the template's auth.oauth_providers entry \"discord\" was not filled";
+const AUTHN_ONLY: &str = "the template has no auth.oauth_providers entry \"github\", so GITHUB_OAUTH_CLIENT_ID was not used";
+
+#[test]
+fn mismatches_in_both_directions_are_warned_about() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, GITHUB));
+    workspace.write("config.textproto.template", &template_with_discord());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(message.contains(&format!("Warning: {}\n", TEMPLATE_ONLY)), "{}", message);
+    assert!(message.contains(&format!("Warning: {}\n", AUTHN_ONLY)), "{}", message);
+    assert!(workspace.read("config.textproto").contains("discord-client-id"));
+}
+
+#[test]
+fn strict_providers_fails_listing_every_mismatch() {
+    let workspace = Workspace::with_authn(&format!("{}{}", AUTHN, GITHUB));
+    workspace.write("config.textproto.template", &template_with_discord());
+
+    let output = workspace.generate(&["--strict-providers"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains(&format!(
+            "the template and authn file configure different OAuth providers:\n  {}\n  {}",
+            TEMPLATE_ONLY, AUTHN_ONLY
+        )),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn matching_providers_pass_strict_mode_quietly() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--strict-providers"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Warning:"), "{}", stderr(&output));
+}
+
+#[test]
+fn email_mode_does_not_compare_providers() {
+    let authn = AUTHN.lines().filter(|line| !line.starts_with("GOOGLE_")).map(|line| format!("{}\n", line)).collect::<String>();
+    let workspace = Workspace::with_authn(&format!("AUTH_MODE=email\n{}", authn));
+
+    let output = workspace.generate(&["--strict-providers"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("oauth_providers"), "{}", stderr(&output));
+}
+
+#[test]
+fn library_returns_the_mismatches_as_warnings() {
+    let authn = parse_authn_file(&format!("{}{}", AUTHN, GITHUB)).expect("authn file parses");
+    let schema = Schema::load(None).expect("embedded schema");
+
+    let output = generate_with(&schema, TEMPLATE, &authn, &GenerateOptions::default()).expect("generation succeeds");
+    assert_eq!(output.warnings, [AUTHN_ONLY]);
+
+    let strict = GenerateOptions { strict_providers: true, ..GenerateOptions::default() };
+    match generate_with(&schema, TEMPLATE, &authn, &strict) {
+        Err(GenError::Rejected(message)) => assert!(message.ends_with(AUTHN_ONLY), "{}", message),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a mismatch passed strict mode"),
+    }
+}
@@ -0,0 +1,108 @@
+//! Tests for `--providers-json`, which reads OAuth provider credentials from a JSON array.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+
+/// The authn file without its Google provider, so only the array configures providers
+fn email_only_authn() -> String {
+    AUTHN.lines().filter(|line| !line.starts_with("GOOGLE_")).map(|line| format!("{}\n", line)).collect()
+}
+
+/// A workspace whose template also has a GitHub provider block
+fn two_provider_workspace() -> Workspace {
+    let workspace = Workspace::with_authn(&email_only_authn());
+    let block = "{\n    key: \"github\"\n    value {\n      client_id: \"<GITHUB_OAUTH_CLIENT_ID>\"\n      client_secret: \"<REDACTED>\"\n      provider_id: GITHUB\n    }\n  }";
+    workspace.write("config.textproto.template", &TEMPLATE.replacen("  }]\n}", &format!("  }}, {}]\n}}", block), 1));
+    workspace
+}
+
+#[test]
+fn two_element_array_configures_both_providers() {
+    let workspace = two_provider_workspace();
+    workspace.write(
+        "providers.json",
+        r#"[
+  {"provider": "google", "client_id": "json-google-id", "client_secret": "json-google-secret", "scopes": ["openid", "email"]},
+  {"provider": "github", "client_id": "json-github-id", "client_secret": "json-github-secret"}
+]"#,
+    );
+
+    let output = workspace.generate(&["--providers-json", &path_arg(&workspace.path("providers.json"))]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(config.contains("client_id: \"json-google-id\"\n"), "{}", config);
+    assert!(config.contains("client_id: \"json-github-id\"\n"), "{}", config);
+    assert!(!config.contains("json-google-secret") && !config.contains("json-github-secret"), "{}", config);
+    assert!(vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\"\n  value: \"json-google-secret\"\n"), "{}", vault);
+    assert!(vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GITHUB_CLIENT_SECRET\"\n  value: \"json-github-secret\"\n"), "{}", vault);
+}
+
+#[test]
+fn array_overrides_the_authn_files_provider() {
+    let workspace = Workspace::new();
+    workspace.write("providers.json", r#"[{"provider": "google", "client_id": "json-google-id", "client_secret": "json-google-secret"}]"#);
+
+    let output = workspace.run_with_env(&workspace.default_args(), &[("TRAIL_GEN_PROVIDERS_JSON", path_arg(&workspace.path("providers.json")))]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("client_id: \"json-google-id\"\n"));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"json-google-secret\"\n"));
+}
+
+#[test]
+fn invalid_entries_fail_with_their_position() {
+    let workspace = two_provider_workspace();
+    let cases = [
+        (r#"{"provider": "google"}"#, "at line 1, column 1: expected an array of provider objects"),
+        (r#"[{"provider": "google", "client_id": "id", "secret": "s"}]"#, "unknown field 'secret' in provider entry 1"),
+        (r#"[{"client_id": "id"}]"#, "at line 1, column 2: provider entry 1 has no 'provider' name"),
+        (r#"[{"provider": "google", "client_secret": "s"}]"#, "provider 'google' has no client_id"),
+        (
+            "[{\"provider\": \"google\", \"client_id\": \"a\"},\n {\"provider\": \"Google\", \"client_id\": \"b\"}]",
+            "at line 2, column 3: provider 'Google' is listed more than once",
+        ),
+        (r#"[{"provider": "google", "client_id": "id", "scopes": ["openid", ""]}]"#, "scopes in provider entry 1 must be non-empty"),
+    ];
+
+    for (content, expected) in cases {
+        workspace.write("providers.json", content);
+        let output = workspace.generate(&["--providers-json", &path_arg(&workspace.path("providers.json"))]);
+
+        assert_eq!(output.status.code(), Some(3), "{}", content);
+        let message = stderr(&output);
+        assert!(message.contains("invalid providers JSON authn file"), "{}", message);
+        assert!(message.contains(expected), "{}: {}", content, message);
+        assert!(!workspace.exists("config.textproto"));
+    }
+}
+
+#[test]
+fn entries_are_checked_like_authn_keys() {
+    let workspace = two_provider_workspace();
+    // The template has a GitHub block, and the default auth mode needs its secret
+    workspace.write(
+        "providers.json",
+        r#"[{"provider": "google", "client_id": "id", "client_secret": "s"}, {"provider": "github", "client_id": "gh-id"}]"#,
+    );
+
+    let output = workspace.generate(&["--providers-json", &path_arg(&workspace.path("providers.json"))]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("GITHUB_OAUTH_CLIENT_SECRET"), "{}", stderr(&output));
+}
+
+#[test]
+fn authn_files_still_reject_arrays() {
+    let workspace = Workspace::new();
+    workspace.write("authn.json", r#"{"auth_mode": ["oauth"]}"#);
+
+    let mut args = workspace.default_args();
+    args[1] = path_arg(&workspace.path("authn.json"));
+    let output = workspace.run(&args);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("at line 1, column 15: arrays are not supported in authn files"), "{}", stderr(&output));
+}
@@ -0,0 +1,49 @@
+//! Tests for `--redaction-policy`, which rejects plaintext values for vault-only fields.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+#[test]
+fn redacted_policy_fields_pass() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "policy.txt",
+        "# secrets routed to the vault\nemail.smtp_password\nauth.oauth_providers[*].client_secret\n\nserver.s3_storage_config\n",
+    );
+
+    let output = workspace.generate(&["--redaction-policy", "policy.txt"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn plaintext_policy_field_fails_generation() {
+    let workspace = Workspace::new();
+    workspace.write("policy.txt", "email.smtp_password\nemail.smtp_username\nauth.oauth_providers[*].client_id\n");
+
+    let output = workspace.generate(&["--redaction-policy", "policy.txt"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("  email.smtp_username\n"), "{}", message);
+    assert!(message.contains("  auth.oauth_providers[\"google\"].client_id\n"), "{}", message);
+    assert!(!message.contains("email.smtp_password"), "{}", message);
+    assert!(!message.contains("mailer@mail.test"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn unknown_policy_field_is_rejected() {
+    let workspace = Workspace::new();
+    workspace.write("policy.txt", "email.smtp_pasword\n");
+
+    let output = workspace.generate(&["--redaction-policy", "policy.txt"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("redaction policy line 1: 'email.smtp_pasword' names unknown field 'smtp_pasword' in config.EmailConfig"),
+        "{}",
+        stderr(&output)
+    );
+}
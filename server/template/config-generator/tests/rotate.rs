@@ -0,0 +1,91 @@
+//! Tests for `--rotate`, which keeps a changed secret's previous value under `<KEY>_PREVIOUS`.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+const ROTATED_AUTHN_SECRET: &str = "GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-rotated-client-secret";
+
+/// The authn file with Google's client secret replaced by a new one
+fn rotated_authn() -> String {
+    AUTHN.replace("GOOGLE_OAUTH_CLIENT_SECRET=GOCSPX-test-client-secret", ROTATED_AUTHN_SECRET)
+}
+
+#[test]
+fn first_run_writes_only_the_new_secrets() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--rotate"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(
+        vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\"\n  value: \"GOCSPX-test-client-secret\""),
+        "{}",
+        vault
+    );
+    assert!(!vault.contains("_PREVIOUS"), "{}", vault);
+}
+
+#[test]
+fn changed_secret_keeps_the_old_value_as_previous() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(".authn", &rotated_authn());
+
+    let output = workspace.generate(&["--rotate", "--verbose"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(
+        vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\"\n  value: \"GOCSPX-rotated-client-secret\""),
+        "{}",
+        vault
+    );
+    assert!(
+        vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET_PREVIOUS\"\n  value: \"GOCSPX-test-client-secret\""),
+        "{}",
+        vault
+    );
+    // The SMTP password didn't change, so it has nothing to keep
+    assert!(!vault.contains("TRAIL_EMAIL_SMTP_PASSWORD_PREVIOUS"), "{}", vault);
+    let message = stderr(&output);
+    assert!(message.contains("rotated 1 vault secret(s): TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\n"), "{}", message);
+}
+
+#[test]
+fn rerunning_keeps_the_previous_secret_until_rotation_ends() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&[]).status.success());
+    workspace.write(".authn", &rotated_authn());
+    assert!(workspace.generate(&["--rotate"]).status.success());
+
+    // Same secret again: the grace period goes on
+    let output = workspace.generate(&["--rotate"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(
+        vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET_PREVIOUS\"\n  value: \"GOCSPX-test-client-secret\""),
+        "{}",
+        vault
+    );
+
+    // A run without --rotate drops it
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.read("secrets/secrets.textproto").contains("_PREVIOUS"));
+}
+
+#[test]
+fn unreadable_existing_vault_is_rejected() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", "secrets: [{ key: \"UNTERMINATED\n");
+    workspace.write(".authn", &rotated_authn());
+
+    let output = workspace.generate(&["--rotate"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("cannot rotate secrets in '") && message.contains("existing vault is not a valid config.Vault message"), "{}", message);
+    assert!(workspace.read("secrets/secrets.textproto").contains("UNTERMINATED"));
+}
@@ -0,0 +1,75 @@
+//! Tests that error messages never echo a secret from the authn file or an existing vault.
+
+mod common;
+
+use common::{stderr, stdout, Workspace, AUTHN};
+use config_generator::{redact_parse_error, Schema};
+use prost_reflect::DynamicMessage;
+
+/// A client secret that parses as a single bare textproto token when its quotes are lost
+const SECRET: &str = "GOCSPX_test_client_secret";
+
+/// A workspace whose generated vault had the quotes around the client secret removed by hand
+fn workspace_with_unquoted_secret() -> Workspace {
+    let workspace = Workspace::with_authn(&AUTHN.replace("GOCSPX-test-client-secret", SECRET));
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    let edited = vault.replace(&format!("\"{}\"", SECRET), SECRET);
+    assert_ne!(edited, vault, "secret not found in the vault");
+    workspace.write("secrets/secrets.textproto", &edited);
+    workspace
+}
+
+#[test]
+fn vault_parse_error_masks_the_token() {
+    let schema = Schema::load(None).expect("embedded schema");
+    let text = format!("secrets: [{{ key: \"K\" value: {} }}]", SECRET);
+
+    let error = DynamicMessage::parse_text_format(schema.vault, &text).expect_err("bare value is rejected");
+
+    assert!(error.to_string().contains(SECRET), "prost-reflect no longer echoes the token: {}", error);
+    let message = redact_parse_error(&error);
+    assert!(message.ends_with(", but found 'GOCS…'"), "{}", message);
+    assert!(!message.contains(SECRET), "{}", message);
+}
+
+#[test]
+fn check_against_a_broken_vault_does_not_echo_the_secret() {
+    let workspace = workspace_with_unquoted_secret();
+
+    let output = workspace.generate(&["--check"]);
+
+    assert!(!output.status.success());
+    let everything = format!("{}{}", stdout(&output), stderr(&output));
+    assert!(everything.contains("existing vault is not a valid config.Vault message"), "{}", everything);
+    assert!(!everything.contains(SECRET), "{}", everything);
+}
+
+#[test]
+fn canonicalizing_a_broken_vault_does_not_echo_the_secret() {
+    let workspace = workspace_with_unquoted_secret();
+
+    let output = workspace.run(&["--canonicalize", "secrets/secrets.textproto"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("but found 'GOCS…'"), "{}", message);
+    assert!(!message.contains(SECRET), "{}", message);
+}
+
+#[test]
+fn unquoted_json_secret_is_masked() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "authn.json",
+        &format!("{{\"oauth_providers\": {{\"google\": {{\"client_id\": \"id\", \"client_secret\": {}}}}}}}", SECRET),
+    );
+
+    let output = workspace.run(&["config.textproto.template", "authn.json", "config.textproto", "secrets/secrets.textproto"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("invalid JSON authn file at line 1, column"), "{}", message);
+    assert!(!message.contains(SECRET), "{}", message);
+}
@@ -0,0 +1,81 @@
+//! Tests for rejecting malformed `EMAIL_SENDER_ADDRESS` values while reading the authn file.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_file, GenError};
+
+/// Parse the default authn file with `address` as the sender address
+fn parse_with_sender(address: &str) -> Result<String, String> {
+    let authn = AUTHN.replace("EMAIL_SENDER_ADDRESS=noreply@mail.test", &format!("EMAIL_SENDER_ADDRESS='{}'", address));
+    match parse_authn_file(&authn) {
+        Ok(authn) => Ok(authn.email.expect("email settings").sender_address),
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert!(missing.is_empty(), "{:?}", missing);
+            Err(invalid.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn ordinary_and_unusual_addresses_are_accepted() {
+    for address in [
+        "noreply@mail.test",
+        "first.last+alerts@sub.example.co.uk",
+        "\"odd@local\"@example.org",
+        "admin@[192.0.2.1]",
+        "kontakt@bücher.example",
+        "x@a.io",
+    ] {
+        assert_eq!(parse_with_sender(address).as_deref(), Ok(address), "{}", address);
+    }
+}
+
+#[test]
+fn malformed_addresses_are_rejected() {
+    for (address, reason) in [
+        ("foo@bar", "email domain 'bar' has no '.'"),
+        ("noreply.mail.test", "not an email address, expected local-part@domain"),
+        ("@mail.test", "email address has nothing before the '@'"),
+        ("noreply@", "email domain '' has no '.'"),
+        ("noreply@mail..test", "email domain 'mail..test' has an empty label"),
+        ("noreply@.mail.test", "email domain '.mail.test' has an empty label"),
+        ("TrailBase <noreply@mail.test>", "email address must not contain whitespace"),
+    ] {
+        assert_eq!(
+            parse_with_sender(address),
+            Err(format!("EMAIL_SENDER_ADDRESS='{}' ({})", address, reason)),
+            "{}",
+            address
+        );
+    }
+}
+
+#[test]
+fn named_identity_addresses_are_checked_too() {
+    let authn = format!(
+        "{}EMAIL_MARKETING_SMTP_HOST=smtp.mail.test\nEMAIL_MARKETING_SMTP_PORT=587\nEMAIL_MARKETING_SMTP_USERNAME=news\n\
+         EMAIL_MARKETING_SMTP_PASSWORD=news-password\nEMAIL_MARKETING_SENDER_NAME=News\nEMAIL_MARKETING_SENDER_ADDRESS=news@mail\n",
+        AUTHN
+    );
+
+    let error = parse_authn_file(&authn).err().expect("bad address is rejected").to_string();
+
+    assert!(error.contains("EMAIL_MARKETING_SENDER_ADDRESS='news@mail' (email domain 'mail' has no '.')"), "{}", error);
+}
+
+#[test]
+fn generation_fails_before_writing() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("noreply@mail.test", "noreply@mailtest"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("invalid: EMAIL_SENDER_ADDRESS='noreply@mailtest' (email domain 'mailtest' has no '.')"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
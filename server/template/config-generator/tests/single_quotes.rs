@@ -0,0 +1,57 @@
+//! Tests for single-quoted authn values, which are taken literally.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+const LITERAL_PASSWORD: &str = r"p@$$${literal}\n";
+
+#[test]
+fn single_quoted_value_is_used_verbatim() {
+    let workspace = Workspace::with_authn(&AUTHN.replace(
+        "EMAIL_SMTP_PASSWORD=smtp-test-password",
+        &format!("EMAIL_SMTP_PASSWORD='{}'", LITERAL_PASSWORD),
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The backslash is kept as a literal character and escaped only by the textproto printer
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains(r#"value: "p@$$${literal}\\n""#), "{}", vault);
+}
+
+#[test]
+fn authn_template_does_not_interpolate_single_quoted_values() {
+    let workspace = Workspace::new();
+    workspace.write(
+        ".authn.template",
+        &format!("EMAIL_SMTP_PASSWORD='{}'\nEMAIL_SMTP_HOST=${{TEST_SMTP_HOST}}\n", LITERAL_PASSWORD),
+    );
+
+    let output = workspace.run_with_env(
+        &["--authn-template", ".authn.template", "rendered.authn"],
+        &[("TEST_SMTP_HOST", "smtp.mail.test".to_string())],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        workspace.read("rendered.authn"),
+        format!("EMAIL_SMTP_PASSWORD='{}'\nEMAIL_SMTP_HOST=smtp.mail.test\n", LITERAL_PASSWORD)
+    );
+}
+
+#[test]
+fn unterminated_single_quote_is_an_error() {
+    let workspace = Workspace::with_authn(&AUTHN.replace(
+        "EMAIL_SMTP_PASSWORD=smtp-test-password",
+        "EMAIL_SMTP_PASSWORD='smtp-test-password",
+    ));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("EMAIL_SMTP_PASSWORD (unterminated single quote)"), "{}", message);
+    assert!(!message.contains("smtp-test-password"), "{}", message);
+}
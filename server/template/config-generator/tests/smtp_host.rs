@@ -0,0 +1,70 @@
+//! Tests for rejecting `EMAIL_SMTP_HOST` values that aren't a hostname or IP address.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_file, GenError};
+
+/// Parse the default authn file with `host` as the SMTP host
+fn parse_with_host(host: &str) -> Result<String, String> {
+    let authn = AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test", &format!("EMAIL_SMTP_HOST={}", host));
+    match parse_authn_file(&authn) {
+        Ok(authn) => Ok(authn.email.expect("email settings").smtp_host),
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert!(missing.is_empty(), "{:?}", missing);
+            Err(invalid.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn hostnames_are_accepted() {
+    for host in ["smtp.mail.test", "localhost", "smtp-relay.eu-west-1.example.com.", "mail.bücher.example"] {
+        assert_eq!(parse_with_host(host).as_deref(), Ok(host), "{}", host);
+    }
+}
+
+#[test]
+fn ip_addresses_are_accepted() {
+    for host in ["192.0.2.25", "2001:db8::25", "[::1]"] {
+        assert_eq!(parse_with_host(host).as_deref(), Ok(host), "{}", host);
+    }
+}
+
+#[test]
+fn url_with_scheme_is_rejected_with_a_hint() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test", "EMAIL_SMTP_HOST=https://smtp.example.com"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains(
+            "invalid: EMAIL_SMTP_HOST='https://smtp.example.com' (must be a hostname or IP address, not a URL; remove the 'https://' scheme)"
+        ),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn malformed_hosts_are_rejected() {
+    for (host, reason) in [
+        ("smtp.mail.test:587", "hostname must not include a port; set EMAIL_SMTP_PORT instead"),
+        ("smtp.mail.test/relay", "hostname must not contain a path"),
+        ("smtp..mail.test", "hostname 'smtp..mail.test' has an empty label"),
+        ("smtp_relay.mail.test", "hostname must not contain '_'"),
+        ("-smtp.mail.test", "hostname label '-smtp' must not start or end with '-'"),
+        ("192.0.2.300", "'192.0.2.300' is not a valid IPv4 address"),
+    ] {
+        assert_eq!(
+            parse_with_host(host),
+            Err(format!("EMAIL_SMTP_HOST='{}' ({})", host, reason)),
+            "{}",
+            host
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Tests for rejecting SMTP port 0 and warning about uncommon SMTP ports.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+use config_generator::parse_authn_file;
+
+#[test]
+fn port_zero_is_rejected_before_writing() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=0"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("invalid: EMAIL_SMTP_PORT='0' (must be between 1 and 65535)"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn uncommon_port_warns_but_generates() {
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=5870"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(
+        message.contains("Warning: EMAIL_SMTP_PORT=5870 is not a common SMTP port (25, 465, 587, 2525); check that it is right"),
+        "{}",
+        message
+    );
+    assert!(workspace.read("config.textproto").contains("smtp_port: 5870"));
+}
+
+#[test]
+fn common_ports_are_not_warned_about() {
+    for port in ["25", "465", "587", "2525"] {
+        let authn = parse_authn_file(&AUTHN.replace("EMAIL_SMTP_PORT=587", &format!("EMAIL_SMTP_PORT={}", port)))
+            .expect("authn file parses");
+        assert!(authn.warnings.is_empty(), "{}: {:?}", port, authn.warnings);
+    }
+}
@@ -0,0 +1,99 @@
+//! Tests for `EMAIL_SMTP_SECURITY` and the email block's SMTP encryption field.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN};
+use config_generator::{parse_authn_file, SmtpSecurity};
+use prost::Message;
+use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+use prost_reflect::prost_types::{EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
+/// The descriptor set embedded in the binary
+const EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+/// Write the embedded descriptor set with an `SmtpEncryption smtp_encryption = 5` field added to
+/// `EmailConfig`, returning its path
+fn write_schema_with_encryption(workspace: &Workspace) -> String {
+    let mut set = FileDescriptorSet::decode(EMBEDDED).unwrap();
+    let file = set.file.iter_mut().find(|file| file.message_type.iter().any(|message| message.name() == "EmailConfig")).unwrap();
+    file.enum_type.push(EnumDescriptorProto {
+        name: Some("SmtpEncryption".to_string()),
+        value: ["SMTP_ENCRYPTION_NONE", "SMTP_ENCRYPTION_STARTTLS", "SMTP_ENCRYPTION_TLS"]
+            .iter()
+            .enumerate()
+            .map(|(number, name)| EnumValueDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number as i32),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    });
+    let email = file.message_type.iter_mut().find(|message| message.name() == "EmailConfig").unwrap();
+    let mut field = FieldDescriptorProto {
+        name: Some("smtp_encryption".to_string()),
+        number: Some(5),
+        type_name: Some(".config.SmtpEncryption".to_string()),
+        ..Default::default()
+    };
+    field.set_label(Label::Optional);
+    field.set_type(Type::Enum);
+    email.field.push(field);
+
+    let path = workspace.path("descriptors.bin");
+    std::fs::write(&path, set.encode_to_vec()).unwrap();
+    path_arg(&path)
+}
+
+fn authn_with(port: &str, security: Option<&str>) -> String {
+    let mut authn = AUTHN.replace("EMAIL_SMTP_PORT=587", &format!("EMAIL_SMTP_PORT={}", port));
+    if let Some(security) = security {
+        authn.push_str(&format!("EMAIL_SMTP_SECURITY={}\n", security));
+    }
+    authn
+}
+
+#[test]
+fn security_defaults_from_the_port() {
+    let security = |port| parse_authn_file(&authn_with(port, None)).unwrap().email.unwrap().smtp_security;
+
+    assert_eq!(security("465"), Some(SmtpSecurity::Tls));
+    assert_eq!(security("587"), Some(SmtpSecurity::StartTls));
+    assert_eq!(security("2525"), None);
+}
+
+#[test]
+fn explicit_security_overrides_the_port() {
+    let authn = parse_authn_file(&authn_with("465", Some("none"))).unwrap();
+
+    assert_eq!(authn.email.unwrap().smtp_security, Some(SmtpSecurity::None));
+}
+
+#[test]
+fn unknown_security_is_a_parse_error() {
+    let message = parse_authn_file(&authn_with("587", Some("ssl"))).err().expect("unknown mode is rejected").to_string();
+
+    assert!(message.contains("invalid: EMAIL_SMTP_SECURITY='ssl' (must be one of starttls, tls, none)"), "{}", message);
+}
+
+#[test]
+fn security_is_emitted_when_the_schema_defines_it() {
+    let workspace = Workspace::with_authn(&authn_with("465", None));
+    let schema = write_schema_with_encryption(&workspace);
+
+    let output = workspace.generate(&["--descriptor-set", &schema]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_encryption: SMTP_ENCRYPTION_TLS"), "{}", config);
+}
+
+#[test]
+fn security_is_omitted_when_the_schema_lacks_it() {
+    let workspace = Workspace::with_authn(&authn_with("587", Some("starttls")));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!workspace.read("config.textproto").contains("smtp_encryption"));
+}
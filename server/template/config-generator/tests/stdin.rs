@@ -0,0 +1,60 @@
+//! Tests for reading the authn file or template from stdin with `-`.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+
+/// Positional arguments with the authn file replaced by `-`
+fn args_with_authn_from_stdin(workspace: &Workspace) -> Vec<String> {
+    let mut args = workspace.default_args();
+    args[1] = "-".to_string();
+    args
+}
+
+#[test]
+fn authn_file_from_stdin() {
+    let workspace = Workspace::new();
+    std::fs::remove_file(workspace.path(".authn")).expect("remove authn file");
+
+    let output = workspace.run_with_stdin(&args_with_authn_from_stdin(&workspace), AUTHN);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("GOCSPX-test-client-secret"));
+}
+
+#[test]
+fn template_from_stdin() {
+    let workspace = Workspace::new();
+    let mut args = workspace.default_args();
+    args[0] = "-".to_string();
+
+    let output = workspace.run_with_stdin(&args, TEMPLATE);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("client_id: \"test-client-id.apps.googleusercontent.com\""));
+}
+
+#[test]
+fn empty_stdin_reports_missing_keys() {
+    let workspace = Workspace::new();
+
+    let output = workspace.run_with_stdin(&args_with_authn_from_stdin(&workspace), "");
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("invalid authn file: missing: "), "{}", message);
+    assert!(message.contains("EMAIL_SMTP_HOST"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn both_inputs_from_stdin_are_rejected() {
+    let workspace = Workspace::new();
+    let output_paths = [path_arg(&workspace.path("config.textproto")), path_arg(&workspace.path("secrets.textproto"))];
+    let args = ["-", "-", output_paths[0].as_str(), output_paths[1].as_str()];
+
+    let output = workspace.run_with_stdin(&args, AUTHN);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("only one of <template-file> and <authn-file> can be read from stdin"), "{}", stderr(&output));
+}
@@ -0,0 +1,71 @@
+//! Tests for warnings about unknown authn keys and `--strict`, which turns every warning into an error.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+
+const UNKNOWN_KEY: &str = "EMAIL_SMTP_HOSTNAME is not an authn key the generator reads, and no #if in the template tests it; ignored";
+
+#[test]
+fn unknown_key_passes_with_a_warning() {
+    let workspace = Workspace::with_authn(&format!("{}EMAIL_SMTP_HOSTNAME=smtp.typo.test\n", AUTHN));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains(&format!("Warning: {}\n", UNKNOWN_KEY)), "{}", stderr(&output));
+}
+
+#[test]
+fn unknown_key_fails_under_strict() {
+    let workspace = Workspace::with_authn(&format!("{}EMAIL_SMTP_HOSTNAME=smtp.typo.test\n", AUTHN));
+
+    let output = workspace.generate(&["--strict"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains(&format!("--strict: 1 warning(s) treated as errors:\n  {}", UNKNOWN_KEY)), "{}", message);
+    assert!(!message.contains("Warning:"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn key_tested_by_the_template_is_not_unknown() {
+    let workspace = Workspace::with_authn(&format!("{}SEND_EMAIL=1\n", AUTHN));
+    workspace.write("config.textproto.template", &TEMPLATE.replacen("email {}", "#if SEND_EMAIL\nemail {}\n#endif", 1));
+
+    let output = workspace.generate(&["--strict"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("SEND_EMAIL"), "{}", stderr(&output));
+}
+
+#[test]
+fn every_kind_of_warning_is_listed() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-secret\n",
+        AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=5870")
+    ));
+    workspace.write("config.textproto.template", &TEMPLATE.replacen("email {}\n", "", 1));
+
+    let output = workspace.generate(&["--strict"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("--strict: 3 warning(s) treated as errors:"), "{}", message);
+    assert!(message.contains("\n  EMAIL_SMTP_PORT=5870 is not a common SMTP port"), "{}", message);
+    assert!(message.contains("\n  the template has no auth.oauth_providers entry \"github\""), "{}", message);
+    // Only --verbose prints this one, but --strict counts it as well
+    assert!(message.contains("\n  the template has no email block, so the EMAIL_* settings were not used"), "{}", message);
+}
+
+#[test]
+fn clean_run_passes_strict() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--strict"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+}
@@ -0,0 +1,121 @@
+//! Tests for JSON and YAML authn files, picked by the authn file's extension.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+use config_generator::{parse_authn_as, AuthnFormat, GenError};
+
+/// The default authn file's values as JSON
+const AUTHN_JSON: &str = r#"{
+  "oauth_providers": {
+    "google": {
+      "client_id": "test-client-id.apps.googleusercontent.com",
+      "client_secret": "GOCSPX-test-client-secret"
+    }
+  },
+  "email": {
+    "smtp_host": "smtp.mail.test",
+    "smtp_port": 587,
+    "smtp_username": "mailer@mail.test",
+    "smtp_password": "smtp-test-password",
+    "sender_name": "TrailBase Test",
+    "sender_address": "noreply@mail.test"
+  }
+}
+"#;
+
+/// The default authn file's values as YAML
+const AUTHN_YAML: &str = "\
+# Deployment credentials
+oauth_providers:
+  google:
+    client_id: test-client-id.apps.googleusercontent.com
+    client_secret: 'GOCSPX-test-client-secret'
+email:
+  smtp_host: smtp.mail.test
+  smtp_port: 587  # submission
+  smtp_username: mailer@mail.test
+  smtp_password: \"smtp-test-password\"
+  sender_name: TrailBase Test
+  sender_address: noreply@mail.test
+";
+
+/// Generate from `authn` written to `name`, returning the generator's output
+fn generate_from(workspace: &Workspace, name: &str, authn: &str) -> std::process::Output {
+    workspace.write(name, authn);
+    let mut args = workspace.default_args();
+    args[1] = path_arg(&workspace.path(name));
+    workspace.run(&args)
+}
+
+#[test]
+fn json_and_yaml_match_the_key_value_format() {
+    let reference = Workspace::new();
+    let output = reference.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for (name, authn) in [("authn.json", AUTHN_JSON), ("authn.yaml", AUTHN_YAML), ("authn.YML", AUTHN_YAML)] {
+        let workspace = Workspace::new();
+        let output = generate_from(&workspace, name, authn);
+
+        assert!(output.status.success(), "{}: {}", name, stderr(&output));
+        assert_eq!(workspace.read("config.textproto"), reference.read("config.textproto"), "{}", name);
+        assert_eq!(workspace.read("secrets/secrets.textproto"), reference.read("secrets/secrets.textproto"), "{}", name);
+    }
+}
+
+#[test]
+fn malformed_json_points_at_the_line_and_column() {
+    let workspace = Workspace::new();
+    let authn = AUTHN_JSON.replace("\"smtp_port\": 587,", "\"smtp_port\": 587");
+
+    let output = generate_from(&workspace, "authn.json", &authn);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("invalid JSON authn file at line 11, column 5: expected '}' or ',' after a value, found '\"'"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn unknown_yaml_field_points_at_the_key() {
+    let workspace = Workspace::new();
+    let authn = AUTHN_YAML.replace("  smtp_host:", "  smtp_hots:");
+
+    let output = generate_from(&workspace, "authn.yml", &authn);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("invalid YAML authn file at line 7, column 3: unknown field 'smtp_hots' in email"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn structured_files_are_checked_like_key_value_files() {
+    let authn = "auth_mode: oauth\noauth_providers:\n  github:\n    client_id: gh-client-id\n";
+
+    match parse_authn_as(authn, AuthnFormat::Yaml, std::iter::empty()) {
+        Err(GenError::Authn { missing, invalid, .. }) => {
+            assert_eq!(missing, ["GITHUB_OAUTH_CLIENT_SECRET"]);
+            assert!(invalid.is_empty());
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("incomplete authn file parsed"),
+    }
+}
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(AuthnFormat::from_path("deploy/authn.json"), AuthnFormat::Json);
+    assert_eq!(AuthnFormat::from_path("authn.yaml"), AuthnFormat::Yaml);
+    assert_eq!(AuthnFormat::from_path("authn.yml"), AuthnFormat::Yaml);
+    assert_eq!(AuthnFormat::from_path(".authn"), AuthnFormat::KeyValue);
+    assert_eq!(AuthnFormat::from_path("-"), AuthnFormat::KeyValue);
+}
@@ -0,0 +1,52 @@
+//! Tests for `#if KEY ... #endif` blocks in the template.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+
+/// The shipped template with its email block wrapped in a conditional
+fn conditional_template() -> String {
+    TEMPLATE.replacen("email {}", "#if SEND_EMAIL\nemail {}\n#endif", 1)
+}
+
+#[test]
+fn email_block_included_when_key_is_set() {
+    let workspace = Workspace::with_authn(&format!("{}SEND_EMAIL=1\n", AUTHN));
+    workspace.write("config.textproto.template", &conditional_template());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(!config.contains("#if") && !config.contains("#endif"), "{}", config);
+}
+
+#[test]
+fn email_block_dropped_when_key_is_absent() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.template", &conditional_template());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(!config.contains("email {"), "{}", config);
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+}
+
+#[test]
+fn unmatched_directives_are_rejected() {
+    let workspace = Workspace::new();
+
+    workspace.write("config.textproto.template", &format!("#if SEND_EMAIL\n{}", TEMPLATE));
+    let output = workspace.generate(&[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 1: '#if SEND_EMAIL' is never closed"), "{}", stderr(&output));
+
+    workspace.write("config.textproto.template", &format!("{}#endif\n", TEMPLATE));
+    let output = workspace.generate(&[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("'#endif' without matching '#if'"), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+}
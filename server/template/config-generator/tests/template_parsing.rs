@@ -0,0 +1,48 @@
+//! Tests for filling the template through the descriptor pool rather than by string replacement.
+
+mod common;
+
+use common::{stderr, Workspace};
+
+/// A hand-formatted template: reordered fields, an `email` block spanning lines with a template
+/// of its own, and irregular whitespace around the provider block
+const LOOSE_TEMPLATE: &str = r#"
+auth { oauth_providers { key: "google"
+    value { provider_id: GOOGLE   client_secret:"<REDACTED>" client_id : '<GOOGLE_OAUTH_CLIENT_ID>' } } }
+email {
+  password_reset_template { subject: "Reset your password" }
+}
+"#;
+
+#[test]
+fn loosely_formatted_template_is_filled() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.template", LOOSE_TEMPLATE);
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains("client_id: \"test-client-id.apps.googleusercontent.com\""), "{}", config);
+    assert!(config.contains("smtp_host: \"smtp.mail.test\""), "{}", config);
+    assert!(config.contains("smtp_password: \"<REDACTED>\""), "{}", config);
+    assert!(config.contains("subject: \"Reset your password\""), "{}", config);
+    assert!(!config.contains("_OAUTH_CLIENT_ID>"), "{}", config);
+}
+
+#[test]
+fn template_typo_is_a_parse_error() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "config.textproto.template",
+        &common::TEMPLATE.replace("client_secret: \"<REDACTED>\"", "client_secert: \"<REDACTED>\""),
+    );
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("Error in template file"), "{}", message);
+    assert!(message.contains("client_secert"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
@@ -0,0 +1,44 @@
+//! Tests for `--time-budget`, which aborts runs that take too long.
+
+mod common;
+
+use common::{stderr, Workspace};
+use std::time::{Duration, Instant};
+
+#[test]
+fn slow_pre_hook_is_killed_at_the_deadline() {
+    let workspace = Workspace::new();
+
+    let started = Instant::now();
+    let output = workspace.generate(&["--time-budget", "0.5", "--pre-hook", "sleep 10"]);
+
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+    assert_eq!(output.status.code(), Some(5));
+    let message = stderr(&output);
+    assert!(
+        message.contains("time budget of 0.5s exceeded during pre-hook 'sleep 10'"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn run_within_budget_succeeds() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--time-budget", "30", "--pre-hook", "true"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+}
+
+#[test]
+fn invalid_budget_is_rejected() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--time-budget", "soon"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--time-budget must be a positive number of seconds, got 'soon'"));
+}
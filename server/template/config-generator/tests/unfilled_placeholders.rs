@@ -0,0 +1,66 @@
+//! Tests for rejecting template placeholders that survive into the generated config.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+
+/// The default template with a GitHub block after Google's
+fn template_with_github() -> String {
+    let github = "{\n    key: \"github\"\n    value {\n      client_id: \"<GITHUB_OAUTH_CLIENT_ID>\"\n      client_secret: \"<REDACTED>\"\n      provider_id: GITHUB\n    }\n  }";
+    let template = TEMPLATE.replacen("  }]\n}", &format!("  }}, {}]\n}}", github), 1);
+    assert_ne!(template, TEMPLATE, "template anchor not found");
+    template
+}
+
+#[test]
+fn provider_block_without_credentials_is_rejected() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.template", &template_with_github());
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains(r#"template placeholders were not filled: auth.oauth_providers["github"].client_id = "<GITHUB_OAUTH_CLIENT_ID>""#),
+        "{}",
+        message
+    );
+    assert!(!message.contains("client_secret"), "{}", message);
+    assert!(!workspace.exists("config.textproto"));
+}
+
+#[test]
+fn redacted_outside_secret_fields_is_rejected() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "config.textproto.template",
+        &TEMPLATE.replace("site_url: \"http://localhost:7000\"", "site_url: \"<REDACTED>\""),
+    );
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains(r#"server.site_url = "<REDACTED>""#), "{}", stderr(&output));
+}
+
+#[test]
+fn filled_provider_passes() {
+    let workspace = Workspace::with_authn(&format!("{}GITHUB_OAUTH_CLIENT_ID=gh-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-secret\n", AUTHN));
+    workspace.write("config.textproto.template", &template_with_github());
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn no_validate_skips_the_placeholder_check() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto.template", &template_with_github());
+
+    let output = workspace.generate(&["--no-validate"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("<GITHUB_OAUTH_CLIENT_ID>"));
+}
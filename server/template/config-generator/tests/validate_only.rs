@@ -0,0 +1,98 @@
+//! Tests for `--validate-only`, which checks the authn file without a template or outputs.
+
+mod common;
+
+use common::{path_arg, stderr, stdout, Workspace, AUTHN};
+
+/// Run `--validate-only` on the workspace's authn file, plus `extra`
+fn validate(workspace: &Workspace, extra: &[&str]) -> std::process::Output {
+    let mut args = vec!["--validate-only".to_string()];
+    args.extend(extra.iter().map(|arg| arg.to_string()));
+    args.push(path_arg(&workspace.path(".authn")));
+    workspace.run(&args)
+}
+
+#[test]
+fn valid_file_is_summarized_without_secrets() {
+    let workspace = Workspace::with_authn(&format!("{}CONFIG_server.site_url=https://app.example.com\nDEPLOY_REGION=eu\n", AUTHN));
+
+    let output = validate(&workspace, &[]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "auth mode: both\n\
+         oauth providers: google\n\
+         email: smtp.mail.test:587 as TrailBase Test <noreply@mail.test>\n\
+         config values: server.site_url\n\
+         vault secrets: TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET, TRAIL_EMAIL_SMTP_PASSWORD\n\
+         keys only #if conditionals can use: DEPLOY_REGION\n"
+    );
+    assert!(stderr(&output).is_empty(), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+    assert!(!workspace.exists("secrets"));
+}
+
+#[test]
+fn invalid_values_fail_before_anything_is_printed() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}CONFIG_server.site_ur=https://app.example.com\n",
+        AUTHN.replace("EMAIL_SMTP_HOST=smtp.mail.test\n", "").replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=port")
+    ));
+
+    let output = validate(&workspace, &[]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    let message = stderr(&output);
+    assert!(message.contains("missing: EMAIL_SMTP_HOST"), "{}", message);
+    assert!(message.contains("EMAIL_SMTP_PORT='port'"), "{}", message);
+
+    // Schema lookups run once the values themselves are valid
+    workspace.write(".authn", &format!("{}CONFIG_server.site_ur=https://app.example.com\n", AUTHN));
+    let output = validate(&workspace, &[]);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("config.ServerConfig has no field 'site_ur'"), "{}", stderr(&output));
+}
+
+#[test]
+fn quiet_and_strict_leave_only_the_exit_code() {
+    let workspace = Workspace::new();
+    let output = validate(&workspace, &["--quiet"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty() && stderr(&output).is_empty(), "{}{}", stdout(&output), stderr(&output));
+
+    let workspace = Workspace::with_authn(&AUTHN.replace("EMAIL_SMTP_PORT=587", "EMAIL_SMTP_PORT=5870"));
+    let output = validate(&workspace, &["--strict"]);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("--strict: 1 warning(s) treated as errors"), "{}", stderr(&output));
+}
+
+#[test]
+fn template_and_outputs_are_optional_but_not_read() {
+    let workspace = Workspace::new();
+
+    // All four arguments are accepted as for a normal run; the template isn't read
+    let output = workspace.run(&[
+        "--validate-only".to_string(),
+        path_arg(&workspace.path("missing.template")),
+        path_arg(&workspace.path(".authn")),
+        path_arg(&workspace.path("config.textproto")),
+        path_arg(&workspace.path("secrets/secrets.textproto")),
+    ]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(!workspace.exists("config.textproto"));
+
+    let output = workspace.run_with_env(&["--validate-only"], &[("TRAIL_GEN_AUTHN", path_arg(&workspace.path(".authn")))]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    for flag in ["--check", "--dry-run"] {
+        let output = validate(&workspace, &[flag]);
+        assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+        assert!(
+            stderr(&output).contains(&format!("--validate-only cannot be combined with {}, which needs generated outputs", flag)),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
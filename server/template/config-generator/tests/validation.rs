@@ -0,0 +1,33 @@
+//! Tests for the post-generation validation pass and its `--no-validate` opt-out.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+const TRICKY_SENDER_NAME: &str = "O'Brien \"The Great\"";
+
+fn tricky_authn() -> String {
+    AUTHN.replace(
+        "EMAIL_SENDER_NAME=TrailBase Test",
+        &format!("EMAIL_SENDER_NAME={}", TRICKY_SENDER_NAME),
+    )
+}
+
+#[test]
+fn valid_outputs_pass_validation() {
+    let workspace = Workspace::new();
+    let output = workspace.generate(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.exists("config.textproto"));
+    assert!(workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn no_validate_skips_the_pass() {
+    let workspace = Workspace::with_authn(&tricky_authn());
+    let output = workspace.generate(&["--no-validate"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = workspace.read("config.textproto");
+    assert!(config.contains(r#"sender_name: "O\'Brien \"The Great\"""#), "{}", config);
+}
@@ -0,0 +1,67 @@
+//! Tests for `@path` authn values, which are read from the named file.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN};
+
+/// The default authn file with `key`'s value replaced by `value`
+fn authn_with(key: &str, value: &str) -> String {
+    AUTHN
+        .lines()
+        .map(|line| if line.starts_with(&format!("{}=", key)) { format!("{}={}", key, value) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn secret_is_read_from_its_file_without_the_trailing_newline() {
+    let workspace = Workspace::new();
+    workspace.write("run/google", "GOCSPX-from-a-file\n");
+    let reference = format!("@{}", path_arg(&workspace.path("run/google")));
+    workspace.write(".authn", &authn_with("GOOGLE_OAUTH_CLIENT_SECRET", &reference));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("value: \"GOCSPX-from-a-file\"\n"), "{}", vault);
+}
+
+#[test]
+fn any_key_can_use_a_file() {
+    let workspace = Workspace::new();
+    workspace.write("smtp-host", "smtp.from-file.test\r\n");
+    workspace.write(".authn", &authn_with("EMAIL_SMTP_HOST", "@smtp-host"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("config.textproto").contains("smtp_host: \"smtp.from-file.test\""));
+}
+
+#[test]
+fn missing_file_is_reported_with_its_key() {
+    let workspace = Workspace::with_authn(&authn_with("GOOGLE_OAUTH_CLIENT_SECRET", "@/run/secrets/does-not-exist"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("invalid: GOOGLE_OAUTH_CLIENT_SECRET (cannot read '@/run/secrets/does-not-exist': "),
+        "{}",
+        message
+    );
+    assert!(!message.contains("missing"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn single_quoted_at_sign_is_literal() {
+    let workspace = Workspace::with_authn(&authn_with("EMAIL_SMTP_PASSWORD", "'@not-a-file'"));
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace.read("secrets/secrets.textproto").contains("value: \"@not-a-file\""));
+}
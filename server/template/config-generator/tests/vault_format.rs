@@ -0,0 +1,89 @@
+//! Tests for `--vault-format binary`, which writes the vault as the message's binary wire form.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{encode_vault, generate_with, parse_authn_file, GenerateOptions, Schema, Vault, VaultFormat};
+use prost::Message;
+use std::fs;
+
+/// The vault the workspace holds, decoded from its binary form
+fn read_binary_vault(workspace: &Workspace) -> Vault {
+    let bytes = fs::read(workspace.path("secrets/secrets.textproto")).expect("vault exists");
+    Vault::decode(bytes.as_slice()).expect("vault decodes as config.Vault")
+}
+
+#[test]
+fn binary_vault_decodes_to_the_generated_secrets() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--vault-format", "binary"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = read_binary_vault(&workspace);
+    assert_eq!(vault.secrets.len(), 2, "{:?}", vault.secrets.keys());
+    assert_eq!(
+        vault.secrets.get("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET").map(String::as_str),
+        Some("GOCSPX-test-client-secret")
+    );
+    assert_eq!(vault.secrets.get("TRAIL_EMAIL_SMTP_PASSWORD").map(String::as_str), Some("smtp-test-password"));
+    // The config is textproto either way
+    assert!(workspace.read("config.textproto").starts_with("# Auto-generated config.Config textproto\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn binary_vault_is_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--vault-format", "binary"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mode = fs::metadata(workspace.path("secrets/secrets.textproto")).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode, 0o600);
+}
+
+#[test]
+fn library_encodes_the_same_secrets_in_either_format() {
+    let schema = Schema::load(None).expect("embedded schema");
+    let authn = parse_authn_file(AUTHN).expect("complete authn file parses");
+    let output = generate_with(&schema, TEMPLATE, &authn, &GenerateOptions::default()).expect("generation succeeds");
+
+    let binary = encode_vault(&schema, &output.secrets, VaultFormat::Binary, Some("ignored header")).expect("vault encodes");
+    let text = encode_vault(&schema, &output.secrets, VaultFormat::Textproto, None).expect("vault encodes");
+
+    let decoded = Vault::decode(binary.as_slice()).expect("vault decodes");
+    assert_eq!(decoded.secrets.into_iter().collect::<std::collections::BTreeMap<_, _>>(), output.secrets);
+    assert_eq!(String::from_utf8(text).unwrap(), output.vault);
+}
+
+#[test]
+fn check_and_rotate_read_the_existing_binary_vault() {
+    let workspace = Workspace::new();
+    assert!(workspace.generate(&["--vault-format", "binary"]).status.success());
+
+    let output = workspace.generate(&["--vault-format", "binary", "--check"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    workspace.write(
+        ".authn",
+        &AUTHN.replace("EMAIL_SMTP_PASSWORD=smtp-test-password", "EMAIL_SMTP_PASSWORD=rotated-smtp-password"),
+    );
+    let output = workspace.generate(&["--vault-format", "binary", "--rotate"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = read_binary_vault(&workspace);
+    assert_eq!(vault.secrets.get("TRAIL_EMAIL_SMTP_PASSWORD").map(String::as_str), Some("rotated-smtp-password"));
+    assert_eq!(vault.secrets.get("TRAIL_EMAIL_SMTP_PASSWORD_PREVIOUS").map(String::as_str), Some("smtp-test-password"));
+}
+
+#[test]
+fn unknown_format_is_rejected() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--vault-format", "json"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--vault-format must be 'textproto' or 'binary', got 'json'"), "{}", stderr(&output));
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
@@ -0,0 +1,77 @@
+//! Tests for `--vault-key-map`, which renames individual secrets in the vault.
+
+mod common;
+
+use common::{path_arg, stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate_with, parse_authn_file, parse_vault_key_map, GenerateOptions, Schema};
+
+/// Run with `map` as the vault key map
+fn generate_with_map(workspace: &Workspace, map: &str) -> std::process::Output {
+    workspace.write("vault-keys", map);
+    workspace.generate(&["--vault-key-map", &path_arg(&workspace.path("vault-keys"))])
+}
+
+#[test]
+fn email_password_key_is_renamed() {
+    let workspace = Workspace::new();
+
+    let output = generate_with_map(&workspace, "# TrailBase 0.x naming\nEMAIL_SMTP_PASSWORD=TRAIL_SMTP_PASSWORD\n");
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(
+        vault.contains("key: \"TRAIL_SMTP_PASSWORD\"\n  value: \"smtp-test-password\""),
+        "{}",
+        vault
+    );
+    assert!(!vault.contains("TRAIL_EMAIL_SMTP_PASSWORD"), "{}", vault);
+    // Unmapped secrets keep their default names
+    assert!(vault.contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\""), "{}", vault);
+}
+
+#[test]
+fn mapped_provider_overrides_the_key_template() {
+    let authn = parse_authn_file(AUTHN).expect("complete authn file parses");
+    let options = GenerateOptions {
+        vault_key_template: "OAUTH_{PROVIDER}_SECRET".to_string(),
+        vault_keys: parse_vault_key_map("GOOGLE_OAUTH_CLIENT_SECRET=GOOGLE_SECRET").expect("map parses"),
+        ..GenerateOptions::default()
+    };
+
+    let output = generate_with(&Schema::load(None).expect("embedded schema"), TEMPLATE, &authn, &options)
+        .expect("generation succeeds");
+
+    assert_eq!(output.secrets.get("GOOGLE_SECRET").map(String::as_str), Some("GOCSPX-test-client-secret"));
+    assert!(!output.secrets.contains_key("OAUTH_GOOGLE_SECRET"), "{:?}", output.secrets.keys());
+}
+
+#[test]
+fn non_secret_keys_are_rejected() {
+    let workspace = Workspace::new();
+
+    let output = generate_with_map(&workspace, "EMAIL_SMTP_PASSWORD=SMTP_PASSWORD\nEMAIL_SMTP_HOST=SMTP_HOST\n");
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("line 2: 'EMAIL_SMTP_HOST' is not a secret authn key (EMAIL_SMTP_PASSWORD, EMAIL_<NAME>_SMTP_PASSWORD or <PROVIDER>_OAUTH_CLIENT_SECRET)"),
+        "{}",
+        message
+    );
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
+
+#[test]
+fn two_secrets_under_one_key_are_rejected() {
+    let workspace = Workspace::new();
+
+    let output = generate_with_map(&workspace, "EMAIL_SMTP_PASSWORD=TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\n");
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("vault key 'TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET' is used for more than one secret"),
+        "{}",
+        message
+    );
+}
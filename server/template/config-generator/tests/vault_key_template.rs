@@ -0,0 +1,45 @@
+//! Tests for `--vault-key-template`, which names provider client secrets in the vault.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN};
+
+#[test]
+fn default_template_matches_trailbase_naming() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(workspace
+        .read("secrets/secrets.textproto")
+        .contains("key: \"TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET\""));
+}
+
+#[test]
+fn custom_template_interpolates_provider() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--vault-key-template", "OAUTH_{PROVIDER}_SECRET"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault = workspace.read("secrets/secrets.textproto");
+    assert!(vault.contains("key: \"OAUTH_GOOGLE_SECRET\""), "{}", vault);
+    assert!(vault.contains("value: \"GOCSPX-test-client-secret\""), "{}", vault);
+    assert!(!vault.contains("TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET"), "{}", vault);
+}
+
+#[test]
+fn template_without_provider_is_rejected_for_several_providers() {
+    let workspace = Workspace::with_authn(&format!(
+        "{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-client-secret\n",
+        AUTHN
+    ));
+
+    let output = workspace.generate(&["--vault-key-template", "OAUTH_CLIENT_SECRET"]);
+
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("must contain {PROVIDER} when 2 OAuth providers are configured"), "{}", message);
+    assert!(!workspace.exists("secrets/secrets.textproto"));
+}
@@ -0,0 +1,62 @@
+//! Tests that vault secrets are written in a stable, key-sorted order.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate, parse_authn_file};
+
+const EXTRA_PROVIDERS: &str = "\
+GITHUB_OAUTH_CLIENT_ID=gh-client-id
+GITHUB_OAUTH_CLIENT_SECRET=gh-client-secret
+DISCORD_OAUTH_CLIENT_ID=discord-client-id
+DISCORD_OAUTH_CLIENT_SECRET=discord-client-secret
+";
+
+#[test]
+fn secrets_are_sorted_by_key() {
+    let authn = parse_authn_file(&format!("{}{}", AUTHN, EXTRA_PROVIDERS)).expect("authn file parses");
+
+    let output = generate(TEMPLATE, &authn).expect("generation succeeds");
+
+    let keys: Vec<&str> = output
+        .vault
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("key: \""))
+        .map(|key| key.trim_end_matches('"'))
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            "TRAIL_AUTH_OAUTH_PROVIDERS_DISCORD_CLIENT_SECRET",
+            "TRAIL_AUTH_OAUTH_PROVIDERS_GITHUB_CLIENT_SECRET",
+            "TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET",
+            "TRAIL_EMAIL_SMTP_PASSWORD",
+        ]
+    );
+}
+
+#[test]
+fn insertion_order_does_not_change_the_vault() {
+    let mut authn = parse_authn_file(&format!("{}{}", AUTHN, EXTRA_PROVIDERS)).expect("authn file parses");
+    let sorted = generate(TEMPLATE, &authn).expect("generation succeeds").vault;
+
+    authn.oauth_providers.reverse();
+    let reversed = generate(TEMPLATE, &authn).expect("generation succeeds").vault;
+
+    assert_eq!(reversed, sorted);
+}
+
+#[test]
+fn reordered_authn_file_writes_identical_bytes() {
+    let forward = Workspace::with_authn(&format!("{}{}", AUTHN, EXTRA_PROVIDERS));
+    let mut lines: Vec<&str> = AUTHN.lines().chain(EXTRA_PROVIDERS.lines()).collect();
+    lines.reverse();
+    let backward = Workspace::with_authn(&format!("{}\n", lines.join("\n")));
+
+    for workspace in [&forward, &backward] {
+        let output = workspace.generate(&[]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    assert_eq!(backward.read("secrets/secrets.textproto"), forward.read("secrets/secrets.textproto"));
+}
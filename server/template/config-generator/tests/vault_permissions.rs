@@ -0,0 +1,62 @@
+//! Tests that files holding plaintext secrets are written readable by their owner only.
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{path_arg, stderr, Workspace};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+fn mode(workspace: &Workspace, name: &str) -> u32 {
+    fs::metadata(workspace.path(name)).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn new_vault_is_owner_only() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode(&workspace, "secrets/secrets.textproto"), 0o600);
+}
+
+#[test]
+fn existing_world_readable_vault_is_tightened() {
+    let workspace = Workspace::new();
+    workspace.write("secrets/secrets.textproto", "# stale\n");
+    fs::set_permissions(workspace.path("secrets/secrets.textproto"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode(&workspace, "secrets/secrets.textproto"), 0o600);
+    assert!(workspace.read("secrets/secrets.textproto").contains("smtp-test-password"));
+}
+
+#[test]
+fn config_keeps_default_permissions() {
+    let workspace = Workspace::new();
+    workspace.write("config.textproto", "");
+    fs::set_permissions(workspace.path("config.textproto"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode(&workspace, "config.textproto"), 0o644);
+}
+
+#[test]
+fn rendered_authn_file_is_owner_only() {
+    let workspace = Workspace::new();
+    workspace.write("authn.template", "GOOGLE_OAUTH_CLIENT_SECRET=${SECRET_FOR_TEST}\n");
+
+    let output = workspace.run_with_env(
+        &["--authn-template", &path_arg(&workspace.path("authn.template")), &path_arg(&workspace.path("rendered.authn"))],
+        &[("SECRET_FOR_TEST", "from-the-environment".to_string())],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(mode(&workspace, "rendered.authn"), 0o600);
+}
@@ -0,0 +1,97 @@
+//! Tests for `--verbose` logging of substitutions and unmatched authn values.
+
+mod common;
+
+use common::{stderr, Workspace, AUTHN, TEMPLATE};
+use config_generator::{generate, parse_authn_file, FillNote};
+
+#[test]
+fn verbose_logs_each_substitution() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&["--verbose"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(message.contains("set client_id for google\n"), "{}", message);
+    assert!(
+        message.contains(
+            "filled email block (smtp_host, smtp_port, smtp_username, smtp_password, sender_name, sender_address)\n"
+        ),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("wrote 2 vault secret(s): TRAIL_AUTH_OAUTH_PROVIDERS_GOOGLE_CLIENT_SECRET, TRAIL_EMAIL_SMTP_PASSWORD\n"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("Warning:"), "{}", message);
+}
+
+#[test]
+fn normal_runs_stay_quiet() {
+    let workspace = Workspace::new();
+
+    let output = workspace.generate(&[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(!message.contains("set client_id"), "{}", message);
+    assert!(!message.contains("vault secret(s)"), "{}", message);
+}
+
+#[test]
+fn provider_without_a_template_entry_is_warned_about() {
+    let workspace = Workspace::with_authn(&format!("{}GITHUB_OAUTH_CLIENT_ID=gh-client-id\nGITHUB_OAUTH_CLIENT_SECRET=gh-secret\n", AUTHN));
+
+    let output = workspace.generate(&["--verbose"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(
+        message.contains(
+            "Warning: the template has no auth.oauth_providers entry \"github\", so GITHUB_OAUTH_CLIENT_ID was not used"
+        ),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn overwritten_hardcoded_client_id_is_warned_about() {
+    let workspace = Workspace::new();
+    workspace.write(
+        "config.textproto.template",
+        &TEMPLATE.replace("client_id: \"<GOOGLE_OAUTH_CLIENT_ID>\"", "client_id: \"stale-id\""),
+    );
+
+    let output = workspace.generate(&["--verbose"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(
+        message.contains(
+            "Warning: auth.oauth_providers[\"google\"].client_id was \"stale-id\", not the placeholder <GOOGLE_OAUTH_CLIENT_ID>; overwritten"
+        ),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn library_notes_a_template_without_an_email_block() {
+    let authn = parse_authn_file(AUTHN).expect("complete authn file parses");
+    let template = TEMPLATE.replacen("email {}\n", "", 1);
+    assert_ne!(template, TEMPLATE, "template anchor not found");
+
+    let output = generate(&template, &authn).expect("generation succeeds");
+
+    assert_eq!(
+        output.notes,
+        [
+            FillNote::Filled("set client_id for google".to_string()),
+            FillNote::Unmatched("the template has no email block, so the EMAIL_* settings were not used".to_string()),
+        ]
+    );
+}